@@ -0,0 +1,253 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use crate::{generation_rng, stream_rng, tournament_child, Crossover, Fitness, Generator, GradedIndividual, Individual, Rates};
+
+// An individual with the number of generations its genetic material has been evolving.
+#[derive(Clone)]
+pub struct Aged<I>
+where
+    I: Individual + Send + Sync
+{
+    pub graded: GradedIndividual<I>,
+    pub age: usize,
+}
+
+// Age-Layered Population Structure (Hornby). The population is split into layers by age,
+// every age_gap generations the bottom layer is replaced by new random individuals, and
+// individuals that get too old for their layer move up to the next one (if they are good
+// enough to make it there). New material only ever competes against individuals of about
+// its own age, so the run keeps exploring instead of converging on one early winner.
+//
+// Each layer breeds from itself and the layer below it, with parents picked by binary
+// tournament. Offspring take the age of their oldest parent, and everyone gets a year
+// older every generation.
+pub struct Alps<I>
+where
+    I: Individual + Send + Sync
+{
+    pub layer_size: usize,
+    pub age_gap: usize,
+    // oldest age allowed in each layer, the top layer has no limit
+    pub age_limits: Vec<usize>,
+    pub rates: Rates,
+    pub seed: Option<u64>,
+    layers: Vec<Vec<Aged<I>>>,
+    generation: usize,
+}
+
+impl<I> Alps<I>
+where
+    I: Individual + Send + Sync
+{
+    // layer_count layers with the usual polynomial age limits, age_gap * 1, 2, 4, 9, 16, ...
+    pub fn new<G, F>(layer_count: usize, layer_size: usize, age_gap: usize, generator: &G, fitness: F) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        Self::build(layer_count, layer_size, age_gap, generator, fitness, None)
+    }
+
+    // Like new, but seeded, so the same seed always evolves the same way.
+    pub fn new_seeded<G, F>(layer_count: usize, layer_size: usize, age_gap: usize, generator: &G, fitness: F, seed: u64) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        Self::build(layer_count, layer_size, age_gap, generator, fitness, Some(seed))
+    }
+
+    fn build<G, F>(layer_count: usize, layer_size: usize, age_gap: usize, generator: &G, fitness: F, seed: Option<u64>) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let age_limits = (0..layer_count.saturating_sub(1)).map(|layer| age_gap * match layer {
+            0 => 1,
+            1 => 2,
+            n => n * n,
+        }).collect();
+        let mut alps = Alps{
+            layer_size,
+            age_gap,
+            age_limits,
+            rates: Rates::new(0.5, 0.5),
+            seed,
+            layers: (0..layer_count.max(1)).map(|_| Vec::new()).collect(),
+            generation: 0,
+        };
+        alps.layers[0] = alps.fresh(generator, &fitness.into(), &mut generation_rng(seed, 0));
+        alps
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    // youngest layer first, each sorted best first
+    pub fn layers(&self) -> &[Vec<Aged<I>>] {
+        &self.layers
+    }
+
+    pub fn best(&self) -> Option<&GradedIndividual<I>> {
+        self.layers.iter().filter_map(|layer| layer.first()).map(|aged| &aged.graded).max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+    }
+
+    pub fn evolve<G, C, F>(&mut self, generator: &G, crossover: &C, fitness: F)
+    where
+        G: Generator<I> + Send + Sync,
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        self.generation += 1;
+        let mut rng = generation_rng(self.seed, self.generation);
+        let base: u64 = rng.gen();
+
+        // every layer that has anyone in it (or below it) breeds a full layer of offspring
+        let slots: Vec<(usize, usize)> = (0..self.layers.len())
+            .filter(|&layer| !self.pool(layer).is_empty())
+            .flat_map(|layer| (0..self.layer_size).map(move |slot| (layer, slot)))
+            .collect();
+        let offspring: Vec<(usize, Aged<I>)> = slots.par_iter().enumerate().map(|(i, &(layer, _))| {
+            (layer, self.breed(layer, crossover, &fitness, &mut stream_rng(base, i)))
+        }).collect();
+
+        let mut candidates: Vec<Vec<Aged<I>>> = std::mem::take(&mut self.layers);
+        for layer in candidates.iter_mut() {
+            layer.iter_mut().for_each(|aged| aged.age += 1);
+        }
+        for (layer, child) in offspring {
+            candidates[layer].push(child);
+        }
+
+        // bottom up, whoever is too old for a layer competes for a place in the next one
+        let mut layers = Vec::with_capacity(candidates.len());
+        let mut too_old: Vec<Aged<I>> = Vec::new();
+        for (layer, mut pool) in candidates.into_iter().enumerate() {
+            pool.append(&mut too_old);
+            if let Some(&limit) = self.age_limits.get(layer) {
+                let (fits, old): (Vec<Aged<I>>, Vec<Aged<I>>) = pool.into_iter().partition(|aged| aged.age <= limit);
+                pool = fits;
+                too_old = old;
+            }
+            pool.par_sort_by(|a, b| b.graded.fitness.total_cmp(&a.graded.fitness));
+            pool.truncate(self.layer_size);
+            layers.push(pool);
+        }
+        self.layers = layers;
+
+        // the bottom layer starts over, its members move up if they are good enough
+        if self.generation.is_multiple_of(self.age_gap) {
+            let bottom = self.fresh(generator, &fitness, &mut rng);
+            let previous = std::mem::replace(&mut self.layers[0], bottom);
+            if self.layers.len() > 1 {
+                let next = &mut self.layers[1];
+                next.extend(previous);
+                next.par_sort_by(|a, b| b.graded.fitness.total_cmp(&a.graded.fitness));
+                next.truncate(self.layer_size);
+            }
+        }
+    }
+
+    fn fresh<G>(&self, generator: &G, fitness: &Fitness<I>, rng: &mut StdRng) -> Vec<Aged<I>>
+    where
+        G: Generator<I> + Send + Sync
+    {
+        let base: u64 = rng.gen();
+        let mut layer: Vec<Aged<I>> = (0..self.layer_size).into_par_iter().map(|slot| Aged{
+            graded: fitness.grade(generator.generate(&mut stream_rng(base, slot))),
+            age: 0,
+        }).collect();
+        layer.par_sort_by(|a, b| b.graded.fitness.total_cmp(&a.graded.fitness));
+        layer
+    }
+
+    // the individuals layer breeds from, itself and the layer below
+    fn pool(&self, layer: usize) -> Vec<&Aged<I>> {
+        let below = if layer > 0 { self.layers[layer - 1].iter() } else { [].iter() };
+        self.layers[layer].iter().chain(below).collect()
+    }
+
+    fn breed<C>(&self, layer: usize, crossover: &C, fitness: &Fitness<I>, r: &mut StdRng) -> Aged<I>
+    where
+        C: Crossover<I> + Send + Sync
+    {
+        let pool = self.pool(layer);
+        let graded: Vec<&GradedIndividual<I>> = pool.iter().map(|aged| &aged.graded).collect();
+        let (child, lineage) = tournament_child(&graded, self.rates, crossover, r);
+        // the age of the oldest parent
+        let age = match lineage.partner {
+            Some(partner) => pool[lineage.parent].age.max(pool[partner].age),
+            None => pool[lineage.parent].age,
+        };
+        Aged{
+            graded: fitness.grade(child),
+            age,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use rand::RngCore;
+    use crate::ScalarFitness;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn generate(r: &mut dyn RngCore) -> Number {
+        Number(r.gen_range(0.0..1.0))
+    }
+
+    fn average(a: &Number, b: &Number, _r: &mut dyn RngCore) -> Number {
+        Number((a.0 + b.0) / 2.0)
+    }
+
+    fn value() -> ScalarFitness<Number> {
+        Arc::new(|n: &Number| n.0)
+    }
+
+    fn values(alps: &Alps<Number>) -> Vec<Vec<f32>> {
+        alps.layers().iter().map(|layer| layer.iter().map(|aged| aged.graded.individual.0).collect()).collect()
+    }
+
+    #[test]
+    fn layers_keep_to_their_age_limits() {
+        let mut alps = Alps::new_seeded(3, 10, 3, &generate, value(), 1);
+        assert_eq!(alps.age_limits, vec![3, 6]);
+        for _ in 0..20 {
+            alps.evolve(&generate, &average, value());
+            for (layer, limit) in alps.layers().iter().zip(&alps.age_limits) {
+                assert!(layer.iter().all(|aged| aged.age <= *limit));
+            }
+            assert!(alps.layers().iter().all(|layer| layer.len() <= 10));
+            if alps.generation().is_multiple_of(3) {
+                // the bottom layer just started over
+                assert!(alps.layers()[0].iter().all(|aged| aged.age == 0));
+            }
+        }
+        assert!(alps.layers().iter().all(|layer| !layer.is_empty()));
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let run = || {
+            let mut alps = Alps::new_seeded(3, 10, 3, &generate, value(), 7);
+            for _ in 0..10 {
+                alps.evolve(&generate, &average, value());
+            }
+            values(&alps)
+        };
+        assert_eq!(run(), run());
+    }
+}
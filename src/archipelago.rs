@@ -0,0 +1,404 @@
+use rand::{Rng, RngCore};
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use rayon::ThreadPool;
+use std::sync::Arc;
+use crate::{by_fitness, generation_rng, stream_rng, Crossover, EvolveConfig, Fitness, Generator, GradedIndividual, Individual, Population};
+
+// Decides who migrates between islands and whom they replace. Islands are passed sorted as
+// EvolveConfig::sort leaves them, so only the best k may be in order under SortMode::TopK.
+pub trait MigrationPolicy<I>: Send + Sync
+where
+    I: Individual + Send + Sync
+{
+    // how many individuals leave an island of size at each migration
+    fn count(&self, size: usize) -> usize;
+
+    // indices of the count individuals that leave
+    fn emigrants(&self, island: &[GradedIndividual<I>], count: usize, rng: &mut dyn RngCore) -> Vec<usize>;
+
+    // indices of the count individuals that make room for the arrivals
+    fn replaced(&self, island: &[GradedIndividual<I>], count: usize, rng: &mut dyn RngCore) -> Vec<usize>;
+}
+
+// Who leaves an island.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emigrants {
+    Best,
+    Random,
+    // the best of this many random picks, for each emigrant
+    Tournament(usize),
+}
+
+// Who makes room for the arrivals.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Replaced {
+    Worst,
+    Random,
+}
+
+// The standard policies, any combination of who leaves and whom they replace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Migration {
+    pub emigrants: Emigrants,
+    pub replaced: Replaced,
+    pub count: usize,
+}
+
+impl Migration {
+    pub fn new(emigrants: Emigrants, replaced: Replaced, count: usize) -> Self {
+        Migration{
+            emigrants,
+            replaced,
+            count,
+        }
+    }
+
+    // the usual policy, fast to spread good genes
+    pub fn best_replace_worst(count: usize) -> Self {
+        Self::new(Emigrants::Best, Replaced::Worst, count)
+    }
+
+    // much less selection pressure, keeps the islands more diverse
+    pub fn random_replace_random(count: usize) -> Self {
+        Self::new(Emigrants::Random, Replaced::Random, count)
+    }
+}
+
+impl Default for Migration {
+    fn default() -> Self {
+        Self::best_replace_worst(2)
+    }
+}
+
+fn random_indices(size: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+    let indices: Vec<usize> = (0..size).collect();
+    indices.choose_multiple(rng, count).copied().collect()
+}
+
+impl<I> MigrationPolicy<I> for Migration
+where
+    I: Individual + Send + Sync
+{
+    fn count(&self, size: usize) -> usize {
+        self.count.min(size)
+    }
+
+    fn emigrants(&self, island: &[GradedIndividual<I>], count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        match self.emigrants {
+            Emigrants::Best => by_fitness(island).into_iter().take(count).collect(),
+            Emigrants::Random => random_indices(island.len(), count, rng),
+            // ranked explicitly, the best pick is the lowest rank
+            Emigrants::Tournament(size) => {
+                let order = by_fitness(island);
+                (0..count).map(|_| {
+                    order[(0..size.max(1)).map(|_| rng.gen_range(0..island.len())).min().unwrap()]
+                }).collect()
+            },
+        }
+    }
+
+    fn replaced(&self, island: &[GradedIndividual<I>], count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        match self.replaced {
+            Replaced::Worst => by_fitness(island).into_iter().rev().take(count).collect(),
+            Replaced::Random => random_indices(island.len(), count, rng),
+        }
+    }
+}
+
+// A migrant as one line of text, for sending it to an island in another process (see
+// cluster::MpiArchipelago): "fitness raw_fitness violation score_count scores... genome", with
+// the genome in the caller's format, which must not produce newlines. The grades go along so
+// migrants aren't evaluated again when they arrive.
+pub fn format_migrant<I, F>(migrant: &GradedIndividual<I>, format: F) -> String
+where
+    I: Individual + Send + Sync,
+    F: Fn(&I) -> String,
+{
+    let mut line = format!("{0} {1} {2} {3}", migrant.fitness, migrant.raw_fitness, migrant.violation, migrant.scores.len());
+    for score in &migrant.scores {
+        line.push_str(&format!(" {0}", score));
+    }
+    line.push(' ');
+    line.push_str(&format(&migrant.individual));
+    line
+}
+
+// A migrant from a line written by format_migrant, None if the line or its genome can't be
+// parsed.
+pub fn parse_migrant<I, P>(line: &str, parse: P) -> Option<GradedIndividual<I>>
+where
+    I: Individual + Send + Sync,
+    P: Fn(&str) -> Option<I>,
+{
+    // the next field, leaving rest at what follows it
+    fn field<'a>(rest: &mut &'a str) -> Option<&'a str> {
+        let (field, after) = rest.split_once(' ')?;
+        *rest = after;
+        Some(field)
+    }
+    let mut rest = line;
+    let fitness: f32 = field(&mut rest)?.parse().ok()?;
+    let raw_fitness: f32 = field(&mut rest)?.parse().ok()?;
+    let violation: f32 = field(&mut rest)?.parse().ok()?;
+    let count: usize = field(&mut rest)?.parse().ok()?;
+    let scores = (0..count).map(|_| field(&mut rest)?.parse().ok()).collect::<Option<Vec<f32>>>()?;
+    let mut migrant = GradedIndividual::new(parse(rest)?, fitness);
+    migrant.raw_fitness = raw_fitness;
+    migrant.violation = violation;
+    migrant.scores = scores;
+    Some(migrant)
+}
+
+// Where an island runs. By default islands share rayon's global pool and the archipelago's
+// fitness function. An island can have a pool of its own, e.g. to keep a few cores for the
+// other islands, and a fitness function of its own, e.g. Fitness::Evaluators wrapping a GPU
+// evaluator or a client for a group of remote workers, for heavyweight evaluation on some
+// islands next to cheap local ones. The fitness function has to grade the same way as the
+// archipelago's, migrants aren't regraded when they arrive.
+#[derive(Clone)]
+pub struct Placement<I>
+where
+    I: Individual + Send + Sync
+{
+    pub pool: Option<Arc<ThreadPool>>,
+    pub fitness: Option<Fitness<I>>,
+}
+
+impl<I> Default for Placement<I>
+where
+    I: Individual + Send + Sync
+{
+    fn default() -> Self {
+        Placement{
+            pool: None,
+            fitness: None,
+        }
+    }
+}
+
+// Island model: several populations evolving independently, with a few individuals of each
+// island copied to the next one (in a ring) every migration_interval generations. The islands
+// drift towards different solutions between migrations, and migration spreads good genes
+// without letting one solution take over everywhere at once.
+pub struct Archipelago<I>
+where
+    I: Individual + Send + Sync
+{
+    pub islands: Vec<Population<I>>,
+    // generations between migrations, 0 for none
+    pub migration_interval: usize,
+    // who each island sends to the next one, and whom they replace there
+    pub migration: Arc<dyn MigrationPolicy<I>>,
+    // used by every island, a seed is offset per island so they don't evolve in lockstep
+    pub config: EvolveConfig<I>,
+    // placement[i] is where island i runs, islands without one run on the defaults
+    pub placement: Vec<Placement<I>>,
+}
+
+impl<I> Archipelago<I>
+where
+    I: Individual + Send + Sync
+{
+    // islands populations of island_size generated individuals each
+    pub fn new<G, F>(islands: usize, island_size: usize, generator: &G, fitness: F) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        Archipelago{
+            islands: (0..islands).into_par_iter().map(|_| Population::new(island_size, generator, fitness.clone())).collect(),
+            migration_interval: 10,
+            migration: Arc::new(Migration::default()),
+            config: EvolveConfig::default(),
+            placement: Vec::new(),
+        }
+    }
+
+    // number of times the islands have been evolved
+    pub fn generation(&self) -> usize {
+        self.islands.first().map_or(0, |island| island.generation)
+    }
+
+    pub fn best(&self) -> Option<&GradedIndividual<I>> {
+        self.islands.iter().filter_map(|island| island.individuals.first()).max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+    }
+
+    // Evolves every island by one generation in parallel, then migrates if it is time to.
+    pub fn evolve<C, F>(&self, crossover: &C, fitness: F) -> Self
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let mut islands: Vec<Population<I>> = self.islands.par_iter().enumerate().map(|(i, island)| {
+            let mut config = self.config.clone();
+            config.seed = config.seed.map(|seed| seed.wrapping_add(i as u64));
+            let placement = self.placement.get(i);
+            let fitness = placement.and_then(|placement| placement.fitness.clone()).unwrap_or_else(|| fitness.clone());
+            let evolve = || island.evolve_with(crossover, fitness, &config);
+            match placement.and_then(|placement| placement.pool.as_ref()) {
+                Some(pool) => pool.install(evolve),
+                None => evolve(),
+            }
+        }).collect();
+
+        let generation = self.generation() + 1;
+        if islands.len() > 1 && self.migration_interval > 0 && generation.is_multiple_of(self.migration_interval) {
+            self.migrate(&mut islands, generation);
+        }
+        Archipelago{
+            islands,
+            migration_interval: self.migration_interval,
+            migration: self.migration.clone(),
+            config: self.config.clone(),
+            placement: self.placement.clone(),
+        }
+    }
+
+    // each island's emigrants replace individuals of the next island, as the policy picks them
+    fn migrate(&self, islands: &mut [Population<I>], generation: usize) {
+        let base: u64 = generation_rng(self.config.seed, generation).gen();
+        let count = islands.len();
+        let emigrants: Vec<Vec<GradedIndividual<I>>> = islands.iter().enumerate().map(|(i, island)| {
+            let leaving = self.migration.count(island.individuals.len());
+            let chosen = self.migration.emigrants(&island.individuals, leaving, &mut stream_rng(base, i));
+            chosen.into_iter().map(|index| island.individuals[index].clone()).collect()
+        }).collect();
+        islands.par_iter_mut().enumerate().for_each(|(i, island)| {
+            let arriving = &emigrants[(i + count - 1) % count];
+            let room = arriving.len().min(island.individuals.len());
+            let replaced = self.migration.replaced(&island.individuals, room, &mut stream_rng(base, count + i));
+            for (index, migrant) in replaced.into_iter().zip(arriving) {
+                island.individuals[index] = migrant.clone();
+            }
+            self.config.sort.apply(&mut island.individuals);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::*;
+    use crate::{OptimizationDirection, ScalarFitness};
+
+    #[derive(Clone, Debug)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn island(values: &[f32]) -> Population<Number> {
+        let mut individuals: Vec<GradedIndividual<Number>> = values.iter().map(|&x| GradedIndividual::new(Number(x), x)).collect();
+        individuals.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals,
+            generation: 0,
+            summary: None,
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    fn archipelago(islands: Vec<Population<Number>>) -> Archipelago<Number> {
+        Archipelago{
+            islands,
+            migration_interval: 1,
+            migration: Arc::new(Migration::best_replace_worst(2)),
+            config: EvolveConfig::default(),
+            placement: Vec::new(),
+        }
+    }
+
+    fn fitness(island: &Population<Number>) -> Vec<f32> {
+        island.individuals.iter().map(|entry| entry.fitness).collect()
+    }
+
+    #[test]
+    fn best_replace_worst_around_the_ring() {
+        let mut islands = vec![island(&[1.0, 2.0, 3.0]), island(&[10.0, 20.0, 30.0]), island(&[5.0, 6.0, 7.0])];
+        archipelago(Vec::new()).migrate(&mut islands, 1);
+        // each island's best two replace the worst two of the next, the last sending to the first
+        assert_eq!(fitness(&islands[0]), vec![7.0, 6.0, 3.0]);
+        assert_eq!(fitness(&islands[1]), vec![30.0, 3.0, 2.0]);
+        assert_eq!(fitness(&islands[2]), vec![30.0, 20.0, 7.0]);
+    }
+
+    #[test]
+    fn worst_is_picked_by_fitness_in_a_partly_sorted_island() {
+        let island = island(&[4.0, 0.0, 3.0, 1.0, 2.0]);
+        let mut individuals = island.individuals;
+        // as SortMode::TopK(1) could leave it
+        individuals.swap(1, 4);
+        let policy = Migration::best_replace_worst(2);
+        let mut rng = StdRng::seed_from_u64(1);
+        let replaced = policy.replaced(&individuals, 2, &mut rng);
+        let replaced: Vec<f32> = replaced.into_iter().map(|i| individuals[i].fitness).collect();
+        assert_eq!(replaced, vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn no_migration_between_intervals() {
+        let mut archipelago = archipelago(vec![island(&[1.0, 2.0, 3.0]), island(&[10.0, 20.0, 30.0])]);
+        archipelago.migration_interval = 2;
+        let fitness: ScalarFitness<Number> = Arc::new(|n: &Number| n.0);
+        let average = |a: &Number, b: &Number, _r: &mut dyn RngCore| Number((a.0 + b.0) / 2.0);
+        let evolved = archipelago.evolve(&average, fitness);
+        // one generation of mutation and averaging can't take the first island near 10
+        assert!(evolved.islands[0].individuals.iter().all(|entry| entry.fitness < 5.0));
+        assert_eq!(evolved.generation(), 1);
+    }
+
+    #[test]
+    fn islands_run_where_they_are_placed() {
+        use std::sync::Mutex;
+        let mut archipelago = archipelago(vec![island(&[1.0, 2.0, 3.0, 4.0]), island(&[1.0, 2.0, 3.0, 4.0])]);
+        archipelago.migration_interval = 0;
+        let threads: Arc<Mutex<Vec<String>>> = Arc::default();
+        let seen = threads.clone();
+        let placed: ScalarFitness<Number> = Arc::new(move |_: &Number| {
+            seen.lock().unwrap().push(std::thread::current().name().unwrap_or("").to_string());
+            100.0
+        });
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(2).thread_name(|i| format!("island-{0}", i)).build().unwrap();
+        archipelago.placement = vec![Placement::default(), Placement{pool: Some(Arc::new(pool)), fitness: Some(placed.into())}];
+        let fitness: ScalarFitness<Number> = Arc::new(|n: &Number| n.0);
+        let average = |a: &Number, b: &Number, _r: &mut dyn RngCore| Number((a.0 + b.0) / 2.0);
+        let evolved = archipelago.evolve(&average, fitness);
+        // the first island's offspring are graded by the archipelago's fitness, the second's
+        // by its own on its own pool
+        assert!(evolved.islands[0].individuals.iter().all(|entry| entry.fitness < 5.0));
+        assert!(evolved.islands[1].individuals.iter().any(|entry| entry.fitness == 100.0));
+        let threads = threads.lock().unwrap();
+        assert!(!threads.is_empty());
+        assert!(threads.iter().all(|name| name.starts_with("island-")), "{0:?}", threads);
+    }
+
+    #[test]
+    fn migrants_round_trip_as_text() {
+        let mut migrant = GradedIndividual::new(Number(0.25), -1.5);
+        migrant.raw_fitness = 2.0;
+        migrant.violation = 0.5;
+        migrant.scores = vec![1.0, f32::MIN, 0.1];
+        let line = format_migrant(&migrant, |n| format!("number {0}", n.0));
+        let parsed = parse_migrant(&line, |genome| genome.strip_prefix("number ")?.parse().ok().map(Number)).unwrap();
+        assert_eq!(parsed.individual.0, 0.25);
+        assert_eq!((parsed.fitness, parsed.raw_fitness, parsed.violation), (-1.5, 2.0, 0.5));
+        assert_eq!(parsed.scores, migrant.scores);
+    }
+
+    #[test]
+    fn migrants_that_do_not_parse() {
+        let parse = |genome: &str| genome.parse().ok().map(Number);
+        assert!(parse_migrant("1 1 0 0 x", parse).is_none());
+        assert!(parse_migrant("1 1 0 2 1.0 3", parse).is_none());
+        assert!(parse_migrant("1 1 0", parse).is_none());
+        assert!(parse_migrant("1 1 0 0 3", parse).is_some());
+    }
+}
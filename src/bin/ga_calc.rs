@@ -1,10 +1,16 @@
 use ga::Individual;
 use std::fmt::{Display, Formatter};
 use std::num::Wrapping;
-use std::sync::Arc;
+use std::str::FromStr;
+use std::ops::Range;
+use std::sync::{Arc, OnceLock};
 use rand;
-use rand::Rng;
+use rand::{Rng, RngCore};
+use rand::distributions::{Distribution, WeightedIndex};
 use ga;
+use ga::mutation::{Mutation, OneOf};
+use ga::observer::Observer;
+use ga::output::{Log, Verbosity};
 use clap::Parser;
 use rayon::iter::split;
 
@@ -15,13 +21,128 @@ struct Args {
     max_generations: usize,
     #[arg(long, default_value_t=10000)]
     population_size: usize,
+    // how much to print: quiet, summary, generation or debug
+    #[arg(short, long, default_value_t=Verbosity::Summary)]
+    verbosity: Verbosity,
+    // print nothing but the final JSON summary line, same as --verbosity quiet
     #[arg(short, long, default_value_t=false)]
-    verbose: bool,
+    quiet: bool,
+    // select parents with lexicase selection over a fixed set of test cases
+    #[arg(long, default_value_t=false)]
+    lexicase: bool,
+    #[arg(long, default_value_t=10)]
+    cases: usize,
+    // with lexicase, also select for programs that take fewer steps
+    #[arg(long, default_value_t=false, requires="lexicase")]
+    step_objective: bool,
+    // evolve for three objectives, error, program length and steps, with Pareto tournament
+    // selection over --cases fixed inputs, and print the Pareto set at the end
+    #[arg(long, default_value_t=false, conflicts_with="lexicase")]
+    multi_objective: bool,
+    // with --multi-objective, evolve with SPEA2 keeping an archive of this many programs
+    // instead of Pareto tournament selection
+    #[arg(long, requires="multi_objective")]
+    spea2: Option<usize>,
+    // only keep the best top_k individuals sorted instead of the whole population
+    #[arg(long)]
+    top_k: Option<usize>,
+    // seeds the initial population and evolution, the fitness inputs are still random
+    #[arg(long)]
+    seed: Option<u64>,
+    // keep going past max_generations, up to this many, while the best fitness is still improving
+    #[arg(long)]
+    generation_cap: Option<usize>,
+    // average improvement of the best fitness per generation that counts as still improving
+    #[arg(long, default_value_t=0.0)]
+    min_improvement: f32,
+    // write the best program of every generation to this file
+    #[arg(long)]
+    elite_history: Option<String>,
+    // keep the best this many distinct programs seen during the run, and print them at the end
+    #[arg(long)]
+    hall_of_fame: Option<usize>,
+    // every generation replace this fraction of the population with mutants of hall of fame
+    // programs
+    #[arg(long, requires="hall_of_fame")]
+    immigration: Option<f32>,
+    // skip the run if its seed is recorded as completed in this file, otherwise record it
+    // there when done, so an interrupted sweep over seeds only redoes the missing runs
+    #[arg(long, requires="seed")]
+    manifest: Option<String>,
+    // write the run's settings, statistics, checkpoints, best programs and result to this
+    // directory, see ga::bundle::RunBundle
+    #[arg(long)]
+    out_dir: Option<String>,
+    // stop once this many programs have been evaluated, checked between generations
+    #[arg(long)]
+    max_evaluations: Option<usize>,
+    // time this many evaluations, print how long the run would take and exit
+    #[arg(long)]
+    pilot: Option<usize>,
+    // range of the offsets of randomly generated jumps, as start..end (end excluded)
+    #[arg(long, value_parser=parse_range, default_value="0..5")]
+    jump_range: Range<i32>,
+    // range of the memory addresses of randomly generated push (n) and pop_to n
+    #[arg(long, value_parser=parse_range, default_value="0..5")]
+    memory_range: Range<i32>,
+    // range of the constants of randomly generated push n
+    #[arg(long, value_parser=parse_range, default_value="0..5")]
+    constant_range: Range<i32>,
+    // relative odds of each instruction in randomly generated opcodes, as name=weight pairs
+    // separated by commas, e.g. abort=0.2,add=3. Instructions not listed have weight 1
+    #[arg(long, value_parser=parse_weights, default_value="")]
+    instruction_weights: InstructionWeights,
+    // start with the program in this file in the initial population, may be given more than
+    // once. One op per line (or separated by ;) as the final solution is printed, blank lines
+    // and lines starting with # are skipped
+    #[arg(long)]
+    seed_program: Vec<String>,
+    // put the seed programs back into the population for this many generations if evolution
+    // drops them, giving it time to build on them
+    #[arg(long, default_value_t=0)]
+    protect_seeds: usize,
 }
 
+fn parse_range(s: &str) -> Result<Range<i32>, String> {
+    let (start, end) = s.split_once("..").ok_or_else(|| format!("expected start..end, got {0:?}", s))?;
+    let start: i32 = start.trim().parse().map_err(|e| format!("bad range start {0:?}: {1}", start, e))?;
+    let end: i32 = end.trim().parse().map_err(|e| format!("bad range end {0:?}: {1}", end, e))?;
+    if start >= end {
+        return Err(format!("range {0:?} is empty", s));
+    }
+    Ok(start..end)
+}
+
+fn parse_weights(s: &str) -> Result<InstructionWeights, String> {
+    let mut weights = InstructionWeights::default();
+    for pair in s.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let (name, weight) = pair.split_once('=').ok_or_else(|| format!("expected name=weight, got {0:?}", pair))?;
+        let instruction = Instruction::from_name(name.trim()).ok_or_else(|| format!("unknown instruction {0:?}", name))?;
+        let weight: f32 = weight.trim().parse().map_err(|e| format!("bad weight {0:?}: {1}", weight, e))?;
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(format!("weight of {0} must be zero or more, got {1}", name, weight));
+        }
+        let code: u8 = instruction.into();
+        weights.0[code as usize] = weight;
+    }
+    if weights.0.iter().all(|&weight| weight == 0.0) {
+        return Err("at least one instruction needs a weight above zero".to_string());
+    }
+    Ok(weights)
+}
+
+
+// What a run of a program used, so fitness functions can reward efficient programs as well
+// as correct ones. Reset with the rest of the VM state.
+#[derive(Clone,Copy,Debug)]
 struct Stats {
+    // steps executed
     instructions_issued: i32,
     invalid_instructions: i32,
+    // deepest the stack got
+    max_stack_depth: usize,
+    // distinct memory cells the program read or wrote
+    memory_touched: usize,
 }
 
 impl Stats {
@@ -29,6 +150,8 @@ impl Stats {
         Stats{
             instructions_issued: 0,
             invalid_instructions: 0,
+            max_stack_depth: 0,
+            memory_touched: 0,
         }
     }
 }
@@ -42,6 +165,8 @@ struct SVM {
     memory: Vec<i32>,
     stack: Vec<i32>,
     stats: Stats,
+    // which memory cells the program has used, for Stats::memory_touched
+    touched: Vec<bool>,
 }
 
 impl SVM {
@@ -50,8 +175,10 @@ impl SVM {
             memory: Vec::new(),
             stack: Vec::new(),
             stats: Stats::new(),
+            touched: Vec::new(),
         };
         vm.memory.resize(words, 0);
+        vm.touched.resize(words, false);
         vm.stack.reserve(stack_size);
         vm
     }
@@ -80,6 +207,17 @@ impl SVM {
     fn push_stack(&mut self, val: i32) {
         if self.stack.len() < self.stack.capacity() {
             self.stack.push(val);
+            self.stats.max_stack_depth = self.stats.max_stack_depth.max(self.stack.len());
+        }
+    }
+
+    // records that the program used the memory cell at address
+    fn touch(&mut self, address: usize) {
+        if let Some(touched) = self.touched.get_mut(address) {
+            if !*touched {
+                *touched = true;
+                self.stats.memory_touched += 1;
+            }
         }
     }
 
@@ -87,9 +225,15 @@ impl SVM {
         self.memory.fill(0);
         self.stack.clear();
         self.stats = Stats::new();
+        self.touched.fill(false);
     }
 
-    fn execute(&mut self, program: &Vec<OpCode>, max_steps: i32) -> ExitType {
+    // what the last run used
+    fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn execute(&mut self, program: &[OpCode], max_steps: i32) -> ExitType {
         let mut ip: i32 = 0;
         let mut done = false;
 
@@ -166,8 +310,12 @@ impl SVM {
                     self.push_stack(val);
                     self.push_stack(val);
                 },
-                Instruction::PushMem => {self.push_stack(self.peek_mem(cur_op.literal as usize));},
+                Instruction::PushMem => {
+                    self.touch(cur_op.literal as usize);
+                    self.push_stack(self.peek_mem(cur_op.literal as usize));
+                },
                 Instruction::PopMem => {
+                    self.touch(cur_op.literal as usize);
                     let value = self.pop_stack();
                     self.poke_mem(cur_op.literal as usize, value);
                 },
@@ -197,7 +345,7 @@ impl SVM {
     }
 }
 
-#[derive(Copy,Clone,Debug)]
+#[derive(Copy,Clone,Debug,PartialEq)]
 enum Instruction {
     Nop,
     BitOr,
@@ -219,6 +367,38 @@ enum Instruction {
     Abort,
 }
 
+impl Instruction {
+    const COUNT: usize = 18;
+
+    // the mnemonic used for the instruction on the command line
+    fn name(self) -> &'static str {
+        match self {
+            Instruction::Nop => "nop",
+            Instruction::BitOr => "bit_or",
+            Instruction::BitAnd => "bit_and",
+            Instruction::BitXor => "bit_xor",
+            Instruction::Add => "add",
+            Instruction::Sub => "sub",
+            Instruction::Mult => "mult",
+            Instruction::Div => "div",
+            Instruction::Push => "push",
+            Instruction::Pop => "pop",
+            Instruction::PushDuplicate => "push_dup",
+            Instruction::PushMem => "push_mem",
+            Instruction::PopMem => "pop_to",
+            Instruction::JumpRel => "jmp",
+            Instruction::JumpEq => "jmp_eq",
+            Instruction::JumpGt => "jmp_gt",
+            Instruction::JumpLt => "jmp_lt",
+            Instruction::Abort => "abort",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        (0..Instruction::COUNT as u8).filter_map(|code| Instruction::try_from(code).ok()).find(|instruction| instruction.name() == name)
+    }
+}
+
 impl TryFrom<u8> for Instruction {
     type Error = &'static str;
 
@@ -272,20 +452,69 @@ impl Into<u8> for Instruction {
     }
 }
 
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq)]
 struct OpCode {
     code: Instruction,
     literal: i32,
 }
 
+// Ranges the literals of randomly generated opcodes are drawn from, by what the literal is
+// used for. Set once from the command line, see literal_ranges.
+#[derive(Clone,Debug)]
+struct LiteralRanges {
+    jump: Range<i32>,
+    memory: Range<i32>,
+    constant: Range<i32>,
+}
+
+impl Default for LiteralRanges {
+    fn default() -> Self {
+        LiteralRanges{
+            jump: 0..5,
+            memory: 0..5,
+            constant: 0..5,
+        }
+    }
+}
+
+static LITERAL_RANGES: OnceLock<LiteralRanges> = OnceLock::new();
+
+fn literal_ranges() -> &'static LiteralRanges {
+    LITERAL_RANGES.get_or_init(LiteralRanges::default)
+}
+
+// Relative odds of each instruction (indexed by its code) in randomly generated opcodes, so
+// e.g. fewer random programs abort right away. Set once from the command line, see
+// instruction_distribution.
+#[derive(Clone,Debug)]
+struct InstructionWeights([f32; Instruction::COUNT]);
+
+impl Default for InstructionWeights {
+    fn default() -> Self {
+        InstructionWeights([1.0; Instruction::COUNT])
+    }
+}
+
+static INSTRUCTION_DISTRIBUTION: OnceLock<WeightedIndex<f32>> = OnceLock::new();
+
+fn instruction_distribution() -> &'static WeightedIndex<f32> {
+    INSTRUCTION_DISTRIBUTION.get_or_init(|| WeightedIndex::new(InstructionWeights::default().0).unwrap())
+}
+
 impl OpCode {
-    fn rand() -> Self {
-        let mut r = rand::thread_rng();
-        let a = Instruction::Abort;
-        let end: u8 = a.try_into().unwrap();
+    fn rand<R: Rng + ?Sized>(r: &mut R) -> Self {
+        let code = Instruction::try_from(instruction_distribution().sample(r) as u8).unwrap();
+        let ranges = literal_ranges();
+        let literal = match code {
+            Instruction::JumpRel | Instruction::JumpEq | Instruction::JumpGt | Instruction::JumpLt => r.gen_range(ranges.jump.clone()),
+            Instruction::PushMem | Instruction::PopMem => r.gen_range(ranges.memory.clone()),
+            Instruction::Push => r.gen_range(ranges.constant.clone()),
+            // unused by the other instructions
+            _ => 0,
+        };
         OpCode {
-            code: Instruction::try_from(r.gen_range(0..end+1u8)).unwrap(),
-            literal: r.gen_range(0..5),
+            code,
+            literal,
         }
     }
 }
@@ -308,174 +537,468 @@ impl Display for OpCode {
             Instruction::PopMem => write!(f, "pop_to {0}", self.literal),
             Instruction::JumpRel => write!(f, "jmp {0}", self.literal),
             Instruction::JumpEq => write!(f, "jmp_eq {0}", self.literal),
-            Instruction::JumpGt => write!(f, "jmp_lt {0}", self.literal),
-            Instruction::JumpLt => write!(f, "jmp_gt {0}", self.literal),
+            Instruction::JumpGt => write!(f, "jmp_gt {0}", self.literal),
+            Instruction::JumpLt => write!(f, "jmp_lt {0}", self.literal),
             Instruction::Abort => write!(f, "abort"),
         }
     }
 }
 
-#[derive(Clone)]
+// Reads an op as written by Display, the instruction name with its literal if it has one.
+// push_mem n is accepted for push (n).
+impl FromStr for OpCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (name, literal) = match s.split_once(' ') {
+            Some((name, literal)) => (name, Some(literal.trim())),
+            None => (s, None),
+        };
+        let (code, literal) = match (name, literal) {
+            ("push", Some(literal)) if literal.starts_with('(') && literal.ends_with(')') => {
+                (Instruction::PushMem, Some(&literal[1..literal.len() - 1]))
+            },
+            (name, literal) => (Instruction::from_name(name).ok_or_else(|| format!("unknown instruction {0:?}", name))?, literal),
+        };
+        let takes_literal = matches!(code, Instruction::Push | Instruction::PushMem | Instruction::PopMem
+            | Instruction::JumpRel | Instruction::JumpEq | Instruction::JumpGt | Instruction::JumpLt);
+        let literal = match (takes_literal, literal) {
+            (true, Some(literal)) => literal.trim().parse().map_err(|e| format!("bad literal in {0:?}: {1}", s, e))?,
+            (true, None) => return Err(format!("{0} needs a literal", name)),
+            (false, None) => 0,
+            (false, Some(_)) => return Err(format!("{0} takes no literal", name)),
+        };
+        Ok(OpCode {
+            code,
+            literal,
+        })
+    }
+}
+
+// programs rarely grow past 32 ops, so keep them inline
+type Program = ga::genome::SmallGenome<OpCode, 32>;
+
+#[derive(Clone,PartialEq)]
 struct CalcIndividual {
-    ops: Vec<OpCode>,
+    ops: Program,
 }
 
 impl CalcIndividual {
-    fn new() -> Self {
-        let mut r = rand::thread_rng();
+    fn new<R: Rng>(r: &mut R) -> Self {
         let count = r.gen_range(5..25);
-        let mut ops = Vec::new();
-        ops.reserve(count);
-        for _ in 0..count {
-            ops.push(OpCode::rand());
-        }
         CalcIndividual {
-            ops,
+            ops: Program::from_fn(count, |_| OpCode::rand(r)),
         }
     }
 }
 
+fn read_program(path: &str) -> Result<CalcIndividual, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{0}: {1}", path, e))?;
+    let ops = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .flat_map(|line| line.split(';'))
+        .map(str::trim)
+        .filter(|op| !op.is_empty())
+        .map(|op| op.parse::<OpCode>().map_err(|e| format!("{0}: {1}", path, e)))
+        .collect::<Result<Vec<OpCode>, String>>()?;
+    if ops.is_empty() {
+        return Err(format!("{0}: no program", path));
+    }
+    Ok(CalcIndividual {
+        ops: ops.into(),
+    })
+}
+
+// Puts the seed programs that aren't in the population in place of its worst individuals.
+fn inject(pop: &mut ga::Population<CalcIndividual>, seeds: &[CalcIndividual], fitness: &ga::Fitness<CalcIndividual>) {
+    let missing: Vec<&CalcIndividual> = seeds.iter().filter(|seed| !pop.individuals.iter().any(|ind| &ind.individual == *seed)).collect();
+    if missing.is_empty() {
+        return;
+    }
+    // sorted in full first, with --top-k only the best are in order
+    pop.individuals.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+    let keep = pop.individuals.len().saturating_sub(missing.len());
+    pop.individuals.truncate(keep);
+    pop.individuals.extend(missing.into_iter().map(|seed| fitness.grade(seed.clone())));
+    pop.individuals.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+}
+
+fn replace_op(ind: &CalcIndividual, r: &mut dyn RngCore) -> CalcIndividual {
+    CalcIndividual{
+        ops: ind.ops.replaced(r.gen_range(0..ind.ops.len()), OpCode::rand(r)),
+    }
+}
+
+fn remove_op(ind: &CalcIndividual, r: &mut dyn RngCore) -> CalcIndividual {
+    // very short programs grow instead
+    if ind.ops.len() <= 2 {
+        return push_op(ind, r);
+    }
+    CalcIndividual{
+        ops: ind.ops.removed(r.gen_range(0..ind.ops.len())),
+    }
+}
+
+fn push_op(ind: &CalcIndividual, r: &mut dyn RngCore) -> CalcIndividual {
+    CalcIndividual{
+        ops: ind.ops.pushed(OpCode::rand(r)),
+    }
+}
+
+// one of the edits above, picked uniformly
+fn mutation() -> &'static OneOf<CalcIndividual> {
+    static MUTATION: OnceLock<OneOf<CalcIndividual>> = OnceLock::new();
+    MUTATION.get_or_init(|| {
+        OneOf::new()
+            .or(replace_op, 1.0)
+            .or(remove_op, 1.0)
+            .or(push_op, 1.0)
+    })
+}
+
 impl ga::Individual for CalcIndividual {
-    fn mutate(&self) -> Self {
-        let mut r = rand::thread_rng();
-        let mut action: i32 = r.gen_range(0..3);
-        let l = self.ops.len();
-        if l <= 2 && action == 1 {
-            action = 2;
-        }
-        let action = action;
-        let mut ops: Vec<OpCode> = Vec::new();
-        match action {
-            0 => {
-                ops = self.ops.clone();
-                ops[r.gen_range(0..l) as usize] = OpCode::rand();
-            },
-            1 => {
-                let split_point = r.gen_range(0..l) as usize;
-                ops.reserve(l+1);
-                let mut i: usize = 0;
-                self.ops.iter().filter_map(|op| -> Option<OpCode> {
-                    let result = if i == split_point {
-                        None
-                    } else {
-                        Some((*op).clone())
-                    };
-                    i += 1;
-                    result
-                }).for_each(|op| {
-                    ops.push(op);
-                })
-            },
-            _ => {
-                ops.reserve(l+1);
-                self.ops.iter().for_each(|op| {
-                    ops.push((*op).clone());
-                });
-                ops.push(OpCode::rand());
-            },
-        }
-        CalcIndividual{
-            ops
-        }
+    fn mutate<R: Rng>(&self, r: &mut R) -> Self {
+        mutation().mutate(self, r)
     }
 }
 
 struct Generator {}
 
 impl ga::Generator<CalcIndividual> for Generator {
-    fn generate(&self) -> CalcIndividual {
-        CalcIndividual::new()
+    fn generate<R: Rng>(&self, r: &mut R) -> CalcIndividual {
+        CalcIndividual::new(r)
     }
+}
 
-    fn evolve(&self, a: &CalcIndividual, _: &CalcIndividual) -> CalcIndividual {
-        (*a).mutate()
+impl ga::Crossover<CalcIndividual> for Generator {
+    fn crossover<R: Rng>(&self, a: &CalcIndividual, _: &CalcIndividual, r: &mut R) -> CalcIndividual {
+        (*a).mutate(r)
     }
 }
 
 fn get_val() -> i32 {
-    rand::thread_rng().gen_range(1..10000)
+    get_val_with(&mut rand::thread_rng())
 }
 
-fn fitness_function() -> Arc<dyn Fn(&CalcIndividual) -> f32 + Send + Sync> {
-    Arc::new(move |subject: &CalcIndividual| -> f32 {
-        let a = get_val();
-        let b = get_val();
-        //let c = get_val();
-        let expected = ((a * b) + a) as f32;
+fn get_val_with<R: Rng>(r: &mut R) -> i32 {
+    r.gen_range(1..10000)
+}
 
-        let mut vm = SVM::new(100, 100);
-        vm.poke_mem(0, a);
-        vm.poke_mem(1, b);
-        //vm.poke_mem(2, c);
-        let exit_type = vm.execute(&subject.ops, 25);
-        let val = vm.peek_mem(3) as f32;
-        let modifier: f32 = match exit_type {
-            ExitType::Abort => {
-                if expected == val {
-                    let l = subject.ops.len();
-                    if l < 10 {
-                        2.0
-                    } else if l < 15 {
-                        1.8
-                    } else if l < 20 {
-                        1.7
-                    } else if l < 25 {
-                        1.6
-                    } else if l < 30 {
-                        1.5
-                    } else {
-                        1.0
-                    }
+// how long a program may run on a case before it times out
+const MAX_STEPS: i32 = 25;
+
+// Runs subject on a and b in vm, which is reset first so it can be reused between runs.
+fn score_case(vm: &mut SVM, subject: &CalcIndividual, a: i32, b: i32) -> f32 {
+    //let c = get_val();
+    let expected = ((a * b) + a) as f32;
+
+    vm.reset_state();
+    vm.poke_mem(0, a);
+    vm.poke_mem(1, b);
+    //vm.poke_mem(2, c);
+    let exit_type = vm.execute(&subject.ops, MAX_STEPS);
+    let val = vm.peek_mem(3) as f32;
+    let modifier: f32 = match exit_type {
+        ExitType::Abort => {
+            if expected == val {
+                let l = subject.ops.len();
+                if l < 10 {
+                    2.0
+                } else if l < 15 {
+                    1.8
+                } else if l < 20 {
+                    1.7
+                } else if l < 25 {
+                    1.6
+                } else if l < 30 {
+                    1.5
                 } else {
-                    0.0
+                    1.0
                 }
-            },
-            ExitType::Timeout => {
+            } else {
                 0.0
-            },
-        };
-        modifier - ((expected-val) as f32).abs()
+            }
+        },
+        ExitType::Timeout => {
+            0.0
+        },
+    };
+    modifier - ((expected-val) as f32).abs()
+}
+
+// Each worker thread gets its own VM and rng, built once per generation instead of a new VM
+// for every evaluation.
+struct CalcEvaluator {
+    vm: SVM,
+    rng: rand::rngs::StdRng,
+}
+
+impl ga::evaluator::Evaluator<CalcIndividual> for CalcEvaluator {
+    fn evaluate(&mut self, subject: &CalcIndividual) -> f32 {
+        let (a, b) = (get_val_with(&mut self.rng), get_val_with(&mut self.rng));
+        score_case(&mut self.vm, subject, a, b)
+    }
+}
+
+fn fitness_function() -> ga::Fitness<CalcIndividual> {
+    ga::evaluator::Evaluators::new(|| CalcEvaluator{
+        vm: SVM::new(100, 100),
+        rng: rand::SeedableRng::from_entropy(),
+    }).into()
+}
+
+// Scores every program against the same fixed set of inputs, one score per case,
+// for use with lexicase selection. With steps set there is one more score, for how few steps
+// the program took over all cases, between -1 (every case timed out) and 0.
+fn case_fitness_function(case_count: usize, steps: bool) -> ga::CaseFitness<CalcIndividual> {
+    let cases: Vec<(i32, i32)> = (0..case_count).map(|_| (get_val(), get_val())).collect();
+    Arc::new(move |subject: &CalcIndividual| -> Vec<f32> {
+        let mut vm = SVM::new(100, 100);
+        let mut used = 0;
+        let mut scores: Vec<f32> = cases.iter().map(|&(a, b)| {
+            let score = score_case(&mut vm, subject, a, b);
+            used += vm.stats().instructions_issued;
+            score
+        }).collect();
+        if steps {
+            scores.push(-(used as f32) / (MAX_STEPS * cases.len().max(1) as i32) as f32);
+        }
+        scores
+    })
+}
+
+// The objectives of the multi-objective run, all negated so higher is better: the mean error
+// over the cases, the program length and the mean steps it took.
+fn objectives_function(case_count: usize) -> ga::CaseFitness<CalcIndividual> {
+    let cases: Vec<(i32, i32)> = (0..case_count.max(1)).map(|_| (get_val(), get_val())).collect();
+    Arc::new(move |subject: &CalcIndividual| -> Vec<f32> {
+        let mut vm = SVM::new(100, 100);
+        let mut error = 0.0;
+        let mut steps = 0;
+        for &(a, b) in &cases {
+            vm.reset_state();
+            vm.poke_mem(0, a);
+            vm.poke_mem(1, b);
+            let expected = ((a * b) + a) as f32;
+            error += match vm.execute(&subject.ops, MAX_STEPS) {
+                ExitType::Abort => (expected - vm.peek_mem(3) as f32).abs(),
+                ExitType::Timeout => expected.abs() + 1.0,
+            };
+            steps += vm.stats().instructions_issued;
+        }
+        let n = cases.len() as f32;
+        vec![-error / n, -(subject.ops.len() as f32), -(steps as f32) / n]
     })
 }
 
+// worse than any program that aborts with a sensible answer, for the hypervolume
+const OBJECTIVES_REFERENCE: [f32; 3] = [-1.0e8, -64.0, -(MAX_STEPS as f32) - 1.0];
 
 fn main() {
     let args = Args::parse();
+    let log = Log::new(if args.quiet { Verbosity::Quiet } else { args.verbosity });
+    let mut manifest = args.manifest.as_ref().map(|path| ga::manifest::Manifest::open(path).expect("failed to read the manifest"));
+    let run = args.seed.map(|seed| seed.to_string()).unwrap_or_default();
+    if let Some(result) = manifest.as_ref().and_then(|manifest| manifest.completed(&run)) {
+        log.summary(format_args!("Seed {0} already completed", run));
+        log.summary(format_args!("{0}", result));
+        return;
+    }
+
+    LITERAL_RANGES.set(LiteralRanges{
+        jump: args.jump_range.clone(),
+        memory: args.memory_range.clone(),
+        constant: args.constant_range.clone(),
+    }).expect("literal ranges set twice");
+    INSTRUCTION_DISTRIBUTION.set(WeightedIndex::new(args.instruction_weights.0).unwrap()).expect("instruction weights set twice");
 
     let gen = Generator{};
-    let fitness = fitness_function();
-    let mut pop = ga::Population::new(args.population_size, &gen, fitness.clone() );
+    let fitness: ga::Fitness<CalcIndividual> = if args.multi_objective {
+        objectives_function(args.cases).into()
+    } else if args.lexicase {
+        case_fitness_function(args.cases, args.step_objective).into()
+    } else {
+        fitness_function()
+    };
+    let evaluations = ga::budget::Evaluations::new();
+    let fitness = evaluations.counting(fitness);
+    // the step score only lowers the total, a correct program can still use every step
+    let target = if args.lexicase { 1.9 * args.cases as f32 - if args.step_objective { 1.0 } else { 0.0 } } else { 1.9 };
+    let mut config = ga::EvolveConfig::default();
+    if args.lexicase {
+        config.selection = Some(Arc::new(ga::selection::Lexicase::default()));
+    }
+    if args.multi_objective {
+        config.selection = Some(Arc::new(ga::selection::ParetoTournament::default()));
+    }
+    let hypervolume = ga::hypervolume::Hypervolume::new(OBJECTIVES_REFERENCE.to_vec());
+    let mut spea2 = args.spea2.map(|archive_size| {
+        let mut spea2 = ga::spea2::Spea2::new(archive_size);
+        spea2.seed = args.seed;
+        spea2
+    });
+    if let Some(k) = args.top_k {
+        config.sort = ga::SortMode::TopK(k);
+    }
+    config.seed = args.seed;
+    if let Err(errors) = config.validate(args.population_size, &fitness) {
+        errors.iter().for_each(|error| eprintln!("{0}", error));
+        std::process::exit(2);
+    }
+    let cap = args.generation_cap.unwrap_or(args.max_generations);
+    let mut budget = ga::budget::GenerationBudget::extendable(args.max_generations, cap, args.min_improvement);
+    if let Some(sample) = args.pilot {
+        let estimate = budget.estimate(args.population_size, sample, &gen, fitness.clone());
+        log.summary(format_args!("{0}", estimate));
+        return;
+    }
+    let mut pop = match args.seed {
+        Some(seed) => ga::Population::new_seeded(args.population_size, &gen, fitness.clone(), seed),
+        None => ga::Population::new(args.population_size, &gen, fitness.clone()),
+    };
+    let seeds: Vec<CalcIndividual> = args.seed_program.iter().map(|path| read_program(path)).collect::<Result<_, _>>().unwrap_or_else(|error| {
+        eprintln!("{0}", error);
+        std::process::exit(2);
+    });
+    inject(&mut pop, &seeds, &fitness);
 
     let mut generations = 1;
-    let mut need_matches = 100;
-
-    for g in 1..args.max_generations {
-        if args.verbose {
-            println!("{0})", g);
-            pop.individuals.iter().take(5).for_each(|ind| {
-                println!("\t{0}", ind.fitness)
-            });
+    // the target has to hold for 100 generations in a row
+    let mut reached = ga::termination::TargetFitness::sustained(target, 100);
+    let mut solved = false;
+    let mut history = ga::history::EliteHistory::new();
+    let started = std::time::Instant::now();
+    let mut progress = ga::observer::Progress::new(Log::new(log.verbosity));
+    let mut bundle = args.out_dir.as_ref().map(|dir| {
+        let bundle = ga::bundle::RunBundle::create(dir, |ind: &CalcIndividual| ind.ops.iter().map(|op| op.to_string()).collect::<Vec<String>>().join("; "))
+            .expect("failed to create the output directory");
+        bundle.write_config(&args).expect("failed to write the run's settings");
+        bundle
+    });
+    let mut hall_of_fame = args.hall_of_fame.map(ga::history::HallOfFame::new);
+
+    while !budget.exhausted(generations) {
+        let best = pop.individuals.first().unwrap().fitness;
+        let stats = ga::termination::GenerationStats{
+            generation: generations,
+            best,
+            mean: pop.summary.as_ref().map(|summary| summary.mean()),
+            direction: pop.direction,
+            elapsed: started.elapsed(),
+            evaluations: Some(evaluations.count()),
+        };
+        progress.on_generation(&stats, &pop);
+        budget.record(best);
+        if let Some(bundle) = &mut bundle {
+            bundle.record(&stats);
+            bundle.checkpoint(&pop);
+        }
+        if args.multi_objective && log.enabled(Verbosity::Debug) {
+            log.debug(format_args!("\thypervolume {0}", hypervolume.of_population(&pop)));
+        }
+        if args.elite_history.is_some() {
+            history.record(&pop);
         }
-        if pop.individuals.first().unwrap().fitness >= 1.9 {
-            need_matches -= 1;
+        if let Some(hall_of_fame) = &mut hall_of_fame {
+            hall_of_fame.record(&pop);
+        }
+        // with multiple objectives solved is any program without error, however long or slow
+        let hit = if args.multi_objective {
+            pop.individuals.iter().any(|ind| ind.scores.first() == Some(&0.0))
         } else {
-            need_matches = 100;
+            best >= target
+        };
+        if reached.record(hit) {
+            solved = true;
+            break;
         }
-        if need_matches == 0 {
+        if args.max_evaluations.is_some_and(|max| evaluations.count() >= max) {
             break;
         }
-        pop = pop.evolve(&gen, fitness.clone());
+        pop = match &mut spea2 {
+            Some(spea2) => spea2.evolve(&pop, &gen, fitness.clone()),
+            None => pop.evolve_with(&gen, fitness.clone(), &config),
+        };
+        if let (Some(hall_of_fame), Some(rate)) = (&hall_of_fame, args.immigration) {
+            hall_of_fame.immigrate(&mut pop, rate, fitness.clone(), &config);
+        }
+        if generations <= args.protect_seeds {
+            inject(&mut pop, &seeds, &fitness);
+        }
         generations += 1;
     }
 
-    println!("After {0} Generations", generations);
+    log.summary(format_args!("After {0} Generations", generations));
+    if budget.extended(generations) {
+        log.summary(format_args!("Ran past {0} generations while the best fitness was still improving", args.max_generations));
+    }
+    if !solved && budget.improving() {
+        log.summary(format_args!("Not converged, the best fitness was still improving"));
+    }
     pop.individuals.iter().take(5).for_each(|ind| {
-        println!("\t{0}", ind.fitness)
+        log.summary(format_args!("\t{0}", ind.fitness))
     });
 
     let final_solution = &pop.individuals.first().unwrap().individual;
-    println!("Final solution:");
+    log.summary(format_args!("Final solution:"));
     final_solution.ops.iter().for_each(|op| {
-        println!("{0}", op);
+        log.summary(format_args!("{0}", op));
     });
+
+    if args.multi_objective {
+        // SPEA2 keeps the best programs found in its archive rather than the population
+        let candidates = match &spea2 {
+            Some(spea2) => spea2.archive().iter().filter(|ind| !spea2.archive().iter().any(|other| other.dominates(ind))).collect(),
+            None => pop.pareto_front(),
+        };
+        let mut front: Vec<&ga::GradedIndividual<CalcIndividual>> = Vec::new();
+        for ind in candidates {
+            if !front.iter().any(|other| other.individual == ind.individual) {
+                front.push(ind);
+            }
+        }
+        front.sort_by(|a, b| b.scores[0].total_cmp(&a.scores[0]));
+        log.summary(format_args!("Pareto set (error, length, steps), hypervolume {0}:", hypervolume.of_population(&pop)));
+        front.iter().for_each(|ind| {
+            let program = ind.individual.ops.iter().map(|op| op.to_string()).collect::<Vec<String>>().join("; ");
+            log.summary(format_args!("\t{0} {1} {2} {3}", -ind.scores[0], -ind.scores[1], -ind.scores[2], program));
+        });
+    }
+
+    if let Some(hall_of_fame) = &mut hall_of_fame {
+        hall_of_fame.record(&pop);
+        log.summary(format_args!("Hall of fame:"));
+        hall_of_fame.members().iter().for_each(|ind| {
+            let program = ind.individual.ops.iter().map(|op| op.to_string()).collect::<Vec<String>>().join("; ");
+            log.summary(format_args!("\t{0} {1}", ind.fitness, program));
+        });
+    }
+
+    if let Some(path) = &args.elite_history {
+        history.record(&pop);
+        let format = |ind: &CalcIndividual| ind.ops.iter().map(|op| op.to_string()).collect::<Vec<String>>().join("; ");
+        history.write(path, format).expect("failed to write the elite history");
+    }
+
+    let best = pop.individuals.first().unwrap();
+    let summary = ga::output::RunSummary{
+        solved,
+        generations,
+        best_fitness: best.fitness,
+        best: best.individual.ops.iter().map(|op| op.to_string()).collect::<Vec<String>>().join("; "),
+    };
+    // printed whatever the verbosity, it's what scripts read
+    println!("{0}", summary.to_json());
+    if let Some(manifest) = &mut manifest {
+        manifest.record(&run, &summary.to_json()).expect("failed to write the manifest");
+    }
+    if let Some(bundle) = bundle {
+        bundle.finish(&pop, &summary).expect("failed to write the output directory");
+    }
+    // non-zero when no program reached the target fitness, for scripts
+    if !solved {
+        std::process::exit(1);
+    }
 }
\ No newline at end of file
@@ -1,7 +1,12 @@
-use std::sync::Arc;
+use std::fs::File;
+use std::fmt::{Display, Formatter};
+use std::hash::Hash;
+use std::io::{self, BufWriter, Read, Write};
+use std::sync::{Arc, OnceLock};
 use rand;
 use rand::Rng;
 use ga;
+use ga::output::{Log, Verbosity};
 use clap::Parser;
 
 
@@ -10,62 +15,120 @@ use clap::Parser;
 struct Args {
     #[arg(long, default_value="helloworld")]
     phrase: String,
+    // read the phrase from this file instead, - for stdin. Everything but the final line
+    // break is the phrase, so it may span lines
+    #[arg(long)]
+    phrase_file: Option<String>,
     #[arg(long, default_value_t=1000)]
     max_generations: usize,
     #[arg(long, default_value_t=1000)]
     population_size: usize,
+    // how much to print: quiet, summary, generation or debug
+    #[arg(short, long, default_value_t=Verbosity::Summary)]
+    verbosity: Verbosity,
+    // print nothing but the final JSON summary line, same as --verbosity quiet
     #[arg(short, long, default_value_t=false)]
-    verbose: bool,
+    quiet: bool,
+    // keep going past max_generations, up to this many, while the best fitness is still improving
+    #[arg(long)]
+    generation_cap: Option<usize>,
+    // average improvement of the best fitness per generation that counts as still improving
+    #[arg(long, default_value_t=0.0)]
+    min_improvement: f32,
+    // seed the initial population with the strings in this file, one per line
+    #[arg(long)]
+    warm_start: Option<String>,
+    // save the final population to this file, for --warm-start
+    #[arg(long)]
+    save: Option<String>,
+    // replace duplicate strings with mutants every generation
+    #[arg(long, default_value_t=false)]
+    dedup: bool,
+    // write the best string of every generation to this file, one line per generation, to
+    // replay the run
+    #[arg(long)]
+    replay: Option<String>,
+    // time this many evaluations, print how long the run would take and exit
+    #[arg(long)]
+    pilot: Option<usize>,
+    // stop after this many seconds, checked between generations
+    #[arg(long)]
+    time_limit: Option<f64>,
+    // write the run's settings, statistics, checkpoints, best strings and result to this
+    // directory, see ga::bundle::RunBundle
+    #[arg(long)]
+    out_dir: Option<String>,
+    // evolve strings of Unicode chars from --alphabet or --char-range instead of a-z bytes,
+    // as long as the phrase, which may then be any length
+    #[arg(long, default_value_t=false)]
+    unicode: bool,
+    // with --unicode, the chars strings are made of
+    #[arg(long)]
+    alphabet: Option<String>,
+    // with --unicode and no --alphabet, the chars strings are made of as first..last, e.g. α..ω
+    #[arg(long, default_value="a..z")]
+    char_range: String,
+    // evolve with a preset (classic-generational, steady-state, aggressive-exploration or
+    // expensive-fitness), which also decides when to stop unless --generation-cap is given
+    #[arg(long)]
+    preset: Option<ga::preset::Preset>,
 }
 
-#[derive(Clone)]
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 struct StringIndividual {
-    genes: [u8; 10]
+    genes: ga::genome::ArrayGenome<u8, 10>
+}
+
+impl StringIndividual {
+    fn new<R: Rng>(r: &mut R) -> Self {
+        StringIndividual{genes: ga::genome::ArrayGenome::random(r, |r| b'a' + r.gen_range(0..26))}
+    }
 }
 
 impl StringIndividual {
-    fn new() -> Self {
-        let mut r = rand::thread_rng();
-        StringIndividual{genes: [
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-            'a' as u8 + r.gen_range(0..26),
-        ]}
+    fn parse(line: &str) -> Option<Self> {
+        let genes: [u8; 10] = line.as_bytes().try_into().ok()?;
+        if !genes.iter().all(u8::is_ascii_lowercase) {
+            return None;
+        }
+        Some(StringIndividual{genes: genes.into()})
+    }
+
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(self.genes.genes()).unwrap()
+    }
+}
+
+impl Display for StringIndividual {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}", self.as_str())
     }
 }
 
 impl ga::Individual for StringIndividual {
-    fn mutate(&self) -> Self {
-        let mut r = rand::thread_rng();
-        let i = r.gen_range(0..self.genes.len());
-        let mut ind = self.clone();
-        ind.genes[i] = (r.gen_range(0..26) + 'a' as u8) as u8;
-        ind
+    fn mutate<R: Rng>(&self, r: &mut R) -> Self {
+        StringIndividual{genes: self.genes.mutated(r, |_, r| b'a' + r.gen_range(0..26))}
     }
 }
 
+#[derive(Clone)]
 struct StringGenerator {}
 
 impl ga::Generator<StringIndividual> for StringGenerator {
-    fn generate(&self) -> StringIndividual {
-        StringIndividual::new()
+    fn generate<R: Rng>(&self, r: &mut R) -> StringIndividual {
+        StringIndividual::new(r)
     }
+}
 
-    fn evolve(&self, a: &StringIndividual, b: &StringIndividual) -> StringIndividual {
-        let mut ind: StringIndividual = a.clone();
-        let mut r = rand::thread_rng();
-        let mid = r.gen_range(0..9);
-        for i in mid..10 {
-            ind.genes[i] = b.genes[i];
-        }
-        ind
+impl ga::Crossover<StringIndividual> for StringGenerator {
+    fn crossover<R: Rng>(&self, a: &StringIndividual, b: &StringIndividual, r: &mut R) -> StringIndividual {
+        StringIndividual{genes: a.genes.crossover(&b.genes, r.gen_range(0..9))}
+    }
+
+    fn crossover_pair<R: Rng>(&self, a: &StringIndividual, b: &StringIndividual, r: &mut R) -> (StringIndividual, StringIndividual) {
+        let (first, second) = a.genes.crossover_pair(&b.genes, r.gen_range(0..9));
+        (StringIndividual{genes: first}, StringIndividual{genes: second})
     }
 }
 
@@ -83,40 +146,242 @@ fn string_fitness(phrase: String) -> Arc<dyn Fn(&StringIndividual) -> f32 + Send
         data.push(bytes[i])
     }
     Arc::new(move |s: &StringIndividual| -> f32 {
-        let mut score = 0.0;
-        for i in 0..10 {
-            let delta = data[i as usize] as i32 - s.genes[i as usize] as i32;
-            score += 1.0 - delta.abs() as f32;
+        10.0 - ga::simd::abs_diff_sum(&data, &s.genes) as f32
+    })
+}
+
+// The chars of --unicode strings, set once from the command line.
+static ALPHABET: OnceLock<Vec<char>> = OnceLock::new();
+
+fn alphabet() -> &'static [char] {
+    ALPHABET.get().expect("alphabet not set")
+}
+
+fn random_char<R: Rng>(r: &mut R) -> char {
+    let alphabet = alphabet();
+    alphabet[r.gen_range(0..alphabet.len())]
+}
+
+// A string of Unicode scalar values from the alphabet, for --unicode.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CharIndividual {
+    genes: Vec<char>,
+}
+
+impl CharIndividual {
+    fn parse(line: &str) -> Option<Self> {
+        let genes: Vec<char> = line.chars().collect();
+        if !genes.iter().all(|c| alphabet().contains(c)) {
+            return None;
         }
-        score
+        Some(CharIndividual{genes})
+    }
+}
+
+impl Display for CharIndividual {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}", self.genes.iter().collect::<String>())
+    }
+}
+
+impl ga::Individual for CharIndividual {
+    fn mutate<R: Rng>(&self, r: &mut R) -> Self {
+        let mut ind = self.clone();
+        if !ind.genes.is_empty() {
+            let i = r.gen_range(0..ind.genes.len());
+            ind.genes[i] = random_char(r);
+        }
+        ind
+    }
+}
+
+#[derive(Clone)]
+struct CharGenerator {
+    length: usize,
+}
+
+impl ga::Generator<CharIndividual> for CharGenerator {
+    fn generate<R: Rng>(&self, r: &mut R) -> CharIndividual {
+        CharIndividual{genes: (0..self.length).map(|_| random_char(r)).collect()}
+    }
+}
+
+impl ga::Crossover<CharIndividual> for CharGenerator {
+    fn crossover<R: Rng>(&self, a: &CharIndividual, b: &CharIndividual, r: &mut R) -> CharIndividual {
+        let mid = r.gen_range(0..=a.genes.len().min(b.genes.len()));
+        CharIndividual{genes: a.genes[..mid].iter().chain(&b.genes[mid..]).copied().collect()}
+    }
+}
+
+// the length of the phrase less the number of chars that differ from it, with any chars
+// missing or extra (from a warm start) counting as different
+fn char_fitness(phrase: Vec<char>) -> Arc<dyn Fn(&CharIndividual) -> f32 + Send + Sync> {
+    Arc::new(move |s: &CharIndividual| -> f32 {
+        let different = ga::simd::char_hamming_distance(&phrase, &s.genes) + phrase.len().abs_diff(s.genes.len());
+        phrase.len() as f32 - different as f32
     })
 }
 
+// the chars first..=last of first..last
+fn parse_char_range(range: &str) -> Result<Vec<char>, String> {
+    let (first, last) = range.split_once("..").ok_or_else(|| format!("expected first..last, got {0:?}", range))?;
+    let single = |s: &str| -> Result<char, String> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(format!("expected a single char in the range, got {0:?}", s)),
+        }
+    };
+    let (first, last) = (single(first)?, single(last)?);
+    let alphabet: Vec<char> = (first..=last).collect();
+    if alphabet.is_empty() {
+        return Err(format!("char range {0:?} is empty", range));
+    }
+    Ok(alphabet)
+}
+
+// Reads the phrase from path, or stdin for -, without the line break that ends the file.
+fn read_phrase(path: &str) -> io::Result<String> {
+    let mut phrase = String::new();
+    if path == "-" {
+        io::stdin().read_to_string(&mut phrase)?;
+    } else {
+        File::open(path)?.read_to_string(&mut phrase)?;
+    }
+    if phrase.ends_with('\n') {
+        phrase.pop();
+        if phrase.ends_with('\r') {
+            phrase.pop();
+        }
+    }
+    Ok(phrase)
+}
+
 fn main() {
     let args = Args::parse();
+    let log = Log::new(if args.quiet { Verbosity::Quiet } else { args.verbosity });
+
+    let phrase = match &args.phrase_file {
+        Some(path) => read_phrase(path).unwrap_or_else(|e| {
+            eprintln!("failed to read the phrase from {0}: {1}", path, e);
+            std::process::exit(2);
+        }),
+        None => args.phrase.clone(),
+    };
+    if args.unicode {
+        let alphabet = match &args.alphabet {
+            Some(alphabet) => alphabet.chars().collect(),
+            None => parse_char_range(&args.char_range).unwrap_or_else(|e| {
+                eprintln!("{0}", e);
+                std::process::exit(2);
+            }),
+        };
+        let phrase: Vec<char> = phrase.chars().collect();
+        if let Some(c) = phrase.iter().find(|c| !alphabet.contains(c)) {
+            eprintln!("the phrase has {0:?}, which isn't in the alphabet", c);
+            std::process::exit(2);
+        }
+        ALPHABET.set(alphabet).expect("alphabet set twice");
+        let gen = CharGenerator{length: phrase.len()};
+        let target = phrase.len() as f32;
+        run(&args, &log, &gen, char_fitness(phrase), target, CharIndividual::parse);
+    } else {
+        run(&args, &log, &StringGenerator{}, string_fitness(phrase), 10.0, StringIndividual::parse);
+    }
+}
 
-    let gen = StringGenerator{};
-    let fitness = string_fitness(args.phrase.clone());
-    let mut pop = ga::Population::new(args.population_size, &gen, fitness.clone());
+// Evolves strings until one scores target, the phrase itself.
+fn run<I, G, P>(args: &Args, log: &Log, gen: &G, fitness: Arc<dyn Fn(&I) -> f32 + Send + Sync>, target: f32, parse: P)
+where
+    I: ga::Individual + Display + Hash + Eq + Send + Sync + 'static,
+    G: ga::Generator<I> + ga::Crossover<I> + Clone + Send + Sync,
+    P: Fn(&str) -> Option<I>,
+{
+    let cap = args.generation_cap.unwrap_or(args.max_generations);
+    let budget = match args.preset {
+        Some(preset) if args.generation_cap.is_none() => preset.budget(args.max_generations, args.population_size),
+        _ => ga::budget::GenerationBudget::extendable(args.max_generations, cap, args.min_improvement),
+    };
+    let config = args.preset.map(|preset| preset.config());
+    if let Some(sample) = args.pilot {
+        let estimate = budget.estimate(args.population_size, sample, gen, fitness.clone());
+        log.summary(format_args!("{0}", estimate));
+        return;
+    }
+    let pop = match &args.warm_start {
+        Some(path) => {
+            let individuals = ga::persist::read_individuals(path, parse).expect("failed to read the warm start population");
+            ga::Population::warm_start(args.population_size, individuals, gen, fitness.clone())
+        },
+        None => ga::Population::new(args.population_size, gen, fitness.clone()),
+    };
 
-    let mut generations = 1;
+    let mut engine = ga::engine::Engine::new(pop, gen.clone(), gen.clone(), fitness);
+    engine.budget = budget;
+    engine.target = Some(target);
+    if let Some(seconds) = args.time_limit {
+        engine.termination = Some(Box::new(ga::termination::TimeLimit::new(std::time::Duration::from_secs_f64(seconds))));
+    }
+    if let Some(dir) = &args.out_dir {
+        let bundle = ga::bundle::RunBundle::create(dir, |ind: &I| ind.to_string()).expect("failed to create the output directory");
+        bundle.write_config(args).expect("failed to write the run's settings");
+        engine.bundle = Some(bundle);
+    }
+    if let Some(config) = config {
+        engine.config = config;
+    }
+    // printed on its own thread, printing every generation slows a run down
+    let progress = ga::observer::Progress::new(Log::new(log.verbosity)).with_format(|ind: &I| ind.to_string());
+    let keep = progress.top;
+    engine.observers.push(Box::new(ga::observer::InBackground::spawn(progress, keep, 16, ga::observer::Backpressure::Block)));
+    let mut replay = args.replay.as_ref().map(|path| BufWriter::new(File::create(path).expect("failed to create the replay file")));
 
-    for g in 1..args.max_generations {
-        if args.verbose {
-            println!("{0})", g);
-            pop.individuals.iter().take(5).for_each(|ind| {
-                println!("\t{0} {1}", std::str::from_utf8(&ind.individual.genes).unwrap(), ind.fitness)
-            });
+    loop {
+        let best = engine.population().individuals.first().unwrap();
+        if let Some(out) = &mut replay {
+            writeln!(out, "{0} {1} {2}", engine.generation_count(), best.individual, best.fitness).expect("failed to write the replay file");
         }
-        if pop.individuals.first().unwrap().fitness == 10.0 {
+        if !engine.step() {
             break;
         }
-        pop = pop.evolve(&gen,fitness.clone());
-        generations += 1;
+        if args.dedup {
+            engine.update_population(|pop, gen, fitness| pop.deduplicated(ga::Duplicates::Mutate, gen, fitness.clone()));
+        }
+    }
+
+    if let Some(out) = &mut replay {
+        out.flush().expect("failed to write the replay file");
     }
 
-    println!("After {0} Generations:", generations);
+    engine.finish().expect("failed to write the output directory");
+    let result = engine.result();
+    log.summary(format_args!("After {0} Generations:", result.generations));
+    if result.extended {
+        log.summary(format_args!("Ran past {0} generations while the best fitness was still improving", args.max_generations));
+    }
+    if !result.solved && result.improving {
+        log.summary(format_args!("Not converged, the best fitness was still improving"));
+    }
+    let pop = engine.population();
     pop.individuals.iter().take(5).for_each(|ind| {
-        println!("\t{0} {1}", std::str::from_utf8(&ind.individual.genes).unwrap(), ind.fitness)
+        log.summary(format_args!("\t{0} {1}", ind.individual, ind.fitness))
     });
+
+    if let Some(path) = &args.save {
+        let individuals = pop.individuals.iter().map(|ind| &ind.individual);
+        ga::persist::write_individuals(path, individuals, |ind| ind.to_string()).expect("failed to save the population");
+    }
+
+    let summary = ga::output::RunSummary{
+        solved: result.solved,
+        generations: result.generations,
+        best_fitness: result.best.fitness,
+        best: result.best.individual.to_string(),
+    };
+    // printed whatever the verbosity, it's what scripts read
+    println!("{0}", summary.to_json());
+    // non-zero when the phrase wasn't found, for scripts
+    if !result.solved {
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,159 @@
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use rand::Rng;
+use rayon::prelude::*;
+use crate::{stream_rng, Fitness, Generator, Individual};
+
+// How many generations a run gets. A run normally stops at max_generations, but if the best
+// fitness is still improving by more than min_improvement per generation (on average over
+// the last window generations) it may carry on up to hard_cap. A run that stops while still
+// improving is reported as not converged.
+#[derive(Clone, Debug)]
+pub struct GenerationBudget {
+    pub max_generations: usize,
+    pub hard_cap: usize,
+    pub min_improvement: f32,
+    pub window: usize,
+    history: VecDeque<f32>,
+}
+
+impl GenerationBudget {
+    pub const DEFAULT_WINDOW: usize = 10;
+
+    // a fixed budget of max_generations, never extended
+    pub fn new(max_generations: usize) -> Self {
+        GenerationBudget{
+            max_generations,
+            hard_cap: max_generations,
+            min_improvement: 0.0,
+            window: Self::DEFAULT_WINDOW,
+            history: VecDeque::new(),
+        }
+    }
+
+    // allow extending the run up to hard_cap generations while it is still improving
+    pub fn extendable(max_generations: usize, hard_cap: usize, min_improvement: f32) -> Self {
+        GenerationBudget{
+            hard_cap: hard_cap.max(max_generations),
+            min_improvement,
+            ..Self::new(max_generations)
+        }
+    }
+
+    // record the best fitness of the latest generation
+    pub fn record(&mut self, best: f32) {
+        self.history.push_back(best);
+        while self.history.len() > self.window.max(1) + 1 {
+            self.history.pop_front();
+        }
+    }
+
+    // true if the best fitness improved by more than min_improvement per generation over the
+    // last window generations
+    pub fn improving(&self) -> bool {
+        match (self.history.front(), self.history.back()) {
+            (Some(first), Some(last)) if self.history.len() > 1 => {
+                (last - first) / (self.history.len() - 1) as f32 > self.min_improvement
+            },
+            _ => false,
+        }
+    }
+
+    // true once generation generations have been run and the run should stop
+    pub fn exhausted(&self, generation: usize) -> bool {
+        generation >= self.hard_cap || (generation >= self.max_generations && !self.improving())
+    }
+
+    // true if the run went past max_generations
+    pub fn extended(&self, generation: usize) -> bool {
+        generation > self.max_generations
+    }
+
+    // Pilot run: generates and grades sample individuals (in parallel, as evolution does) and
+    // extrapolates how long a run with populations of population_size takes, for
+    // max_generations and for the full hard_cap. Selection and breeding are not timed, so
+    // this is a lower bound for cheap fitness functions.
+    pub fn estimate<I, G, F>(&self, population_size: usize, sample: usize, generator: &G, fitness: F) -> Estimate
+    where
+        I: Individual + Send + Sync,
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let sample = sample.max(1);
+        let base: u64 = rand::thread_rng().gen();
+        let start = Instant::now();
+        (0..sample).into_par_iter().for_each(|slot| {
+            fitness.grade(generator.generate(&mut stream_rng(base, slot)));
+        });
+        let per_evaluation = start.elapsed() / sample as u32;
+        // the initial population plus one population of offspring per generation
+        let evaluations = |generations: usize| population_size * (generations + 1);
+        Estimate{
+            per_evaluation,
+            evaluations: evaluations(self.max_generations),
+            expected: per_evaluation.mul_f64(evaluations(self.max_generations) as f64),
+            worst: per_evaluation.mul_f64(evaluations(self.hard_cap) as f64),
+        }
+    }
+}
+
+// How long a run is expected to take, see GenerationBudget::estimate.
+#[derive(Clone, Copy, Debug)]
+pub struct Estimate {
+    // wall clock time per evaluation with the whole thread pool busy
+    pub per_evaluation: Duration,
+    // evaluations in a run of max_generations
+    pub evaluations: usize,
+    // for max_generations
+    pub expected: Duration,
+    // for hard_cap generations
+    pub worst: Duration,
+}
+
+impl Display for Estimate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0} evaluations at {1:?} each, about {2:?}", self.evaluations, self.per_evaluation, self.expected)?;
+        if self.worst > self.expected {
+            write!(f, " (up to {0:?} if extended)", self.worst)?;
+        }
+        Ok(())
+    }
+}
+
+// A count of fitness evaluations, the budget runs are usually compared by since it doesn't
+// depend on the machine or on how the evaluations are spread over generations. Counts every
+// individual graded by a fitness function from counting, including the initial population,
+// local search and re-grading; clones share the count.
+//
+//   let evaluations = Evaluations::new();
+//   let population = Population::new(100, &generator, evaluations.counting(fitness));
+#[derive(Clone, Debug, Default)]
+pub struct Evaluations {
+    count: Arc<AtomicUsize>,
+}
+
+impl Evaluations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // fitness, counting every individual it grades here
+    pub fn counting<I, F>(&self, fitness: F) -> Fitness<I>
+    where
+        F: Into<Fitness<I>>,
+    {
+        Fitness::Counted(Arc::new(fitness.into()), self.clone())
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add(&self) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::checkpoint::Checkpoints;
+use crate::output::RunSummary;
+use crate::persist::write_individuals;
+use crate::termination::GenerationStats;
+use crate::{Individual, Population};
+
+pub type Format<I> = Arc<dyn Fn(&I) -> String + Send + Sync>;
+
+// Everything a run produces, in one directory with the same layout for every run so runs can
+// be compared file by file and a directory is all there is to keep or share:
+//
+//   config.txt     the settings the run was started with
+//   stats.csv      generation, best and mean fitness, seconds elapsed and fitness evaluations
+//                  so far, one row per generation
+//   checkpoints/   a checkpoint every checkpoint_every generations, see Checkpoints
+//   best.txt       the best individuals of the final population, best first, one per line
+//   report.json    the RunSummary
+//
+// Fitness in stats.csv is as the fitness function returned it. Individuals are written with
+// format, which must not produce newlines. Set as Engine::bundle to have the engine fill it
+// in, or fed by hand with record, checkpoint and finish.
+pub struct RunBundle<I> {
+    pub directory: PathBuf,
+    // 0 for no checkpoints
+    pub checkpoint_every: usize,
+    // how many individuals best.txt gets
+    pub best: usize,
+    format: Format<I>,
+    stats: BufWriter<File>,
+    checkpoints: Checkpoints,
+    // the first error writing stats or checkpoints, returned by finish, so a run doesn't stop
+    // over a full disk
+    error: Option<io::Error>,
+}
+
+impl<I> RunBundle<I>
+where
+    I: Individual + Send + Sync
+{
+    pub const CONFIG: &'static str = "config.txt";
+    pub const STATS: &'static str = "stats.csv";
+    pub const CHECKPOINTS: &'static str = "checkpoints";
+    pub const BEST: &'static str = "best.txt";
+    pub const REPORT: &'static str = "report.json";
+
+    // Creates directory (and any missing parents) and starts stats.csv. Files of an earlier
+    // run in the same directory are overwritten.
+    pub fn create<P, F>(directory: P, format: F) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&I) -> String + Send + Sync + 'static,
+    {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        let mut stats = BufWriter::new(File::create(directory.join(Self::STATS))?);
+        writeln!(stats, "generation,best,mean,elapsed,evaluations")?;
+        Ok(RunBundle{
+            checkpoint_every: 10,
+            best: 10,
+            format: Arc::new(format),
+            stats,
+            checkpoints: Checkpoints::new(directory.join(Self::CHECKPOINTS), 10),
+            error: None,
+            directory,
+        })
+    }
+
+    // Writes config.txt, the settings pretty printed, e.g. a binary's parsed arguments.
+    pub fn write_config<C>(&self, config: &C) -> io::Result<()>
+    where
+        C: Debug + ?Sized
+    {
+        fs::write(self.directory.join(Self::CONFIG), format!("{0:#?}\n", config))
+    }
+
+    // Adds a row to stats.csv.
+    pub fn record(&mut self, stats: &GenerationStats) {
+        let mean = stats.mean.map(|mean| stats.direction.objective(mean).to_string()).unwrap_or_default();
+        let evaluations = stats.evaluations.map(|evaluations| evaluations.to_string()).unwrap_or_default();
+        let written = writeln!(self.stats, "{0},{1},{2},{3},{4}", stats.generation, stats.best_objective(), mean, stats.elapsed.as_secs_f64(), evaluations);
+        self.keep(written);
+    }
+
+    // Checkpoints population if it is due.
+    pub fn checkpoint(&mut self, population: &Population<I>) {
+        if self.checkpoint_every == 0 || !population.generation.is_multiple_of(self.checkpoint_every) {
+            return;
+        }
+        let format = self.format.clone();
+        let written = self.checkpoints.write(population, |individual| format(individual));
+        self.keep(written.map(|_| ()));
+    }
+
+    // Writes best.txt and report.json from the final population and flushes stats.csv,
+    // returning the first error of the run if there was one.
+    pub fn finish(mut self, population: &Population<I>, summary: &RunSummary) -> io::Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        self.stats.flush()?;
+        let best = population.individuals.iter().take(self.best).map(|entry| &entry.individual);
+        write_individuals(self.directory.join(Self::BEST), best, |individual| (self.format)(individual))?;
+        fs::write(self.directory.join(Self::REPORT), format!("{0}\n", summary.to_json()))
+    }
+
+    // individual as it is written to the bundle
+    pub fn format(&self, individual: &I) -> String {
+        (self.format)(individual)
+    }
+
+    fn keep(&mut self, result: io::Result<()>) {
+        if let Err(error) = result {
+            self.error.get_or_insert(error);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::hashing::GenomeHash;
+use crate::ScalarFitness;
+
+// Remembers the fitness of genomes already evaluated, set as Fitness::Cached, so the copies
+// and near copies a population is full of aren't evaluated again. Genomes are told apart by
+// key, by default GenomeHash, for float genomes usually a Quantization so nearly identical
+// ones hit the cache:
+//
+//   Cache::with_key(fitness, |x: &Params| Quantization::DecimalPlaces(3).hash(&x.values))
+//
+// Only for deterministic fitness functions. When more than capacity genomes are cached the
+// cache is emptied and starts over.
+pub struct Cache<I> {
+    fitness: ScalarFitness<I>,
+    key: Arc<dyn Fn(&I) -> u64 + Send + Sync>,
+    pub capacity: usize,
+    entries: Mutex<HashMap<u64, f32>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl<I> Cache<I>
+where
+    I: GenomeHash + 'static
+{
+    pub fn new(fitness: ScalarFitness<I>) -> Self {
+        Self::with_key(fitness, |individual: &I| individual.genome_hash())
+    }
+}
+
+impl<I> Cache<I> {
+    pub fn with_key<K>(fitness: ScalarFitness<I>, key: K) -> Self
+    where
+        K: Fn(&I) -> u64 + Send + Sync + 'static
+    {
+        Cache{
+            fitness,
+            key: Arc::new(key),
+            capacity: 1_000_000,
+            entries: Mutex::new(HashMap::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn evaluate(&self, individual: &I) -> f32 {
+        let key = (self.key)(individual);
+        if let Some(&fitness) = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return fitness;
+        }
+        // evaluated without holding the lock, two threads may both evaluate the same genome
+        let fitness = (self.fitness)(individual);
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let mut entries = self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if entries.len() >= self.capacity {
+            entries.clear();
+        }
+        entries.insert(key, fitness);
+        fitness
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
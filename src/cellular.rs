@@ -0,0 +1,200 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use crate::{generation_rng, stream_rng, tournament_child, Crossover, Fitness, Generator, GradedIndividual, Individual, Rates};
+
+// Which cells around a cell count as its neighbours. The grid wraps around at the edges.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Neighborhood {
+    // the 4 cells sharing an edge
+    #[default]
+    VonNeumann,
+    // the 8 cells sharing an edge or a corner
+    Moore,
+}
+
+impl Neighborhood {
+    fn offsets(&self) -> &'static [(isize, isize)] {
+        match self {
+            Neighborhood::VonNeumann => &[(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighborhood::Moore => &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)],
+        }
+    }
+}
+
+// Cellular GA: one individual per cell of a 2D grid, mating only with its neighbours. A good
+// individual can only spread one cell per generation, so it takes over the grid much more
+// slowly than in a panmictic population and different regions explore different solutions.
+//
+// Every generation each cell picks two parents from its neighbourhood (itself included) by
+// binary tournament and is replaced by their offspring if it is at least as good. All cells
+// are updated at once from the previous grid, so they run in parallel.
+pub struct CellularGrid<I>
+where
+    I: Individual + Send + Sync
+{
+    pub width: usize,
+    pub height: usize,
+    pub neighborhood: Neighborhood,
+    pub rates: Rates,
+    pub seed: Option<u64>,
+    // row by row
+    cells: Vec<GradedIndividual<I>>,
+    generation: usize,
+}
+
+impl<I> CellularGrid<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<G, F>(width: usize, height: usize, generator: &G, fitness: F) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        Self::build(width, height, generator, fitness, None)
+    }
+
+    // Like new, but seeded, so the same seed always evolves the same way.
+    pub fn new_seeded<G, F>(width: usize, height: usize, generator: &G, fitness: F, seed: u64) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        Self::build(width, height, generator, fitness, Some(seed))
+    }
+
+    fn build<G, F>(width: usize, height: usize, generator: &G, fitness: F, seed: Option<u64>) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let base: u64 = generation_rng(seed, 0).gen();
+        let cells = (0..width * height).into_par_iter().map(|cell| {
+            fitness.grade(generator.generate(&mut stream_rng(base, cell)))
+        }).collect();
+        CellularGrid{
+            width,
+            height,
+            neighborhood: Neighborhood::default(),
+            rates: Rates::new(0.8, 0.5),
+            seed,
+            cells,
+            generation: 0,
+        }
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    // row by row
+    pub fn cells(&self) -> &[GradedIndividual<I>] {
+        &self.cells
+    }
+
+    pub fn cell(&self, x: usize, y: usize) -> &GradedIndividual<I> {
+        &self.cells[y * self.width + x]
+    }
+
+    pub fn best(&self) -> Option<&GradedIndividual<I>> {
+        self.cells.iter().max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+    }
+
+    pub fn evolve<C, F>(&mut self, crossover: &C, fitness: F)
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        self.generation += 1;
+        let base: u64 = generation_rng(self.seed, self.generation).gen();
+        let cells = (0..self.cells.len()).into_par_iter().map(|cell| {
+            let child = self.breed(cell, crossover, &fitness, &mut stream_rng(base, cell));
+            if child.fitness >= self.cells[cell].fitness {
+                child
+            } else {
+                self.cells[cell].clone()
+            }
+        }).collect();
+        self.cells = cells;
+    }
+
+    // the cell itself followed by its neighbours
+    fn neighbors(&self, cell: usize) -> impl Iterator<Item = usize> + '_ {
+        let (x, y) = ((cell % self.width) as isize, (cell / self.width) as isize);
+        let (width, height) = (self.width as isize, self.height as isize);
+        std::iter::once(cell).chain(self.neighborhood.offsets().iter().map(move |(dx, dy)| {
+            ((y + dy).rem_euclid(height) * width + (x + dx).rem_euclid(width)) as usize
+        }))
+    }
+
+    fn breed<C>(&self, cell: usize, crossover: &C, fitness: &Fitness<I>, r: &mut StdRng) -> GradedIndividual<I>
+    where
+        C: Crossover<I> + Send + Sync
+    {
+        let neighbors: Vec<&GradedIndividual<I>> = self.neighbors(cell).map(|neighbor| &self.cells[neighbor]).collect();
+        let (child, _) = tournament_child(&neighbors, self.rates, crossover, r);
+        fitness.grade(child)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use rand::RngCore;
+    use crate::ScalarFitness;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn generate(r: &mut dyn RngCore) -> Number {
+        Number(r.gen_range(0.0..1.0))
+    }
+
+    fn average(a: &Number, b: &Number, _r: &mut dyn RngCore) -> Number {
+        Number((a.0 + b.0) / 2.0)
+    }
+
+    fn value() -> ScalarFitness<Number> {
+        Arc::new(|n: &Number| n.0)
+    }
+
+    #[test]
+    fn neighbors_wrap_around_the_edges() {
+        let grid = CellularGrid::new_seeded(4, 3, &generate, value(), 1);
+        // the corner at (0, 0)
+        assert_eq!(grid.neighbors(0).collect::<Vec<_>>(), vec![0, 8, 3, 1, 4]);
+    }
+
+    #[test]
+    fn cells_are_only_replaced_by_something_as_good() {
+        let mut grid = CellularGrid::new_seeded(5, 5, &generate, value(), 1);
+        grid.neighborhood = Neighborhood::Moore;
+        for _ in 0..10 {
+            let before: Vec<f32> = grid.cells().iter().map(|cell| cell.fitness).collect();
+            grid.evolve(&average, value());
+            assert!(grid.cells().iter().zip(before).all(|(cell, fitness)| cell.fitness >= fitness));
+        }
+    }
+
+    #[test]
+    fn seeded_runs_are_reproducible() {
+        let run = || {
+            let mut grid = CellularGrid::new_seeded(5, 5, &generate, value(), 7);
+            for _ in 0..5 {
+                grid.evolve(&average, value());
+            }
+            grid.cells().iter().map(|cell| cell.individual.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(run(), run());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::{Display, Formatter};
+use std::panic::{self, AssertUnwindSafe};
+use rand::Rng;
+use crate::{stream_rng, Crossover, Generator, Individual};
+
+// The operator a check failed in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Generate,
+    Mutate,
+    Crossover,
+    CrossoverPair,
+}
+
+#[derive(Clone, Debug)]
+pub struct Failure {
+    pub operator: Operator,
+    // the trial it happened in, out of Report::trials
+    pub trial: usize,
+    // the panic message or what the validity check returned
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub trials: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{0} trials, {1} failures", self.trials, self.failures.len())?;
+        for failure in &self.failures {
+            writeln!(f, "\ttrial {0} {1:?}: {2}", failure.trial, failure.operator, failure.message)?;
+        }
+        Ok(())
+    }
+}
+
+// Runs the generator's operators on n pairs of random genomes and reports every panic, so
+// broken operators show up before a long run rather than hours into it.
+pub fn operators<I, G>(generator: &G, n: usize) -> Report
+where
+    I: Individual,
+    G: Generator<I> + Crossover<I>,
+{
+    operators_with(generator, n, |_: &I| Ok(()))
+}
+
+// Like operators, and also checks every genome the operators produce with valid, which
+// returns what is wrong with a genome (wrong length, a gene out of bounds, ...) if anything.
+//
+// Panics are caught, so the panic hook is swapped for a silent one while the check runs.
+pub fn operators_with<I, G, V>(generator: &G, n: usize, valid: V) -> Report
+where
+    I: Individual,
+    G: Generator<I> + Crossover<I>,
+    V: Fn(&I) -> Result<(), String>,
+{
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let base: u64 = rand::thread_rng().gen();
+    let mut failures = Vec::new();
+    for trial in 0..n {
+        let mut r = stream_rng(base, trial);
+        let mut check = |operator: Operator, result: std::thread::Result<Vec<I>>| -> Option<Vec<I>> {
+            let message = match result {
+                Ok(genomes) => match genomes.iter().map(&valid).find_map(Result::err) {
+                    None => return Some(genomes),
+                    Some(message) => message,
+                },
+                Err(payload) => match payload.downcast_ref::<&str>() {
+                    Some(message) => format!("panicked: {0}", message),
+                    None => format!("panicked: {0}", payload.downcast_ref::<String>().map_or("", String::as_str)),
+                },
+            };
+            failures.push(Failure{operator, trial, message});
+            None
+        };
+
+        let parents = check(Operator::Generate, panic::catch_unwind(AssertUnwindSafe(|| {
+            vec![generator.generate(&mut r), generator.generate(&mut r)]
+        })));
+        let (a, b) = match parents.as_deref() {
+            Some([a, b]) => (a, b),
+            _ => continue,
+        };
+        check(Operator::Mutate, panic::catch_unwind(AssertUnwindSafe(|| vec![a.mutate(&mut r)])));
+        check(Operator::Crossover, panic::catch_unwind(AssertUnwindSafe(|| vec![generator.crossover(a, b, &mut r)])));
+        check(Operator::CrossoverPair, panic::catch_unwind(AssertUnwindSafe(|| {
+            let (first, second) = generator.crossover_pair(a, b, &mut r);
+            vec![first, second]
+        })));
+    }
+
+    panic::set_hook(hook);
+    Report{
+        trials: n,
+        failures,
+    }
+}
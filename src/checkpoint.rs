@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use crate::persist::{open, Output};
+use crate::{GradedIndividual, Individual, OptimizationDirection, Population};
+
+// Genome as formatted and fitness (as bits, to be hashable), with how many copies of it have
+// that fitness. Copies of a genome can be graded differently (a noisy or changing fitness
+// function, constraint penalties), so each fitness is counted separately.
+type Snapshot = HashMap<(String, u32), usize>;
+
+// Checkpoints of a run, written to a directory as checkpoint-<generation>.full and
+// checkpoint-<generation>.delta files (with .zst added if compress is set, see persist).
+//
+// Every full_every-th checkpoint is a full snapshot of the population, the ones in between only
+// hold what changed since the previous checkpoint: how many copies of each genome and fitness
+// were added or removed. Late in a run most of the population survives from one generation to the next,
+// so checkpoints can be taken often without writing the whole population every time.
+//
+// Genomes are stored in the caller's text format with their fitness, per-case scores are not
+// kept. Restoring needs the last full snapshot and every delta after it.
+pub struct Checkpoints {
+    pub directory: PathBuf,
+    pub full_every: usize,
+    pub compress: bool,
+    written: usize,
+    last: Snapshot,
+}
+
+impl Checkpoints {
+    pub fn new<P>(directory: P, full_every: usize) -> Self
+    where
+        P: AsRef<Path>
+    {
+        Checkpoints{
+            directory: directory.as_ref().to_path_buf(),
+            full_every: full_every.max(1),
+            compress: false,
+            written: 0,
+            last: Snapshot::new(),
+        }
+    }
+
+    // Writes a checkpoint of population, returning the file written. format must not produce
+    // newlines.
+    pub fn write<I, F>(&mut self, population: &Population<I>, format: F) -> io::Result<PathBuf>
+    where
+        I: Individual + Send + Sync,
+        F: Fn(&I) -> String,
+    {
+        let mut snapshot = Snapshot::new();
+        for entry in &population.individuals {
+            *snapshot.entry((format(&entry.individual), entry.fitness.to_bits())).or_insert(0) += 1;
+        }
+
+        let full = self.written.is_multiple_of(self.full_every);
+        let kind = if full { "full" } else { "delta" };
+        let extension = if self.compress { ".zst" } else { "" };
+        let path = self.directory.join(format!("checkpoint-{0:08}.{1}{2}", population.generation, kind, extension));
+        fs::create_dir_all(&self.directory)?;
+
+        let mut out = Output::create(&path)?;
+        let direction = match population.direction {
+            OptimizationDirection::Maximize => "max",
+            OptimizationDirection::Minimize => "min",
+        };
+        writeln!(out, "{0} {1} {2}", population.generation, population.mutation_strength, direction)?;
+        if full {
+            for ((genome, fitness), count) in &snapshot {
+                writeln!(out, "{0} {1} {2}", count, f32::from_bits(*fitness), genome)?;
+            }
+        } else {
+            for (key, &count) in &snapshot {
+                let last = self.last.get(key).copied().unwrap_or(0);
+                if last != count {
+                    writeln!(out, "{0} {1} {2}", count as i64 - last as i64, f32::from_bits(key.1), key.0)?;
+                }
+            }
+            for ((genome, fitness), &last) in &self.last {
+                if !snapshot.contains_key(&(genome.clone(), *fitness)) {
+                    writeln!(out, "-{0} {1} {2}", last, f32::from_bits(*fitness), genome)?;
+                }
+            }
+        }
+        out.finish()?;
+
+        self.written += 1;
+        self.last = snapshot;
+        Ok(path)
+    }
+
+    // Rebuilds the population of the latest checkpoint in directory from the last full
+    // snapshot and the deltas after it, sorted best first. None if there is no full snapshot.
+    pub fn restore<I, P, F>(directory: P, parse: F) -> io::Result<Option<Population<I>>>
+    where
+        I: Individual + Send + Sync,
+        P: AsRef<Path>,
+        F: Fn(&str) -> Option<I>,
+    {
+        let mut files: Vec<(usize, bool, PathBuf)> = Vec::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+            let name = name.strip_suffix(".zst").unwrap_or(name);
+            let parsed = name.strip_prefix("checkpoint-").and_then(|name| name.split_once('.')).and_then(|(generation, kind)| {
+                Some((generation.parse::<usize>().ok()?, match kind {
+                    "full" => true,
+                    "delta" => false,
+                    _ => return None,
+                }))
+            });
+            if let Some((generation, full)) = parsed {
+                files.push((generation, full, path));
+            }
+        }
+        files.sort_by_key(|(generation, _, _)| *generation);
+        let start = match files.iter().rposition(|(_, full, _)| *full) {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+
+        let mut snapshot = Snapshot::new();
+        let mut header = (0, 1.0, OptimizationDirection::Maximize);
+        for (_, full, path) in &files[start..] {
+            header = read_checkpoint(path, *full, &mut snapshot)?;
+        }
+
+        let mut individuals = Vec::new();
+        for ((genome, fitness), count) in snapshot {
+            let individual = parse(&genome).ok_or_else(|| invalid(&genome))?;
+            individuals.extend(std::iter::repeat_n(GradedIndividual::new(individual, f32::from_bits(fitness)), count));
+        }
+        individuals.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Ok(Some(Population{
+            individuals,
+            generation: header.0,
+            summary: None,
+            mutation_strength: header.1,
+            direction: header.2,
+        }))
+    }
+}
+
+fn invalid(line: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("can't parse checkpoint line {0:?}", line))
+}
+
+// Applies a full snapshot or delta to snapshot, returning the generation, mutation strength and
+// direction from its header.
+fn read_checkpoint(path: &Path, full: bool, snapshot: &mut Snapshot) -> io::Result<(usize, f32, OptimizationDirection)> {
+    let mut lines = open(path)?.lines();
+    let line = lines.next().transpose()?.unwrap_or_default();
+    let mut fields = line.split(' ');
+    let header = (|| {
+        let generation = fields.next()?.parse().ok()?;
+        let strength = fields.next()?.parse().ok()?;
+        let direction = match fields.next()? {
+            "max" => OptimizationDirection::Maximize,
+            "min" => OptimizationDirection::Minimize,
+            _ => return None,
+        };
+        Some((generation, strength, direction))
+    })().ok_or_else(|| invalid(&line))?;
+
+    if full {
+        snapshot.clear();
+    }
+    for line in lines {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(3, ' ');
+        let (count, fitness, genome) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(count), Some(fitness), Some(genome)) => (count, fitness, genome),
+            _ => return Err(invalid(&line)),
+        };
+        let count: i64 = count.parse().map_err(|_| invalid(&line))?;
+        let fitness: f32 = fitness.parse().map_err(|_| invalid(&line))?;
+        let key = (genome.to_string(), fitness.to_bits());
+        let copies = (snapshot.get(&key).copied().unwrap_or(0) as i64 + count).max(0) as usize;
+        if copies == 0 {
+            snapshot.remove(&key);
+        } else {
+            snapshot.insert(key, copies);
+        }
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Word(String);
+
+    impl Individual for Word {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    fn population(generation: usize, individuals: &[(&str, f32)]) -> Population<Word> {
+        Population{
+            individuals: individuals.iter().map(|&(word, fitness)| GradedIndividual::new(Word(word.to_string()), fitness)).collect(),
+            generation,
+            summary: None,
+            mutation_strength: 0.5,
+            direction: OptimizationDirection::Minimize,
+        }
+    }
+
+    fn contents(population: &Population<Word>) -> Vec<(String, f32)> {
+        let mut contents: Vec<(String, f32)> = population.individuals.iter().map(|entry| (entry.individual.0.clone(), entry.fitness)).collect();
+        contents.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        contents
+    }
+
+    fn round_trip(compress: bool) {
+        let directory = std::env::temp_dir().join(format!("ga-checkpoint-{0}-{1}", std::process::id(), compress));
+        let mut checkpoints = Checkpoints::new(&directory, 10);
+        checkpoints.compress = compress;
+        // copies of b graded differently have to keep their own fitness
+        checkpoints.write(&population(0, &[("a", 1.0), ("b", 2.0), ("b", 3.0), ("c", 4.0)]), |word| word.0.clone()).unwrap();
+        let last = population(1, &[("a", 1.0), ("b", 3.0), ("b", 3.0), ("d", 5.0)]);
+        let written = checkpoints.write(&last, |word| word.0.clone()).unwrap();
+        assert!(written.to_string_lossy().contains(".delta"));
+
+        let restored = Checkpoints::restore(&directory, |line| Some(Word(line.to_string()))).unwrap().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(contents(&restored), contents(&last));
+        assert_eq!(restored.generation, 1);
+        assert_eq!(restored.mutation_strength, 0.5);
+        assert_eq!(restored.direction, OptimizationDirection::Minimize);
+        assert!(restored.individuals.windows(2).all(|pair| pair[0].fitness >= pair[1].fitness));
+    }
+
+    #[test]
+    fn restores_the_last_checkpoint() {
+        round_trip(false);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn restores_the_last_compressed_checkpoint() {
+        round_trip(true);
+    }
+
+    #[test]
+    fn nothing_to_restore_without_a_full_snapshot() {
+        let directory = std::env::temp_dir().join(format!("ga-checkpoint-empty-{0}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let restored = Checkpoints::restore(&directory, |line| Some(Word(line.to_string()))).unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+        assert!(restored.is_none());
+    }
+}
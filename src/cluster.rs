@@ -0,0 +1,158 @@
+use mpi::datatype::PartitionMut;
+use mpi::topology::SimpleCommunicator;
+use mpi::traits::*;
+use mpi::Count;
+use rand::Rng;
+use std::sync::Arc;
+use crate::archipelago::{format_migrant, parse_migrant, Archipelago};
+use crate::{generation_rng, stream_rng, Crossover, Fitness, GradedIndividual, Individual};
+
+pub type Format<I> = Arc<dyn Fn(&I) -> String + Send + Sync>;
+pub type Parse<I> = Arc<dyn Fn(&str) -> Option<I> + Send + Sync>;
+
+// The island model spread over the processes of an MPI job (with rsmpi), for HPC clusters
+// where running a coordinator over TCP isn't practical. Every process evolves an Archipelago
+// of its own islands, and the ring of migration carries on across processes: every
+// migration_interval generations the emigrants of the last island of each process replace
+// individuals of the first island of the next process by rank, the last process sending to
+// the first, as the archipelago's migration policy picks them. Migrants travel as text (see
+// archipelago::format_migrant) with their grades, so they aren't evaluated again.
+//
+// Migration and best are collective, every process of the communicator has to call them the
+// same number of times. A seed in the archipelago's config is offset per process, so the
+// processes don't evolve in lockstep.
+//
+//   let universe = mpi::initialize().unwrap();
+//   let islands = Archipelago::new(4, 250, &generator, fitness.clone());
+//   let mut cluster = MpiArchipelago::new(universe.world(), islands, format, parse);
+//   for _ in 0..generations {
+//       cluster.evolve(&crossover, fitness.clone());
+//   }
+//   let best = cluster.best();
+pub struct MpiArchipelago<I>
+where
+    I: Individual + Send + Sync
+{
+    pub archipelago: Archipelago<I>,
+    // generations between migrations between processes, 0 for none, by default the
+    // archipelago's own migration interval
+    pub migration_interval: usize,
+    world: SimpleCommunicator,
+    format: Format<I>,
+    parse: Parse<I>,
+}
+
+impl<I> MpiArchipelago<I>
+where
+    I: Individual + Send + Sync
+{
+    // format must not produce newlines, parse turns its output back into the individual.
+    pub fn new<F, P>(world: SimpleCommunicator, mut archipelago: Archipelago<I>, format: F, parse: P) -> Self
+    where
+        F: Fn(&I) -> String + Send + Sync + 'static,
+        P: Fn(&str) -> Option<I> + Send + Sync + 'static,
+    {
+        // past the per island offsets of every process before this one
+        let offset = (world.rank() as usize * archipelago.islands.len()) as u64;
+        archipelago.config.seed = archipelago.config.seed.map(|seed| seed.wrapping_add(offset));
+        MpiArchipelago{
+            migration_interval: archipelago.migration_interval,
+            archipelago,
+            world,
+            format: Arc::new(format),
+            parse: Arc::new(parse),
+        }
+    }
+
+    // this process's rank and the number of processes
+    pub fn rank(&self) -> usize {
+        self.world.rank() as usize
+    }
+
+    pub fn processes(&self) -> usize {
+        self.world.size() as usize
+    }
+
+    // Evolves this process's islands by one generation (see Archipelago::evolve), then
+    // migrates between processes if it is time to.
+    pub fn evolve<C, F>(&mut self, crossover: &C, fitness: F)
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        self.archipelago = self.archipelago.evolve(crossover, fitness);
+        let generation = self.archipelago.generation();
+        if self.processes() > 1 && self.migration_interval > 0 && generation.is_multiple_of(self.migration_interval) {
+            self.migrate(generation);
+        }
+    }
+
+    // The best individual over every process, the same on all of them.
+    pub fn best(&self) -> Option<GradedIndividual<I>> {
+        let line = match self.archipelago.best() {
+            Some(best) => format_migrant(best, |individual| (self.format)(individual)) + "\n",
+            None => String::new(),
+        };
+        let mut lengths = vec![0 as Count; self.processes()];
+        self.world.all_gather_into(&(line.len() as Count), &mut lengths[..]);
+        let displacements: Vec<Count> = lengths.iter().scan(0, |offset, &length| {
+            let start = *offset;
+            *offset += length;
+            Some(start)
+        }).collect();
+        let mut gathered = vec![0u8; lengths.iter().sum::<Count>() as usize];
+        {
+            let mut partition = PartitionMut::new(&mut gathered[..], &lengths[..], &displacements[..]);
+            self.world.all_gather_varcount_into(line.as_bytes(), &mut partition);
+        }
+        self.received(&gathered).into_iter().max_by(|a, b| a.fitness.total_cmp(&b.fitness))
+    }
+
+    // the last island's emigrants go to the next process, the previous process's replace
+    // individuals of the first island
+    fn migrate(&mut self, generation: usize) {
+        let (rank, size) = (self.world.rank(), self.world.size());
+        let base: u64 = generation_rng(self.archipelago.config.seed, generation).gen();
+        let migration = self.archipelago.migration.clone();
+
+        let leaving: String = match self.archipelago.islands.last() {
+            Some(island) => {
+                let count = migration.count(island.individuals.len());
+                migration.emigrants(&island.individuals, count, &mut stream_rng(base, 0)).into_iter()
+                    .map(|index| format_migrant(&island.individuals[index], |individual| (self.format)(individual)) + "\n")
+                    .collect()
+            },
+            None => String::new(),
+        };
+        let next = self.world.process_at_rank((rank + 1) % size);
+        let previous = self.world.process_at_rank((rank + size - 1) % size);
+        // sent without blocking so every process can send to the next before receiving
+        let (arriving, _) = mpi::request::scope(|scope| {
+            let sent = next.immediate_send(scope, leaving.as_bytes());
+            let arriving = previous.receive_vec::<u8>();
+            sent.wait();
+            arriving
+        });
+        let arriving = self.received(&arriving);
+
+        let sort = self.archipelago.config.sort;
+        let Some(island) = self.archipelago.islands.first_mut() else {
+            return;
+        };
+        let room = arriving.len().min(island.individuals.len());
+        let replaced = migration.replaced(&island.individuals, room, &mut stream_rng(base, 1));
+        for (index, migrant) in replaced.into_iter().zip(arriving) {
+            island.individuals[index] = migrant;
+        }
+        sort.apply(&mut island.individuals);
+    }
+
+    // The migrants in a message from another process. Every process runs the same program, so
+    // a line that doesn't parse is a bug in format or parse rather than something to skip.
+    fn received(&self, message: &[u8]) -> Vec<GradedIndividual<I>> {
+        let text = std::str::from_utf8(message).expect("a migrant message isn't UTF-8");
+        text.lines().map(|line| {
+            parse_migrant(line, |genome| (self.parse)(genome)).unwrap_or_else(|| panic!("can't parse the migrant {0:?}", line))
+        }).collect()
+    }
+}
@@ -0,0 +1,287 @@
+use rand::seq::index::sample;
+use std::sync::Arc;
+use crate::{generation_rng, Crossover, EvolveConfig, Fitness, Generator, Individual, Population, ScalarFitness};
+
+pub type Play<A, B> = Arc<dyn Fn(&A, &B) -> (f32, f32) + Send + Sync>;
+
+// Whether both populations of a competitive coevolution are evolved against each other as
+// they were at the start of a step, or one after the other, the second against the first's
+// offspring.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Alternation {
+    #[default]
+    Simultaneous,
+    Alternating,
+}
+
+// Competitive coevolution of two populations, e.g. sorting networks against the inputs that
+// break them, or players of the two sides of a game. There is no fitness function of its
+// own, individuals are graded by play, which pits an A against a B and returns the score of
+// each. Every step opponents individuals are sampled from each population and the fitness of
+// every individual of the other population is its mean score against them. Since fitness is
+// only relative to the opponents of the moment, the parents are graded again against the new
+// opponents before they are evolved.
+//
+//   let mut game = Competitive::new(10, |a: &Attacker, d: &Defender| play(a, d));
+//   let (mut attackers, mut defenders) = game.populations(100, &attacker_gen, 100, &defender_gen);
+//   (attackers, defenders) = game.step(&attackers, &defenders, &attacker_gen, &defender_gen, &config_a, &config_b);
+pub struct Competitive<A, B> {
+    pub opponents: usize,
+    pub alternation: Alternation,
+    pub seed: Option<u64>,
+    play: Play<A, B>,
+    generation: usize,
+}
+
+impl<A, B> Competitive<A, B>
+where
+    A: Individual + Send + Sync + 'static,
+    B: Individual + Send + Sync + 'static,
+{
+    pub fn new<P>(opponents: usize, play: P) -> Self
+    where
+        P: Fn(&A, &B) -> (f32, f32) + Send + Sync + 'static
+    {
+        Competitive{
+            opponents: opponents.max(1),
+            alternation: Alternation::default(),
+            seed: None,
+            play: Arc::new(play),
+            generation: 0,
+        }
+    }
+
+    // Generates both populations and grades them against each other.
+    pub fn populations<GA, GB>(&mut self, size_a: usize, generator_a: &GA, size_b: usize, generator_b: &GB) -> (Population<A>, Population<B>)
+    where
+        GA: Generator<A> + Send + Sync,
+        GB: Generator<B> + Send + Sync,
+    {
+        let ungraded = |_: &A| 0.0f32;
+        let a = Population::new(size_a, generator_a, Arc::new(ungraded) as ScalarFitness<A>);
+        let ungraded = |_: &B| 0.0f32;
+        let b = Population::new(size_b, generator_b, Arc::new(ungraded) as ScalarFitness<B>);
+        let (against_a, against_b) = (self.fitness_a(&b), self.fitness_b(&a));
+        (a.rescore(against_a), b.rescore(against_b))
+    }
+
+    // Evolves both populations one generation, each against opponents from the other.
+    #[allow(clippy::too_many_arguments)]
+    pub fn step<CA, CB>(&mut self, a: &Population<A>, b: &Population<B>, crossover_a: &CA, crossover_b: &CB, config_a: &EvolveConfig<A>, config_b: &EvolveConfig<B>) -> (Population<A>, Population<B>)
+    where
+        CA: Crossover<A> + Send + Sync,
+        CB: Crossover<B> + Send + Sync,
+    {
+        self.generation += 1;
+        let against_b = self.fitness_a(b);
+        let simultaneous = match self.alternation {
+            Alternation::Simultaneous => Some(self.fitness_b(a)),
+            Alternation::Alternating => None,
+        };
+        let a = a.rescore(against_b.clone()).evolve_with(crossover_a, against_b, config_a);
+        let against_a = simultaneous.unwrap_or_else(|| self.fitness_b(&a));
+        let b = b.rescore(against_a.clone()).evolve_with(crossover_b, against_a, config_b);
+        (a, b)
+    }
+
+    // the fitness of an A, its mean score against opponents sampled from b
+    fn fitness_a(&self, b: &Population<B>) -> Fitness<A> {
+        let opponents = self.sample(b, 0);
+        let play = self.play.clone();
+        let fitness: ScalarFitness<A> = Arc::new(move |a: &A| {
+            opponents.iter().map(|b| play(a, b).0).sum::<f32>() / opponents.len() as f32
+        });
+        fitness.into()
+    }
+
+    // the fitness of a B, its mean score against opponents sampled from a
+    fn fitness_b(&self, a: &Population<A>) -> Fitness<B> {
+        let opponents = self.sample(a, 1);
+        let play = self.play.clone();
+        let fitness: ScalarFitness<B> = Arc::new(move |b: &B| {
+            opponents.iter().map(|a| play(a, b).1).sum::<f32>() / opponents.len() as f32
+        });
+        fitness.into()
+    }
+
+    // side keeps the samples from both populations apart in seeded runs
+    fn sample<I>(&self, population: &Population<I>, side: usize) -> Vec<I>
+    where
+        I: Individual + Send + Sync
+    {
+        let count = self.opponents.min(population.individuals.len());
+        let mut rng = generation_rng(self.seed, 2 * self.generation + side);
+        sample(&mut rng, population.individuals.len(), count).into_iter().map(|i| population.individuals[i].individual.clone()).collect()
+    }
+}
+
+pub type Assembled<C> = Arc<dyn Fn(&[C]) -> f32 + Send + Sync>;
+
+// Cooperative coevolution (Potter and De Jong) of a genome split into components, e.g. the
+// weights of each layer of a network or the variables of a large function. Each component is
+// evolved in its own population, one after the other, and a component is graded by putting it
+// together with the representatives of the other components, the best of each population
+// so far, and evaluating the whole. The representatives together are the best solution found.
+//
+//   let mut solver = Cooperative::new(|parts: &[Block]| cost(parts));
+//   let mut populations = solver.populations(8, 50, &block_gen);
+//   populations = solver.step(&populations, &block_gen, &config);
+pub struct Cooperative<C> {
+    evaluate: Assembled<C>,
+    representatives: Vec<C>,
+    fitness: f32,
+}
+
+impl<C> Cooperative<C>
+where
+    C: Individual + Send + Sync + 'static
+{
+    pub fn new<F>(evaluate: F) -> Self
+    where
+        F: Fn(&[C]) -> f32 + Send + Sync + 'static
+    {
+        Cooperative{
+            evaluate: Arc::new(evaluate),
+            representatives: Vec::new(),
+            fitness: 0.0,
+        }
+    }
+
+    // Generates a population of size (at least 1, every component needs a representative)
+    // for each of components components and grades them with a random individual of each as
+    // the first representatives.
+    pub fn populations<G>(&mut self, components: usize, size: usize, generator: &G) -> Vec<Population<C>>
+    where
+        G: Generator<C> + Send + Sync,
+    {
+        let ungraded: ScalarFitness<C> = Arc::new(|_: &C| 0.0f32);
+        let populations: Vec<Population<C>> = (0..components).map(|_| Population::new(size.max(1), generator, ungraded.clone())).collect();
+        self.representatives = populations.iter().map(|population| population.individuals[0].individual.clone()).collect();
+        self.fitness = (self.evaluate)(&self.representatives);
+        (0..components).map(|i| {
+            let population = populations[i].rescore(self.component_fitness(i));
+            self.update(i, &population);
+            population
+        }).collect()
+    }
+
+    // Evolves each population one generation in turn, each against the latest
+    // representatives of the others. populations must have come from populations.
+    pub fn step<X>(&mut self, populations: &[Population<C>], crossover: &X, config: &EvolveConfig<C>) -> Vec<Population<C>>
+    where
+        X: Crossover<C> + Send + Sync,
+    {
+        (0..populations.len()).map(|i| {
+            let fitness = self.component_fitness(i);
+            let population = populations[i].rescore(fitness.clone()).evolve_with(crossover, fitness, config);
+            self.update(i, &population);
+            population
+        }).collect()
+    }
+
+    // the best assembled solution so far, one component from each population
+    pub fn best(&self) -> &[C] {
+        &self.representatives
+    }
+
+    // what the fitness function returned for best
+    pub fn fitness(&self) -> f32 {
+        self.fitness
+    }
+
+    // the fitness of a candidate for component i, assembled with the other representatives
+    fn component_fitness(&self, i: usize) -> Fitness<C> {
+        let evaluate = self.evaluate.clone();
+        let representatives = self.representatives.clone();
+        let fitness: ScalarFitness<C> = Arc::new(move |candidate: &C| {
+            let mut assembled = representatives.clone();
+            assembled[i] = candidate.clone();
+            evaluate(&assembled)
+        });
+        fitness.into()
+    }
+
+    // makes the best of population the representative of component i if it improves the
+    // assembled solution
+    fn update(&mut self, i: usize, population: &Population<C>) {
+        let Some(best) = population.individuals.first() else {
+            return;
+        };
+        // objective turns the fitness function's value into a graded fitness and back
+        if best.fitness > population.direction.objective(self.fitness) {
+            self.representatives[i] = best.individual.clone();
+            self.fitness = population.direction.objective(best.fitness);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, RngCore};
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn generate(r: &mut dyn RngCore) -> Number {
+        Number(r.gen_range(0.0..1.0))
+    }
+
+    // the higher number wins
+    fn play(a: &Number, b: &Number) -> (f32, f32) {
+        if a.0 > b.0 { (1.0, 0.0) } else { (0.0, 1.0) }
+    }
+
+    #[test]
+    fn competitors_are_graded_by_their_wins_against_the_other_side() {
+        let mut game = Competitive::new(10, play);
+        game.seed = Some(1);
+        let (a, b) = game.populations(10, &generate, 10, &generate);
+        // every individual of the other side is an opponent
+        for entry in &a.individuals {
+            let wins = b.individuals.iter().filter(|other| entry.individual.0 > other.individual.0).count();
+            assert_eq!(entry.fitness, wins as f32 / 10.0);
+        }
+        for entry in &b.individuals {
+            let wins = a.individuals.iter().filter(|other| entry.individual.0 >= other.individual.0).count();
+            assert_eq!(entry.fitness, wins as f32 / 10.0);
+        }
+    }
+
+    fn average(a: &Number, b: &Number, _r: &mut dyn RngCore) -> Number {
+        Number((a.0 + b.0) / 2.0)
+    }
+
+    // best with every component at 1.0
+    fn cost(parts: &[Number]) -> f32 {
+        -parts.iter().map(|part| (part.0 - 1.0).abs()).sum::<f32>()
+    }
+
+    #[test]
+    fn cooperation_keeps_the_best_assembly() {
+        let mut solver = Cooperative::new(cost);
+        let config = EvolveConfig{seed: Some(1), ..EvolveConfig::default()};
+        let mut populations = solver.populations(3, 20, &generate);
+        let start = solver.fitness();
+        for _ in 0..20 {
+            populations = solver.step(&populations, &average, &config);
+            assert_eq!(solver.fitness(), cost(solver.best()));
+        }
+        assert!(solver.fitness() > start);
+        assert_eq!(solver.best().len(), 3);
+    }
+
+    #[test]
+    fn cooperation_with_single_individuals() {
+        let mut solver = Cooperative::new(cost);
+        let populations = solver.populations(2, 1, &generate);
+        assert_eq!(populations.iter().map(|population| population.individuals.len()).collect::<Vec<_>>(), vec![1, 1]);
+        assert_eq!(solver.best().len(), 2);
+    }
+}
@@ -0,0 +1,193 @@
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use crate::{GradedIndividual, Individual, ScalarFitness};
+
+// How much a total constraint violation costs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Penalty {
+    // weight * violation, the same all run
+    Static{weight: f32},
+    // (c * generation)^alpha * violation^beta (Joines and Houck), so infeasible individuals
+    // are tolerated early on while the search explores and squeezed out later. They
+    // suggest c = 0.5, alpha = beta = 2.
+    Dynamic{c: f32, alpha: f32, beta: f32},
+    // weight * violation with the weight adapted to the population (Bean and Hadj-Alouane):
+    // if the best individual was feasible for each of the last window generations the
+    // weight is divided by relax, if it was infeasible for all of them it is multiplied by
+    // tighten, so the search keeps to the boundary of the feasible region where the optimum
+    // usually lies. Both factors should be above 1.
+    Adaptive{initial: f32, relax: f32, tighten: f32, window: usize},
+}
+
+impl Penalty {
+    pub fn dynamic() -> Self {
+        Penalty::Dynamic{c: 0.5, alpha: 2.0, beta: 2.0}
+    }
+
+    pub fn adaptive(initial: f32) -> Self {
+        Penalty::Adaptive{initial, relax: 1.5, tighten: 2.0, window: 5}
+    }
+}
+
+// Constraints on individuals, each a closure returning how far an individual violates it,
+// 0 (or less) when it is satisfied. Set on EvolveConfig::constraints, after each generation
+// the violations of every survivor are totalled into GradedIndividual::violation and the
+// penalty for it is taken off its fitness before it is sorted, so feasible individuals
+// win out over infeasible ones without the fitness function knowing about the constraints.
+//
+//   Constraints::new(Penalty::Static{weight: 10.0}).with(|x: &Point| x.radius() - 1.0)
+pub struct Constraints<I> {
+    constraints: Vec<ScalarFitness<I>>,
+    pub penalty: Penalty,
+    // current weight and whether the best individual was feasible, latest last, for
+    // Penalty::Adaptive
+    adaptive: Mutex<(f32, VecDeque<bool>)>,
+}
+
+impl<I> Constraints<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new(penalty: Penalty) -> Self {
+        let weight = match penalty {
+            Penalty::Adaptive{initial, ..} => initial,
+            _ => 0.0,
+        };
+        Constraints{
+            constraints: Vec::new(),
+            penalty,
+            adaptive: Mutex::new((weight, VecDeque::new())),
+        }
+    }
+
+    pub fn with<F>(mut self, constraint: F) -> Self
+    where
+        F: Fn(&I) -> f32 + Send + Sync + 'static
+    {
+        self.constraints.push(Arc::new(constraint));
+        self
+    }
+
+    // the total violation of individual, 0 if it satisfies every constraint
+    pub fn violation(&self, individual: &I) -> f32 {
+        self.constraints.iter().map(|constraint| constraint(individual).max(0.0)).sum()
+    }
+
+    // Sets the violation of every individual, leaving the fitness alone, which is all
+    // multi-objective selection needs since dominance takes violations into account (see
+    // GradedIndividual::dominates).
+    pub fn record(&self, population: &mut [GradedIndividual<I>]) {
+        population.par_iter_mut().for_each(|entry| entry.violation = self.violation(&entry.individual));
+    }
+
+    // Sets the violation of every individual and takes its penalty off the fitness.
+    // The fitness is penalized as it stands, so it has to be fresh (reset from raw_fitness,
+    // or recomputed by fitness sharing) for each call.
+    pub fn apply(&self, population: &mut [GradedIndividual<I>], generation: usize) {
+        self.penalize(population, generation);
+        if let Some(best) = population.iter().max_by(|a, b| a.fitness.total_cmp(&b.fitness)) {
+            self.adapt(best.violation <= 0.0);
+        }
+    }
+
+    // As apply, but leaves the adaptive weight alone, for comparing individuals under the
+    // current penalty without counting it as a generation.
+    pub fn penalize(&self, population: &mut [GradedIndividual<I>], generation: usize) {
+        self.record(population);
+        let weight = self.weight();
+        population.par_iter_mut().filter(|entry| entry.violation > 0.0).for_each(|entry| {
+            entry.fitness -= match self.penalty {
+                Penalty::Static{weight} => weight * entry.violation,
+                Penalty::Dynamic{c, alpha, beta} => (c * generation as f32).powf(alpha) * entry.violation.powf(beta),
+                Penalty::Adaptive{..} => weight * entry.violation,
+            };
+        });
+    }
+
+    // updates the adaptive weight for the next generation from whether the best was feasible
+    fn adapt(&self, feasible: bool) {
+        let Penalty::Adaptive{relax, tighten, window, ..} = self.penalty else {
+            return;
+        };
+        let mut adaptive = self.adaptive.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (weight, history) = &mut *adaptive;
+        history.push_back(feasible);
+        if history.len() > window.max(1) {
+            history.pop_front();
+        }
+        if history.len() >= window.max(1) {
+            if history.iter().all(|&feasible| feasible) {
+                *weight /= relax;
+            } else if history.iter().all(|&feasible| !feasible) {
+                *weight *= tighten;
+            }
+        }
+    }
+
+    // the current weight of an adaptive penalty
+    pub fn weight(&self) -> f32 {
+        self.adaptive.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Point(f32);
+
+    impl Individual for Point {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    // x <= 1 and x >= -1
+    fn constraints(penalty: Penalty) -> Constraints<Point> {
+        Constraints::new(penalty).with(|p: &Point| p.0 - 1.0).with(|p: &Point| -1.0 - p.0)
+    }
+
+    fn graded(points: &[f32]) -> Vec<GradedIndividual<Point>> {
+        points.iter().map(|&x| GradedIndividual::new(Point(x), x)).collect()
+    }
+
+    #[test]
+    fn static_penalty_is_weighted_violation() {
+        let mut population = graded(&[0.5, 3.0, -2.0]);
+        constraints(Penalty::Static{weight: 10.0}).apply(&mut population, 0);
+        let violations: Vec<f32> = population.iter().map(|entry| entry.violation).collect();
+        let fitness: Vec<f32> = population.iter().map(|entry| entry.fitness).collect();
+        assert_eq!(violations, vec![0.0, 2.0, 1.0]);
+        assert_eq!(fitness, vec![0.5, -17.0, -12.0]);
+        // raw fitness is what the fitness function returned
+        assert_eq!(population[1].raw_fitness, 3.0);
+    }
+
+    #[test]
+    fn dynamic_penalty_grows_with_the_generation() {
+        let constraints = constraints(Penalty::dynamic());
+        let mut early = graded(&[2.0]);
+        let mut late = graded(&[2.0]);
+        constraints.apply(&mut early, 2);
+        constraints.apply(&mut late, 10);
+        // (0.5 * generation)^2 * 1^2
+        assert_eq!(early[0].fitness, 1.0);
+        assert_eq!(late[0].fitness, -23.0);
+    }
+
+    #[test]
+    fn adaptive_weight_tightens_while_the_best_is_infeasible() {
+        let constraints = constraints(Penalty::Adaptive{initial: 1.0, relax: 2.0, tighten: 2.0, window: 2});
+        // feasible best
+        constraints.apply(&mut graded(&[0.9, -3.0]), 0);
+        assert_eq!(constraints.weight(), 1.0);
+        // infeasible best, but the window isn't all infeasible yet
+        constraints.apply(&mut graded(&[9.0]), 1);
+        assert_eq!(constraints.weight(), 1.0);
+        constraints.apply(&mut graded(&[9.0]), 2);
+        assert_eq!(constraints.weight(), 2.0);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::{Fitness, Individual, Population};
+
+// When a curriculum moves on to its next stage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Milestone {
+    // once the population has been evolved this many times
+    Generation(usize),
+    // once the best fitness under the current stage reaches this
+    Fitness(f32),
+}
+
+// A fitness function that changes over the run, e.g. easy test cases first and the full set
+// later. Call update with the population before each generation and evolve with fitness():
+//
+//   pop = curriculum.update(pop);
+//   pop = pop.evolve(&gen, curriculum.fitness());
+//
+// When a stage switches the population is graded again with the new fitness, so it isn't
+// selected on stale scores.
+pub struct Curriculum<I>
+where
+    I: Individual + Send + Sync
+{
+    first: Fitness<I>,
+    stages: Vec<(Milestone, Fitness<I>)>,
+    // number of stages after the first that have been reached
+    reached: usize,
+}
+
+impl<I> Curriculum<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<F>(fitness: F) -> Self
+    where
+        F: Into<Fitness<I>>
+    {
+        Curriculum{
+            first: fitness.into(),
+            stages: Vec::new(),
+            reached: 0,
+        }
+    }
+
+    // switch to fitness once milestone is reached in the previous stage
+    pub fn then<F>(mut self, milestone: Milestone, fitness: F) -> Self
+    where
+        F: Into<Fitness<I>>
+    {
+        self.stages.push((milestone, fitness.into()));
+        self
+    }
+
+    // index of the current stage, 0 being the fitness given to new
+    pub fn stage(&self) -> usize {
+        self.reached
+    }
+
+    pub fn fitness(&self) -> Fitness<I> {
+        match self.reached {
+            0 => self.first.clone(),
+            n => self.stages[n - 1].1.clone(),
+        }
+    }
+
+    // Moves on to the next stage if its milestone has been reached (at most one stage per
+    // call), regrading the population if it does.
+    pub fn update(&mut self, population: Population<I>) -> Population<I> {
+        let milestone = match self.stages.get(self.reached) {
+            Some((milestone, _)) => *milestone,
+            None => return population,
+        };
+        let reached = match milestone {
+            Milestone::Generation(generation) => population.generation >= generation,
+            Milestone::Fitness(fitness) => population.individuals.first().is_some_and(|best| best.fitness >= fitness),
+        };
+        if !reached {
+            return population;
+        }
+        self.reached += 1;
+        population.regraded(&self.fitness())
+    }
+}
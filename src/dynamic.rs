@@ -0,0 +1,98 @@
+use rand::Rng;
+use rayon::prelude::*;
+use crate::{collect_summarized, sample_key, stream_rng, Fitness, Generator, GradedIndividual, Individual, Population};
+
+// Support for dynamic optimization, where the fitness function changes during the run (a
+// moving target, changing costs, ...). The caller signals a change with changed (or finds
+// out with detect) and records every generation so the best over time is kept, since the
+// final best alone says little when the target keeps moving.
+pub struct Environment<I>
+where
+    I: Individual + Send + Sync
+{
+    // fraction of the population, from the best down, graded again after a change
+    pub reevaluate: f32,
+    // fraction of the population, from the worst up, replaced by generated individuals after
+    // a change, to bring back diversity lost converging on the old optimum
+    pub immigrants: f32,
+    // best individual of each recorded generation, with its generation
+    history: Vec<(usize, GradedIndividual<I>)>,
+}
+
+impl<I> Environment<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new(reevaluate: f32, immigrants: f32) -> Self {
+        Environment{
+            reevaluate,
+            immigrants,
+            history: Vec::new(),
+        }
+    }
+
+    // True if the best individual no longer scores what it did, i.e. the environment changed
+    // since it was graded. Costs one evaluation.
+    pub fn detect<F>(&self, population: &Population<I>, fitness: F) -> bool
+    where
+        F: Into<Fitness<I>>
+    {
+        match population.individuals.first() {
+            Some(best) => fitness.into().grade_for(best.individual.clone(), population.direction).fitness != best.fitness,
+            None => false,
+        }
+    }
+
+    // Responds to a change: the best reevaluate fraction is graded with the new fitness and
+    // the worst immigrants fraction is replaced with fresh individuals, then the population
+    // is re-sorted. Individuals in between keep their old grades until they are replaced.
+    pub fn changed<G, F>(&self, population: Population<I>, generator: &G, fitness: F) -> Population<I>
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let size = population.individuals.len();
+        let regrade = ((size as f32 * self.reevaluate) as usize).min(size);
+        let replace = ((size as f32 * self.immigrants) as usize).min(size - regrade);
+        let keep = size - regrade - replace;
+
+        let base: u64 = rand::thread_rng().gen();
+        let mut individuals = population.individuals;
+        let old = individuals.split_off(regrade).into_iter().take(keep);
+        let regraded = individuals.into_par_iter().map(|entry| fitness.grade_for(entry.individual, population.direction));
+        let generated = (0..replace).into_par_iter().map(|slot| fitness.grade_for(generator.generate(&mut stream_rng(base, slot)), population.direction));
+        let (mut individuals, summary) = collect_summarized(regraded.chain(generated).enumerate().map(|(slot, ind)| (sample_key(base, slot), ind)));
+        individuals.extend(old);
+
+        individuals.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals,
+            generation: population.generation,
+            summary: Some(summary),
+            mutation_strength: population.mutation_strength,
+            direction: population.direction,
+        }
+    }
+
+    // keeps a copy of the population's best individual
+    pub fn record(&mut self, population: &Population<I>) {
+        if let Some(best) = population.individuals.first() {
+            self.history.push((population.generation, best.clone()));
+        }
+    }
+
+    // the best individual of every recorded generation, oldest first
+    pub fn best_over_time(&self) -> &[(usize, GradedIndividual<I>)] {
+        &self.history
+    }
+
+    // Mean of the best fitness over the recorded generations, the usual measure of how well
+    // a run tracked a moving optimum.
+    pub fn offline_performance(&self) -> f32 {
+        if self.history.is_empty() {
+            return 0.0;
+        }
+        self.history.iter().map(|(_, best)| best.fitness).sum::<f32>() / self.history.len() as f32
+    }
+}
@@ -0,0 +1,760 @@
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::budget::{Evaluations, GenerationBudget};
+use crate::bundle::RunBundle;
+use crate::observer::Observer;
+use crate::output::RunSummary;
+use crate::replacement::{Elitism, Replacement};
+use crate::selection::Selection;
+use crate::termination::{GenerationStats, MaxEvaluations, Termination, TimeLimit};
+use crate::{ConfigError, Crossover, EvolveConfig, Fitness, Generator, GradedIndividual, Individual, OptimizationDirection, Population, Rates};
+
+// How a run of an Engine ended.
+#[derive(Clone)]
+pub struct RunResult<I>
+where
+    I: Individual + Send + Sync
+{
+    // the target fitness was reached
+    pub solved: bool,
+    // generations looked at, counting the initial population as the first
+    pub generations: usize,
+    // the run went past the budget's max_generations because it was still improving
+    pub extended: bool,
+    // the run stopped while the best fitness was still improving
+    pub improving: bool,
+    pub best: GradedIndividual<I>,
+    // best's score with the validation fitness function, if the engine has one
+    pub validation: Option<f32>,
+}
+
+// When the champion of a run is graded with the validation fitness function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ValidateWhen {
+    EveryGeneration,
+    #[default]
+    AtEnd,
+}
+
+// A champion's score with the fitness function it was evolved with and with the validation
+// one, both as the fitness functions returned them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Validated {
+    pub generation: usize,
+    pub training: f32,
+    pub validation: f32,
+}
+
+// A second, holdout fitness function the best individual is graded with but that selection
+// never sees, e.g. test cases held back from the training ones. A champion that scores much
+// worse on it than on the training fitness has been overfitted, or got lucky with a noisy
+// fitness function. Set as Engine::validation.
+pub struct Validation<I> {
+    fitness: Fitness<I>,
+    pub when: ValidateWhen,
+    validated: Vec<Validated>,
+}
+
+impl<I> Validation<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<F>(fitness: F, when: ValidateWhen) -> Self
+    where
+        F: Into<Fitness<I>>,
+    {
+        Validation{
+            fitness: fitness.into(),
+            when,
+            validated: Vec::new(),
+        }
+    }
+
+    // every champion validated so far, oldest first
+    pub fn validated(&self) -> &[Validated] {
+        &self.validated
+    }
+
+    pub fn latest(&self) -> Option<&Validated> {
+        self.validated.last()
+    }
+
+    fn validate(&mut self, population: &Population<I>) {
+        let Some(best) = population.individuals.first() else {
+            return;
+        };
+        if self.latest().is_some_and(|latest| latest.generation == population.generation) {
+            return;
+        }
+        let validation = self.fitness.grade(best.individual.clone()).fitness;
+        self.validated.push(Validated{
+            generation: population.generation,
+            training: population.objective(best),
+            validation,
+        });
+    }
+}
+
+// Owns a run: the population, the operators and fitness function it is evolved with and when
+// to stop. run does the loop the binaries used to write out by hand, evolving until the best
+// fitness reaches target or the budget runs out. step evolves a single generation, for
+// callers that need to do something between generations.
+//
+//   let mut engine = Engine::new(population, generator, crossover, fitness);
+//   engine.target = Some(10.0);
+//   let result = engine.run();
+pub struct Engine<I, G, C>
+where
+    I: Individual + Send + Sync
+{
+    population: Population<I>,
+    generator: G,
+    crossover: C,
+    fitness: Fitness<I>,
+    evaluations: Evaluations,
+    pub config: EvolveConfig<I>,
+    pub budget: GenerationBudget,
+    // the run is solved once the best fitness (as graded, so negated when minimizing) is at
+    // least this
+    pub target: Option<f32>,
+    pub validation: Option<Validation<I>>,
+    // stops the run early, asked after the budget and target
+    pub termination: Option<Box<dyn Termination>>,
+    // gets the stats and checkpoints of every generation, and the report once finish is
+    // called
+    pub bundle: Option<RunBundle<I>>,
+    pub observers: Vec<Box<dyn Observer<I>>>,
+    solved: bool,
+    started: Instant,
+    // the best fitness the observers have been told about
+    improved: Option<f32>,
+    // evolution runs on this pool instead of rayon's global one, see EvolutionBuilder::threads
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl<I, G, C> Engine<I, G, C>
+where
+    I: Individual + Send + Sync,
+    G: Generator<I> + Send + Sync,
+    C: Crossover<I> + Send + Sync,
+{
+    // Runs for up to 1000 generations with the default config unless told otherwise.
+    // Evaluations are counted from here on, population has already been graded.
+    pub fn new<F>(population: Population<I>, generator: G, crossover: C, fitness: F) -> Self
+    where
+        F: Into<Fitness<I>>,
+    {
+        let evaluations = Evaluations::new();
+        Engine{
+            population,
+            generator,
+            crossover,
+            fitness: evaluations.counting(fitness),
+            evaluations,
+            config: EvolveConfig::default(),
+            budget: GenerationBudget::new(1000),
+            target: None,
+            validation: None,
+            termination: None,
+            bundle: None,
+            observers: Vec::new(),
+            solved: false,
+            started: Instant::now(),
+            improved: None,
+            pool: None,
+        }
+    }
+
+    pub fn population(&self) -> &Population<I> {
+        &self.population
+    }
+
+    pub fn into_population(self) -> Population<I> {
+        self.population
+    }
+
+    pub fn generator(&self) -> &G {
+        &self.generator
+    }
+
+    pub fn fitness(&self) -> &Fitness<I> {
+        &self.fitness
+    }
+
+    // Replaces the population with f of it, for anything done to a population between
+    // generations, e.g. Population::deduplicated.
+    pub fn update_population<F>(&mut self, f: F)
+    where
+        F: FnOnce(Population<I>, &G, &Fitness<I>) -> Population<I>
+    {
+        let empty = Population{
+            individuals: Vec::new(),
+            generation: self.population.generation,
+            summary: None,
+            mutation_strength: self.population.mutation_strength,
+            direction: self.population.direction,
+        };
+        let population = std::mem::replace(&mut self.population, empty);
+        self.population = f(population, &self.generator, &self.fitness);
+    }
+
+    // fitness evaluations so far, see budget::Evaluations
+    pub fn evaluations(&self) -> usize {
+        self.evaluations.count()
+    }
+
+    // generations looked at so far, counting the current population
+    pub fn generation_count(&self) -> usize {
+        self.population.generation + 1
+    }
+
+    pub fn best(&self) -> Option<&GradedIndividual<I>> {
+        self.population.individuals.first()
+    }
+
+    // how the current population stands, None if it is empty
+    pub fn stats(&self) -> Option<GenerationStats> {
+        let best = self.best()?;
+        Some(GenerationStats{
+            generation: self.generation_count(),
+            best: best.fitness,
+            mean: self.population.summary.as_ref().map(|summary| summary.mean()),
+            direction: self.population.direction,
+            elapsed: self.started.elapsed(),
+            evaluations: Some(self.evaluations()),
+        })
+    }
+
+    // True once the run should stop. Checks the current population against the target,
+    // records its best fitness with the budget, validates its champion if due and tells the
+    // bundle and observers about it, so it is called exactly once per generation (run and
+    // step do).
+    pub fn done(&mut self) -> bool {
+        if let Some(stats) = self.stats() {
+            if let Some(bundle) = &mut self.bundle {
+                bundle.record(&stats);
+                bundle.checkpoint(&self.population);
+            }
+            self.observe(&stats);
+        }
+        let done = self.check();
+        if let Some(validation) = &mut self.validation {
+            if done || validation.when == ValidateWhen::EveryGeneration {
+                validation.validate(&self.population);
+            }
+        }
+        done
+    }
+
+    fn observe(&mut self, stats: &GenerationStats) {
+        for observer in &mut self.observers {
+            observer.on_generation(stats, &self.population);
+        }
+        let Some(best) = self.population.individuals.first() else {
+            return;
+        };
+        if !self.improved.is_some_and(|improved| best.fitness <= improved) {
+            self.improved = Some(best.fitness);
+            for observer in &mut self.observers {
+                observer.on_improvement(best);
+            }
+        }
+    }
+
+    // The target is checked before the budget, so a run that reaches it in its last
+    // generation counts as solved, and that generation is recorded with the budget.
+    fn check(&mut self) -> bool {
+        let Some(best) = self.population.individuals.first().map(|best| best.fitness) else {
+            return true;
+        };
+        self.budget.record(best);
+        if self.target.is_some_and(|target| best >= target) {
+            self.solved = true;
+            return true;
+        }
+        if self.budget.exhausted(self.generation_count()) {
+            return true;
+        }
+        let stats = self.stats();
+        match (&mut self.termination, stats) {
+            (Some(termination), Some(stats)) => termination.should_stop(&stats),
+            _ => false,
+        }
+    }
+
+    // Evolves one generation.
+    pub fn evolve(&mut self) {
+        let evolve = || self.population.evolve_with(&self.crossover, self.fitness.clone(), &self.config);
+        self.population = match &self.pool {
+            Some(pool) => pool.install(evolve),
+            None => evolve(),
+        };
+    }
+
+    // Evolves one generation unless the run is done, returning false if it was.
+    pub fn step(&mut self) -> bool {
+        if self.done() {
+            return false;
+        }
+        self.evolve();
+        true
+    }
+
+    pub fn run(&mut self) -> RunResult<I> {
+        while self.step() {}
+        self.result()
+    }
+
+    // The run as an iterator over its generations, each evolved when the next one is asked
+    // for, so the loop can be driven with iterator adapters:
+    //
+    //   for generation in engine.generations().take(50) {
+    //       println!("{0} {1}", generation.stats.generation, generation.best.fitness);
+    //   }
+    //
+    // Every generation is yielded, the last one being the one the run stopped at.
+    pub fn generations(&mut self) -> Generations<'_, I, G, C> {
+        Generations{
+            engine: self,
+            started: false,
+            finished: false,
+        }
+    }
+
+    // Anytime optimization: runs for at most time (checked between generations) or until the
+    // run is done, whichever comes first, calling improved with every new best individual as
+    // it is found, starting with the current one. The best so far is always at hand, so a
+    // service can act on it while the run carries on or cut the run short.
+    //
+    //   engine.optimize(Duration::from_millis(200), |best| publish(&best.individual));
+    pub fn optimize<F>(&mut self, time: Duration, mut improved: F) -> RunResult<I>
+    where
+        F: FnMut(&GradedIndividual<I>),
+    {
+        let deadline = Instant::now() + time;
+        let mut best: Option<f32> = None;
+        loop {
+            if let Some(current) = self.best() {
+                if !best.is_some_and(|best| current.fitness <= best) {
+                    best = Some(current.fitness);
+                    improved(current);
+                }
+            }
+            if Instant::now() >= deadline || !self.step() {
+                break;
+            }
+        }
+        self.result()
+    }
+
+    // Tells the observers the run is over and writes the report and best individuals to the
+    // bundle, if there is one.
+    pub fn finish(&mut self) -> io::Result<()> {
+        for observer in &mut self.observers {
+            observer.on_finish();
+        }
+        let Some(bundle) = self.bundle.take() else {
+            return Ok(());
+        };
+        let result = self.result();
+        let summary = RunSummary{
+            solved: result.solved,
+            generations: result.generations,
+            // as the fitness function returned it, like stats.csv
+            best_fitness: self.population.objective(&result.best),
+            best: bundle.format(&result.best.individual),
+        };
+        bundle.finish(&self.population, &summary)
+    }
+
+    // how the run stands
+    pub fn result(&self) -> RunResult<I> {
+        RunResult{
+            solved: self.solved,
+            generations: self.generation_count(),
+            extended: self.budget.extended(self.generation_count()),
+            improving: self.budget.improving(),
+            best: self.population.individuals.first().expect("the population is empty").clone(),
+            validation: self.validation.as_ref().and_then(|validation| validation.latest()).map(|latest| latest.validation),
+        }
+    }
+}
+
+// A generation of a run, see Engine::generations.
+#[derive(Clone)]
+pub struct Generation<I>
+where
+    I: Individual + Send + Sync
+{
+    pub stats: GenerationStats,
+    pub best: GradedIndividual<I>,
+}
+
+// Iterator over the generations of an Engine's run, see Engine::generations. Dropping it
+// leaves the run where it got to, it can be carried on with step or run.
+pub struct Generations<'a, I, G, C>
+where
+    I: Individual + Send + Sync
+{
+    engine: &'a mut Engine<I, G, C>,
+    started: bool,
+    finished: bool,
+}
+
+impl<I, G, C> Iterator for Generations<'_, I, G, C>
+where
+    I: Individual + Send + Sync,
+    G: Generator<I> + Send + Sync,
+    C: Crossover<I> + Send + Sync,
+{
+    type Item = Generation<I>;
+
+    fn next(&mut self) -> Option<Generation<I>> {
+        if self.finished {
+            return None;
+        }
+        if self.started {
+            self.engine.evolve();
+        }
+        self.started = true;
+        self.finished = self.engine.done();
+        let stats = self.engine.stats()?;
+        Some(Generation{
+            stats,
+            best: self.engine.best()?.clone(),
+        })
+    }
+}
+
+// Sets up an Engine knob by knob instead of through positional arguments, checking the whole
+// setup when it is built. Anything not set is as in Engine::new, with a population of 100.
+//
+//   let mut engine = EvolutionBuilder::new(generator.clone(), generator, fitness)
+//       .population_size(500)
+//       .elitism(Elitism::Count(5))
+//       .selection(Rank::new(1.5))
+//       .max_generations(200)
+//       .seed(42)
+//       .threads(4)
+//       .build()?;
+pub struct EvolutionBuilder<I, G, C>
+where
+    I: Individual + Send + Sync
+{
+    generator: G,
+    crossover: C,
+    fitness: Fitness<I>,
+    evaluations: Evaluations,
+    population_size: usize,
+    direction: OptimizationDirection,
+    config: EvolveConfig<I>,
+    budget: GenerationBudget,
+    target: Option<f32>,
+    validation: Option<Validation<I>>,
+    termination: Option<Box<dyn Termination>>,
+    bundle: Option<RunBundle<I>>,
+    observers: Vec<Box<dyn Observer<I>>>,
+    threads: Option<usize>,
+}
+
+impl<I, G, C> EvolutionBuilder<I, G, C>
+where
+    I: Individual + Send + Sync,
+    G: Generator<I> + Send + Sync,
+    C: Crossover<I> + Send + Sync,
+{
+    pub const DEFAULT_POPULATION_SIZE: usize = 100;
+
+    pub fn new<F>(generator: G, crossover: C, fitness: F) -> Self
+    where
+        F: Into<Fitness<I>>,
+    {
+        let evaluations = Evaluations::new();
+        EvolutionBuilder{
+            generator,
+            crossover,
+            fitness: evaluations.counting(fitness),
+            evaluations,
+            population_size: Self::DEFAULT_POPULATION_SIZE,
+            direction: OptimizationDirection::Maximize,
+            config: EvolveConfig::default(),
+            budget: GenerationBudget::new(1000),
+            target: None,
+            validation: None,
+            termination: None,
+            bundle: None,
+            observers: Vec::new(),
+            threads: None,
+        }
+    }
+
+    pub fn population_size(mut self, size: usize) -> Self {
+        self.population_size = size;
+        self
+    }
+
+    pub fn direction(mut self, direction: OptimizationDirection) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    // Starts from config, for the knobs without a method of their own. Knobs set before
+    // this are overwritten, so call it first.
+    pub fn config(mut self, config: EvolveConfig<I>) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn elitism(mut self, elitism: Elitism) -> Self {
+        self.config.elitism = elitism;
+        self
+    }
+
+    pub fn selection<S>(mut self, selection: S) -> Self
+    where
+        S: Selection<I> + 'static
+    {
+        self.config.selection = Some(Arc::new(selection));
+        self
+    }
+
+    pub fn replacement<R>(mut self, replacement: R) -> Self
+    where
+        R: Replacement<I> + 'static
+    {
+        self.config.replacement = Some(Arc::new(replacement));
+        self
+    }
+
+    pub fn rates(mut self, crossover: f32, mutation: f32) -> Self {
+        self.config.rates = Some(Rates::new(crossover, mutation));
+        self
+    }
+
+    // Seeds the initial population and every generation, so the run can be replayed.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    pub fn budget(mut self, budget: GenerationBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    // a fixed budget of max_generations
+    pub fn max_generations(mut self, max_generations: usize) -> Self {
+        self.budget = GenerationBudget::new(max_generations);
+        self
+    }
+
+    // stop once the best fitness reaches target, see Engine::target
+    pub fn target(mut self, target: f32) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    // grades champions with fitness as well, see Validation
+    pub fn validation<F>(mut self, fitness: F, when: ValidateWhen) -> Self
+    where
+        F: Into<Fitness<I>>,
+    {
+        self.validation = Some(Validation::new(fitness, when));
+        self
+    }
+
+    // Stops the run early once termination says so, see Engine::termination. Given more
+    // than once, the run stops once any of them says so.
+    pub fn termination<T>(mut self, termination: T) -> Self
+    where
+        T: Termination + 'static
+    {
+        self.termination = Some(match self.termination.take() {
+            Some(earlier) => Box::new(earlier.or(termination)),
+            None => Box::new(termination),
+        });
+        self
+    }
+
+    // stops the run after limit fitness evaluations, counting the initial population, see
+    // MaxEvaluations
+    pub fn max_evaluations(self, limit: usize) -> Self {
+        self.termination(MaxEvaluations::new(limit))
+    }
+
+    // stops the run after limit of wall clock time, see TimeLimit
+    pub fn time_limit(self, limit: Duration) -> Self {
+        self.termination(TimeLimit::new(limit))
+    }
+
+    // tells observer about the run, see Engine::observers
+    pub fn observer<O>(mut self, observer: O) -> Self
+    where
+        O: Observer<I> + 'static
+    {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    // writes the run to bundle, see Engine::bundle
+    pub fn bundle(mut self, bundle: RunBundle<I>) -> Self {
+        self.bundle = Some(bundle);
+        self
+    }
+
+    // Evaluates and breeds on a thread pool of its own with this many threads, by default
+    // rayon's global pool is used.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    // Checks the setup (see EvolveConfig::validate) and, if it is sound, generates and grades
+    // the initial population. Returns every problem found, not just the first.
+    pub fn build(self) -> Result<Engine<I, G, C>, Vec<ConfigError>> {
+        let mut errors = match self.config.validate(self.population_size, &self.fitness) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors,
+        };
+        if self.budget.max_generations == 0 {
+            errors.push(ConfigError::NoGenerations);
+        }
+        if self.threads == Some(0) {
+            errors.push(ConfigError::NoThreads);
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let pool = match self.threads {
+            Some(threads) => {
+                let pool = ThreadPoolBuilder::new().num_threads(threads).build().map_err(|e| vec![ConfigError::ThreadPool(e.to_string())])?;
+                Some(Arc::new(pool))
+            },
+            None => None,
+        };
+        let generate = || {
+            let population = match self.config.seed {
+                Some(seed) => Population::new_seeded(self.population_size, &self.generator, self.fitness.clone(), seed),
+                None => Population::new(self.population_size, &self.generator, self.fitness.clone()),
+            };
+            population.with_direction(self.direction)
+        };
+        let population = match &pool {
+            Some(pool) => pool.install(generate),
+            None => generate(),
+        };
+        Ok(Engine{
+            population,
+            generator: self.generator,
+            crossover: self.crossover,
+            fitness: self.fitness,
+            evaluations: self.evaluations,
+            config: self.config,
+            budget: self.budget,
+            target: self.target,
+            validation: self.validation,
+            termination: self.termination,
+            bundle: self.bundle,
+            observers: self.observers,
+            solved: false,
+            started: Instant::now(),
+            improved: None,
+            pool,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, RngCore};
+    use super::*;
+    use crate::ScalarFitness;
+    use crate::termination::GenerationStats;
+
+    #[derive(Clone, Debug)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    type Generate = fn(&mut dyn RngCore) -> Number;
+    type Average = fn(&Number, &Number, &mut dyn RngCore) -> Number;
+
+    fn generate(r: &mut dyn RngCore) -> Number {
+        Number(r.gen_range(0.0..1.0))
+    }
+
+    fn average(a: &Number, b: &Number, _r: &mut dyn RngCore) -> Number {
+        Number((a.0 + b.0) / 2.0)
+    }
+
+    fn engine() -> Engine<Number, Generate, Average> {
+        let fitness: ScalarFitness<Number> = Arc::new(|n: &Number| n.0);
+        let population = Population::new_seeded(20, &(generate as Generate), fitness.clone(), 1);
+        let mut engine = Engine::new(population, generate as Generate, average as Average, fitness);
+        engine.config.seed = Some(1);
+        engine
+    }
+
+    #[test]
+    fn builds_the_population_size_asked_for() {
+        for size in [1, 2, 20] {
+            let fitness: ScalarFitness<Number> = Arc::new(|n: &Number| n.0);
+            let mut engine = EvolutionBuilder::new(generate as Generate, average as Average, fitness)
+                .population_size(size)
+                .max_generations(3)
+                .seed(1)
+                .build()
+                .unwrap();
+            assert_eq!(engine.population().individuals.len(), size);
+            let result = engine.run();
+            assert_eq!(engine.population().individuals.len(), size);
+            assert_eq!(result.generations, 3);
+        }
+    }
+
+    #[test]
+    fn stops_at_the_budget() {
+        let mut engine = engine();
+        engine.budget = GenerationBudget::new(5);
+        let result = engine.run();
+        assert_eq!(result.generations, 5);
+        assert!(!result.solved);
+    }
+
+    #[test]
+    fn target_reached_in_the_last_generation_counts_as_solved() {
+        let mut engine = engine();
+        engine.budget = GenerationBudget::new(1);
+        engine.target = Some(-1.0);
+        let result = engine.run();
+        assert_eq!(result.generations, 1);
+        assert!(result.solved);
+    }
+
+    #[test]
+    fn termination_stops_before_the_budget() {
+        let mut engine = engine();
+        engine.budget = GenerationBudget::new(10);
+        engine.termination = Some(Box::new(|stats: &GenerationStats| stats.generation >= 3));
+        let result = engine.run();
+        assert_eq!(result.generations, 3);
+        assert!(!result.solved);
+    }
+
+    #[test]
+    fn max_evaluations_counts_only_the_run() {
+        let mut engine = engine();
+        engine.termination = Some(Box::new(MaxEvaluations::new(1)));
+        // the initial population was graded before the engine counted, so one generation runs
+        let result = engine.run();
+        assert_eq!(result.generations, 2);
+        assert!(engine.evaluations() > 0);
+    }
+}
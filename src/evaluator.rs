@@ -0,0 +1,81 @@
+use std::sync::{Mutex, TryLockError};
+
+// A fitness function with state of its own, a VM, buffers, an rng, ... that is expensive to
+// build for every evaluation and can't be shared between threads. See Evaluators.
+pub trait Evaluator<I>: Send {
+    fn evaluate(&mut self, individual: &I) -> f32;
+}
+
+impl<I, F> Evaluator<I> for F
+where
+    F: FnMut(&I) -> f32 + Send
+{
+    fn evaluate(&mut self, individual: &I) -> f32 {
+        self(individual)
+    }
+}
+
+// Builds evaluators, one for every worker thread.
+pub trait EvaluatorFactory<I>: Send + Sync {
+    fn create(&self) -> Box<dyn Evaluator<I>>;
+}
+
+impl<I, E, F> EvaluatorFactory<I> for F
+where
+    F: Fn() -> E + Send + Sync,
+    E: Evaluator<I> + 'static,
+{
+    fn create(&self) -> Box<dyn Evaluator<I>> {
+        Box::new(self())
+    }
+}
+
+// Per worker evaluators for Fitness::Evaluators. Each rayon worker thread builds its own
+// evaluator from the factory the first time it grades an individual in a generation and
+// reuses it for the rest of that generation, so there is no shared state to contend on and
+// nothing is built per evaluation. They are dropped at the start of every generation, so
+// an evaluator can also pick per-generation state (new test inputs, ...) when it's built.
+//
+//   let fitness: Fitness<MyIndividual> = Evaluators::new(|| MyEvaluator::new()).into();
+pub struct Evaluators<I> {
+    factory: Box<dyn EvaluatorFactory<I>>,
+    // indexed by rayon thread index
+    slots: Vec<Mutex<Option<Box<dyn Evaluator<I>>>>>,
+}
+
+impl<I> Evaluators<I> {
+    pub fn new<F>(factory: F) -> Self
+    where
+        F: EvaluatorFactory<I> + 'static
+    {
+        Evaluators{
+            factory: Box::new(factory),
+            slots: (0..rayon::current_num_threads()).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    pub(crate) fn evaluate(&self, individual: &I) -> f32 {
+        let mutex = rayon::current_thread_index().and_then(|index| self.slots.get(index));
+        let mut slot = match mutex.map(|mutex| mutex.try_lock()) {
+            Some(Ok(slot)) => slot,
+            Some(Err(TryLockError::Poisoned(poisoned))) => {
+                // the evaluator panicked last time, it can't be trusted to be in a sane state
+                let mut slot = poisoned.into_inner();
+                *slot = None;
+                mutex.unwrap().clear_poison();
+                slot
+            },
+            // Outside the thread pool, or the evaluator itself ran parallel work and this thread
+            // picked up another evaluation while waiting. Use a throwaway evaluator.
+            Some(Err(TryLockError::WouldBlock)) | None => return self.factory.create().evaluate(individual),
+        };
+        slot.get_or_insert_with(|| self.factory.create()).evaluate(individual)
+    }
+
+    // drops every evaluator, the workers build new ones when they next need them
+    pub(crate) fn reset(&self) {
+        for slot in &self.slots {
+            *slot.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use rand::Rng;
+use smallvec::SmallVec;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+// A variable length genome that keeps up to N genes inline and only spills onto the heap
+// when it grows past that. For short genomes (a few dozen genes) this saves an allocation
+// for every individual in every generation.
+//
+// The helpers return a new genome rather than modifying in place, to fit with
+// Individual::mutate and Crossover::crossover which build new individuals from old ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SmallGenome<T, const N: usize> {
+    genes: SmallVec<[T; N]>,
+}
+
+impl<T, const N: usize> SmallGenome<T, N> {
+    pub fn new() -> Self {
+        SmallGenome{
+            genes: SmallVec::new(),
+        }
+    }
+
+    // a genome of len genes, gene i being f(i)
+    pub fn from_fn<F>(len: usize, f: F) -> Self
+    where
+        F: FnMut(usize) -> T
+    {
+        (0..len).map(f).collect()
+    }
+
+    // true if the genome has outgrown its inline storage
+    pub fn spilled(&self) -> bool {
+        self.genes.spilled()
+    }
+
+    pub fn push(&mut self, gene: T) {
+        self.genes.push(gene);
+    }
+
+    pub fn insert(&mut self, index: usize, gene: T) {
+        self.genes.insert(index, gene);
+    }
+
+    pub fn remove(&mut self, index: usize) -> T {
+        self.genes.remove(index)
+    }
+}
+
+impl<T, const N: usize> SmallGenome<T, N>
+where
+    T: Clone
+{
+    // copy with the gene at index replaced
+    pub fn replaced(&self, index: usize, gene: T) -> Self {
+        let mut genome = self.clone();
+        genome.genes[index] = gene;
+        genome
+    }
+
+    // copy with the gene at index removed
+    pub fn removed(&self, index: usize) -> Self {
+        self.genes[..index].iter().chain(self.genes[index + 1..].iter()).cloned().collect()
+    }
+
+    // copy with gene inserted at index
+    pub fn inserted(&self, index: usize, gene: T) -> Self {
+        let mut genes = SmallVec::with_capacity(self.genes.len() + 1);
+        genes.extend(self.genes[..index].iter().cloned());
+        genes.push(gene);
+        genes.extend(self.genes[index..].iter().cloned());
+        SmallGenome{
+            genes,
+        }
+    }
+
+    // copy with gene appended
+    pub fn pushed(&self, gene: T) -> Self {
+        self.inserted(self.genes.len(), gene)
+    }
+
+    // One point crossover, the genes of self before cut followed by the genes of other from
+    // other_cut on. The cut points may differ so the child's length can differ from both parents.
+    pub fn crossover(&self, other: &Self, cut: usize, other_cut: usize) -> Self {
+        self.genes[..cut].iter().chain(other.genes[other_cut..].iter()).cloned().collect()
+    }
+}
+
+impl<T, const N: usize> Deref for SmallGenome<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.genes
+    }
+}
+
+impl<T, const N: usize> DerefMut for SmallGenome<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.genes
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for SmallGenome<T, N> {
+    fn from_iter<It: IntoIterator<Item = T>>(iter: It) -> Self {
+        SmallGenome{
+            genes: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T, const N: usize> From<Vec<T>> for SmallGenome<T, N> {
+    fn from(genes: Vec<T>) -> Self {
+        SmallGenome{
+            genes: SmallVec::from_vec(genes),
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a SmallGenome<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.genes.iter()
+    }
+}
+
+// A genome made of segments (program chunks, network layers, ...) that are shared between
+// copies until they are modified. Cloning only copies the segment pointers and modifying a
+// segment only copies that segment, so mutating one part of a large genome doesn't copy
+// the rest of it.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SegmentedGenome<T> {
+    segments: Vec<Arc<Vec<T>>>,
+}
+
+impl<T> SegmentedGenome<T> {
+    pub fn new(segments: Vec<Vec<T>>) -> Self {
+        SegmentedGenome{
+            segments: segments.into_iter().map(Arc::new).collect(),
+        }
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn segment(&self, index: usize) -> &[T] {
+        &self.segments[index]
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &[T]> {
+        self.segments.iter().map(|segment| segment.as_slice())
+    }
+
+    // total number of genes over all segments
+    pub fn len(&self) -> usize {
+        self.segments.iter().map(|segment| segment.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.iter().all(|segment| segment.is_empty())
+    }
+
+    // all of the genes, segment by segment
+    pub fn genes(&self) -> impl Iterator<Item = &T> {
+        self.segments.iter().flat_map(|segment| segment.iter())
+    }
+
+    // true if both genomes point at the same copy of segment index
+    pub fn shares_segment(&self, other: &Self, index: usize) -> bool {
+        match (self.segments.get(index), other.segments.get(index)) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl<T> SegmentedGenome<T>
+where
+    T: Clone
+{
+    // Mutable access to one segment, copying it first if it is shared with another genome.
+    pub fn segment_mut(&mut self, index: usize) -> &mut Vec<T> {
+        Arc::make_mut(&mut self.segments[index])
+    }
+
+    // copy with segment index modified by f, the other segments stay shared
+    pub fn with_segment<F>(&self, index: usize, f: F) -> Self
+    where
+        F: FnOnce(&mut Vec<T>)
+    {
+        let mut genome = self.clone();
+        f(genome.segment_mut(index));
+        genome
+    }
+
+    // copy with segment index replaced
+    pub fn replaced_segment(&self, index: usize, segment: Vec<T>) -> Self {
+        let mut genome = self.clone();
+        genome.segments[index] = Arc::new(segment);
+        genome
+    }
+
+    // The segments of self before cut followed by the segments of other from cut on. No genes
+    // are copied, the child shares every segment with one of its parents.
+    pub fn crossover(&self, other: &Self, cut: usize) -> Self {
+        SegmentedGenome{
+            segments: self.segments[..cut].iter().chain(other.segments[cut..].iter()).cloned().collect(),
+        }
+    }
+}
+
+// A genome of exactly N genes, known at compile time, e.g. ArrayGenome<u8, 10> for a ten
+// letter string. The genes live inline in a plain array, so a genome never allocates, and
+// since every index the helpers use is bounded by N the compiler can drop the bounds checks
+// and unroll the loops. For simple benchmark problems this makes the genome itself cost
+// next to nothing next to selection and grading.
+//
+// Like SmallGenome the helpers return a new genome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ArrayGenome<T, const N: usize> {
+    genes: [T; N],
+}
+
+impl<T, const N: usize> ArrayGenome<T, N>
+where
+    T: Copy
+{
+    pub fn new(genes: [T; N]) -> Self {
+        ArrayGenome{
+            genes,
+        }
+    }
+
+    // gene i being f(i)
+    pub fn from_fn<F>(f: F) -> Self
+    where
+        F: FnMut(usize) -> T
+    {
+        ArrayGenome{
+            genes: std::array::from_fn(f),
+        }
+    }
+
+    // every gene drawn by gene
+    pub fn random<R, F>(r: &mut R, mut gene: F) -> Self
+    where
+        R: Rng,
+        F: FnMut(&mut R) -> T,
+    {
+        Self::from_fn(|_| gene(r))
+    }
+
+    pub fn genes(&self) -> &[T; N] {
+        &self.genes
+    }
+
+    pub fn into_genes(self) -> [T; N] {
+        self.genes
+    }
+
+    // copy with the gene at index replaced
+    pub fn replaced(&self, index: usize, gene: T) -> Self {
+        let mut genome = *self;
+        genome.genes[index] = gene;
+        genome
+    }
+
+    // copy with one gene, picked at random, replaced by mutate of it
+    pub fn mutated<R, F>(&self, r: &mut R, mutate: F) -> Self
+    where
+        R: Rng,
+        F: FnOnce(T, &mut R) -> T,
+    {
+        if N == 0 {
+            return *self;
+        }
+        let index = r.gen_range(0..N);
+        self.replaced(index, mutate(self.genes[index], r))
+    }
+
+    // One point crossover, the genes of self before cut followed by the genes of other.
+    pub fn crossover(&self, other: &Self, cut: usize) -> Self {
+        Self::from_fn(|i| if i < cut { self.genes[i] } else { other.genes[i] })
+    }
+
+    // both children of a one point crossover at cut
+    pub fn crossover_pair(&self, other: &Self, cut: usize) -> (Self, Self) {
+        (self.crossover(other, cut), other.crossover(self, cut))
+    }
+
+    // uniform crossover, each gene from either parent with even odds
+    pub fn uniform<R>(&self, other: &Self, r: &mut R) -> Self
+    where
+        R: Rng,
+    {
+        Self::from_fn(|i| if r.gen::<bool>() { self.genes[i] } else { other.genes[i] })
+    }
+}
+
+impl<T, const N: usize> Default for ArrayGenome<T, N>
+where
+    T: Copy + Default
+{
+    fn default() -> Self {
+        ArrayGenome{
+            genes: [T::default(); N],
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for ArrayGenome<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.genes
+    }
+}
+
+impl<T, const N: usize> DerefMut for ArrayGenome<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        &mut self.genes
+    }
+}
+
+impl<T, const N: usize> From<[T; N]> for ArrayGenome<T, N> {
+    fn from(genes: [T; N]) -> Self {
+        ArrayGenome{
+            genes,
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a ArrayGenome<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.genes.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_genome_spills_past_its_inline_capacity() {
+        let genome: SmallGenome<u8, 4> = SmallGenome::from_fn(4, |i| i as u8);
+        assert!(!genome.spilled());
+        let longer = genome.pushed(4);
+        assert!(longer.spilled());
+        assert_eq!(&longer[..], &[0, 1, 2, 3, 4]);
+        // the helpers leave the original alone
+        assert_eq!(&genome[..], &[0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn small_genome_helpers() {
+        let genome: SmallGenome<u8, 8> = vec![1, 2, 3].into();
+        assert_eq!(&genome.replaced(1, 9)[..], &[1, 9, 3]);
+        assert_eq!(&genome.removed(0)[..], &[2, 3]);
+        assert_eq!(&genome.inserted(1, 7)[..], &[1, 7, 2, 3]);
+        let other: SmallGenome<u8, 8> = vec![4, 5, 6, 7].into();
+        assert_eq!(&genome.crossover(&other, 2, 1)[..], &[1, 2, 5, 6, 7]);
+    }
+
+    #[test]
+    fn segmented_genome_copies_only_what_is_modified() {
+        let genome = SegmentedGenome::new(vec![vec![1, 2], vec![3], vec![4, 5, 6]]);
+        let mutant = genome.with_segment(1, |segment| segment.push(9));
+        assert!(mutant.shares_segment(&genome, 0));
+        assert!(!mutant.shares_segment(&genome, 1));
+        assert!(mutant.shares_segment(&genome, 2));
+        assert_eq!(mutant.genes().copied().collect::<Vec<_>>(), vec![1, 2, 3, 9, 4, 5, 6]);
+        assert_eq!(genome.len(), 6);
+    }
+
+    #[test]
+    fn segmented_crossover_shares_every_segment() {
+        let a = SegmentedGenome::new(vec![vec![1], vec![2], vec![3]]);
+        let b = SegmentedGenome::new(vec![vec![4], vec![5], vec![6]]);
+        let child = a.crossover(&b, 1);
+        assert!(child.shares_segment(&a, 0));
+        assert!(child.shares_segment(&b, 1));
+        assert!(child.shares_segment(&b, 2));
+        assert_eq!(child.genes().copied().collect::<Vec<_>>(), vec![1, 5, 6]);
+    }
+
+    #[test]
+    fn array_genome_crossover() {
+        let a = ArrayGenome::new([1, 2, 3, 4]);
+        let b = ArrayGenome::new([5, 6, 7, 8]);
+        let (first, second) = a.crossover_pair(&b, 1);
+        assert_eq!(first.into_genes(), [1, 6, 7, 8]);
+        assert_eq!(second.into_genes(), [5, 2, 3, 4]);
+    }
+
+    #[test]
+    fn array_genome_mutates_one_gene() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let genome = ArrayGenome::new([0u8; 10]);
+        let mutant = genome.mutated(&mut rng, |gene, _| gene + 1);
+        assert_eq!(mutant.iter().filter(|&&gene| gene == 1).count(), 1);
+        assert_eq!(mutant.iter().sum::<u8>(), 1);
+    }
+}
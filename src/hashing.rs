@@ -0,0 +1,142 @@
+use std::hash::{Hash, Hasher};
+
+// A 64 bit hash of a genome, for anything that needs to recognise genomes it has seen
+// before. Caches (see cache::Cache) treat genomes that hash the same as the same genome;
+// deduplication (see Population::deduplicated) only uses the hash to find candidates and
+// confirms them with ==.
+//
+// Every Hash type has one. Genomes of floats, which aren't Hash, can implement it with a
+// Quantization, which also makes genomes that only differ by rounding noise hit the same
+// cache entry:
+//
+//   impl GenomeHash for Point {
+//       fn genome_hash(&self) -> u64 {
+//           Quantization::DecimalPlaces(4).hash(&self.coordinates)
+//       }
+//   }
+pub trait GenomeHash {
+    fn genome_hash(&self) -> u64;
+}
+
+impl<T> GenomeHash for T
+where
+    T: Hash + ?Sized
+{
+    fn genome_hash(&self) -> u64 {
+        let mut hasher = FastHasher::default();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// How finely float genes are told apart when hashing. Genes that round to the same value
+// count as the same, so nearly identical float genomes hit a cache (see cache::Cache).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quantization {
+    // rounded to this many digits after the decimal point
+    DecimalPlaces(u32),
+    // rounded to the nearest multiple of the epsilon
+    Grid(f64),
+}
+
+impl Quantization {
+    // the width of a bucket
+    pub fn step(&self) -> f64 {
+        match *self {
+            Quantization::DecimalPlaces(places) => 10f64.powi(-(places as i32)),
+            Quantization::Grid(epsilon) => epsilon,
+        }
+    }
+
+    pub fn hash(&self, values: &[f32]) -> u64 {
+        let mut hasher = FastHasher::default();
+        values.len().hash(&mut hasher);
+        for &value in values {
+            quantize(value as f64, self.step()).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    pub fn hash_f64(&self, values: &[f64]) -> u64 {
+        quantized_hash_f64(values, self.step())
+    }
+}
+
+// Hash of values rounded to multiples of step, so values within about step of each other
+// usually hash the same. -0.0 and 0.0 hash the same, as do all NaNs.
+pub fn quantized_hash(values: &[f32], step: f32) -> u64 {
+    let mut hasher = FastHasher::default();
+    values.len().hash(&mut hasher);
+    for &value in values {
+        quantize(value as f64, step as f64).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// as quantized_hash, for f64 genomes
+pub fn quantized_hash_f64(values: &[f64], step: f64) -> u64 {
+    let mut hasher = FastHasher::default();
+    values.len().hash(&mut hasher);
+    for &value in values {
+        quantize(value, step).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn quantize(value: f64, step: f64) -> i64 {
+    if value.is_nan() {
+        return i64::MIN;
+    }
+    // saturates for infinities, +0.0 and -0.0 both round to 0
+    (value / step).round() as i64
+}
+
+// The Fx hash (as used in rustc), much faster than the default SipHash for the short keys
+// genomes hash to. Not resistant to collisions crafted on purpose, which doesn't matter here.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FastHasher {
+    hash: u64,
+}
+
+impl FastHasher {
+    const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(Self::SEED);
+    }
+}
+
+impl Hasher for FastHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0u8; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.add(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
@@ -0,0 +1,232 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use rand::Rng;
+use rayon::prelude::*;
+use crate::{by_fitness, generation_rng, stream_rng, EvolveConfig, Fitness, GradedIndividual, Individual, Population};
+
+// A stretch of generations that all had the same best individual.
+#[derive(Clone)]
+pub struct EliteRun<I>
+where
+    I: Individual + Send + Sync
+{
+    pub first_generation: usize,
+    pub last_generation: usize,
+    pub elite: GradedIndividual<I>,
+}
+
+// The best individual of every generation, for looking back at how the champion evolved.
+// With elitism the best often stays the same for many generations, so it is only stored
+// again when it changes.
+#[derive(Clone)]
+pub struct EliteHistory<I>
+where
+    I: Individual + Send + Sync
+{
+    runs: Vec<EliteRun<I>>,
+}
+
+impl<I> EliteHistory<I>
+where
+    I: Individual + PartialEq + Send + Sync
+{
+    pub fn new() -> Self {
+        EliteHistory{
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, population: &Population<I>) {
+        let best = match population.individuals.first() {
+            Some(best) => best,
+            None => return,
+        };
+        match self.runs.last_mut() {
+            Some(run) if run.elite.individual == best.individual => run.last_generation = population.generation,
+            _ => self.runs.push(EliteRun{
+                first_generation: population.generation,
+                last_generation: population.generation,
+                elite: best.clone(),
+            }),
+        }
+    }
+
+    pub fn runs(&self) -> &[EliteRun<I>] {
+        &self.runs
+    }
+
+    // the best individual of generation, if it was recorded
+    pub fn at(&self, generation: usize) -> Option<&GradedIndividual<I>> {
+        let i = self.runs.partition_point(|run| run.last_generation < generation);
+        self.runs.get(i).filter(|run| run.first_generation <= generation).map(|run| &run.elite)
+    }
+
+    // Writes one line per run: first and last generation, fitness and the genome as
+    // formatted by format (which must not produce newlines).
+    pub fn write<P, F>(&self, path: P, format: F) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        F: Fn(&I) -> String,
+    {
+        let mut out = BufWriter::new(File::create(path)?);
+        for run in &self.runs {
+            writeln!(out, "{0} {1} {2} {3}", run.first_generation, run.last_generation, run.elite.fitness, format(&run.elite.individual))?;
+        }
+        out.flush()
+    }
+}
+
+impl<I> Default for EliteHistory<I>
+where
+    I: Individual + PartialEq + Send + Sync
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The best capacity distinct individuals seen over a whole run, whether or not they are
+// still in the population. With a noisy fitness function an elite can be pushed out by a
+// luckily graded newcomer, this keeps it around anyway.
+#[derive(Clone)]
+pub struct HallOfFame<I>
+where
+    I: Individual + Send + Sync
+{
+    pub capacity: usize,
+    // best first
+    members: Vec<GradedIndividual<I>>,
+}
+
+impl<I> HallOfFame<I>
+where
+    I: Individual + PartialEq + Send + Sync
+{
+    pub fn new(capacity: usize) -> Self {
+        HallOfFame{
+            capacity,
+            members: Vec::with_capacity(capacity + 1),
+        }
+    }
+
+    // Considers the best capacity individuals of the population, which is all that can make
+    // it in.
+    pub fn record(&mut self, population: &Population<I>) {
+        for entry in population.individuals.iter().take(self.capacity) {
+            self.insert(entry);
+        }
+    }
+
+    // Adds a copy of entry if it is good enough. An individual that is already in keeps the
+    // best fitness it was ever graded with.
+    pub fn insert(&mut self, entry: &GradedIndividual<I>) {
+        if let Some(i) = self.members.iter().position(|member| member.individual == entry.individual) {
+            if entry.fitness <= self.members[i].fitness {
+                return;
+            }
+            self.members.remove(i);
+        } else if self.members.len() >= self.capacity && self.members.last().is_none_or(|worst| entry.fitness <= worst.fitness) {
+            return;
+        }
+        let at = self.members.partition_point(|member| member.fitness >= entry.fitness);
+        self.members.insert(at, entry.clone());
+        self.members.truncate(self.capacity);
+    }
+
+    // best first
+    pub fn members(&self) -> &[GradedIndividual<I>] {
+        &self.members
+    }
+
+    pub fn best(&self) -> Option<&GradedIndividual<I>> {
+        self.members.first()
+    }
+
+    // Replaces the worst rate of the population with mutants of random members, to keep
+    // searching around the best regions found so far after the population moved on. Does
+    // nothing while the hall of fame is empty. The mutants come from config's seed and the
+    // population's generation, and the population is sorted by config's sort mode afterwards.
+    pub fn immigrate<F>(&self, population: &mut Population<I>, rate: f32, fitness: F, config: &EvolveConfig<I>)
+    where
+        F: Into<Fitness<I>>,
+    {
+        let count = ((population.individuals.len() as f32 * rate.clamp(0.0, 1.0)).round() as usize).min(population.individuals.len());
+        if self.members.is_empty() || count == 0 {
+            return;
+        }
+        let fitness = fitness.into();
+        let base: u64 = generation_rng(config.seed, population.generation).gen();
+        let immigrants: Vec<GradedIndividual<I>> = (0..count).into_par_iter().map(|slot| {
+            let mut r = stream_rng(base, slot);
+            let member = &self.members[r.gen_range(0..self.members.len())];
+            fitness.grade_for(member.individual.mutate_scaled(&mut r, population.mutation_strength), population.direction)
+        }).collect();
+        // the population may only be partly sorted (SortMode::TopK)
+        let order = by_fitness(&population.individuals);
+        for (&index, immigrant) in order[order.len() - count..].iter().zip(immigrants) {
+            population.individuals[index] = immigrant;
+        }
+        config.sort.apply(&mut population.individuals);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::{OptimizationDirection, ScalarFitness, SortMode};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn value() -> ScalarFitness<Number> {
+        Arc::new(|n: &Number| n.0)
+    }
+
+    // only the best is in front, the rest in no particular order as under SortMode::TopK(1)
+    fn partly_sorted() -> Population<Number> {
+        let fitness = Fitness::from(value());
+        Population{
+            individuals: [5.0, 1.0, 4.0, 0.0, 3.0].into_iter().map(|x| fitness.grade(Number(x))).collect(),
+            generation: 3,
+            summary: None,
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    #[test]
+    fn immigrants_replace_the_worst() {
+        let mut hall_of_fame = HallOfFame::new(1);
+        hall_of_fame.insert(&Fitness::from(value()).grade(Number(10.0)));
+        let config = EvolveConfig{seed: Some(7), sort: SortMode::TopK(1), ..EvolveConfig::default()};
+        let mut population = partly_sorted();
+        hall_of_fame.immigrate(&mut population, 0.4, value(), &config);
+
+        let mut values: Vec<f32> = population.individuals.iter().map(|entry| entry.individual.0).collect();
+        assert!(values[0] > 9.0);
+        values.sort_by(|a, b| a.total_cmp(b));
+        assert_eq!(values[..3], [3.0, 4.0, 5.0]);
+        assert!(values[3..].iter().all(|&x| x > 9.0));
+    }
+
+    #[test]
+    fn immigration_is_reproducible_with_a_seed() {
+        let mut hall_of_fame = HallOfFame::new(1);
+        hall_of_fame.insert(&Fitness::from(value()).grade(Number(10.0)));
+        let config = EvolveConfig{seed: Some(7), ..EvolveConfig::default()};
+        let immigrated = || {
+            let mut population = partly_sorted();
+            hall_of_fame.immigrate(&mut population, 0.4, value(), &config);
+            population.individuals.into_iter().map(|entry| entry.individual).collect::<Vec<_>>()
+        };
+        assert_eq!(immigrated(), immigrated());
+    }
+}
@@ -0,0 +1,90 @@
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::sync::{Arc, Mutex};
+use crate::{stream_rng, Fitness, Generator, GradedIndividual, Individual, Lineage, OptimizationDirection};
+
+// What Hypermutation does while the run is stagnating.
+#[derive(Clone)]
+pub enum Response<I> {
+    // the mutation strength and mutation rate (if rates are set) are multiplied by factor
+    Boost{factor: f32},
+    // fraction of the offspring, the worst, are replaced by new random individuals every
+    // generation
+    Immigrants{fraction: f32, generate: Arc<dyn Fn(&mut StdRng) -> I + Send + Sync>},
+}
+
+impl<I> Response<I> {
+    pub fn immigrants<G>(fraction: f32, generator: G) -> Self
+    where
+        G: Generator<I> + Send + Sync + 'static
+    {
+        Response::Immigrants{fraction, generate: Arc::new(move |r: &mut StdRng| generator.generate(r))}
+    }
+}
+
+// Stagnation triggered hypermutation, set as EvolveConfig::hypermutation. Once the best
+// fitness hasn't improved by more than min_improvement for patience generations the response
+// kicks in, and it stays on until the best fitness improves again.
+//
+//   Hypermutation::new(20, Response::Boost{factor: 4.0})
+pub struct Hypermutation<I> {
+    pub patience: usize,
+    pub min_improvement: f32,
+    pub response: Response<I>,
+    // best fitness so far and generations since it last improved
+    state: Mutex<(f32, usize)>,
+}
+
+impl<I> Hypermutation<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new(patience: usize, response: Response<I>) -> Self {
+        Hypermutation{
+            patience,
+            min_improvement: 0.0,
+            response,
+            state: Mutex::new((f32::NEG_INFINITY, 0)),
+        }
+    }
+
+    // records the best fitness of the population about to be evolved
+    pub(crate) fn record(&self, best: f32) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if best > state.0 + self.min_improvement || state.0 == f32::NEG_INFINITY {
+            *state = (best, 0);
+        } else {
+            state.1 += 1;
+        }
+    }
+
+    // true while the response is on
+    pub fn active(&self) -> bool {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).1 >= self.patience.max(1)
+    }
+
+    // what the mutation strength and rate are multiplied by this generation
+    pub(crate) fn boost(&self) -> f32 {
+        match self.response {
+            Response::Boost{factor} if self.active() => factor,
+            _ => 1.0,
+        }
+    }
+
+    // Replaces the worst offspring with immigrants, if that is the response and it is on. An
+    // immigrant keeps the lineage of the offspring it replaced.
+    pub(crate) fn immigrate(&self, offspring: &mut [(GradedIndividual<I>, Lineage)], fitness: &Fitness<I>, direction: OptimizationDirection, base: u64) {
+        let Response::Immigrants{fraction, generate} = &self.response else {
+            return;
+        };
+        if !self.active() {
+            return;
+        }
+        let count = ((offspring.len() as f32 * fraction.clamp(0.0, 1.0)).round() as usize).min(offspring.len());
+        offspring.par_sort_by(|a, b| b.0.fitness.total_cmp(&a.0.fitness));
+        let keep = offspring.len() - count;
+        offspring[keep..].par_iter_mut().enumerate().for_each(|(slot, (entry, _))| {
+            *entry = fitness.grade_for(generate(&mut stream_rng(base, slot)), direction);
+        });
+    }
+}
@@ -0,0 +1,148 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::{Individual, Population};
+
+// The hypervolume indicator: the volume of objective space dominated by a set of points and
+// bounded by a reference point, which grows as a Pareto front moves forward or spreads out.
+// Tracking it per generation shows whether a multi-objective run is still converging.
+//
+// Objectives are higher is better, as in GradedIndividual::objectives (so negated costs when
+// minimizing), and the reference point should be worse than every point on every objective,
+// points that aren't count for nothing. Exact for up to 3 objectives, estimated from samples
+// random points beyond that. The samples are seeded the same way every time so the estimates
+// of successive generations can be compared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hypervolume {
+    pub reference: Vec<f32>,
+    pub samples: usize,
+    pub seed: u64,
+}
+
+impl Hypervolume {
+    pub fn new(reference: Vec<f32>) -> Self {
+        Hypervolume{
+            reference,
+            samples: 100_000,
+            seed: 0,
+        }
+    }
+
+    // hypervolume of the population's Pareto front
+    pub fn of_population<I>(&self, population: &Population<I>) -> f32
+    where
+        I: Individual + Send + Sync
+    {
+        let front: Vec<&[f32]> = population.pareto_front().into_iter().map(|entry| entry.objectives()).collect();
+        self.of(&front)
+    }
+
+    // Hypervolume of points, each with as many objectives as the reference point. Points with
+    // any other number of objectives are skipped.
+    pub fn of(&self, points: &[&[f32]]) -> f32 {
+        let reference = &self.reference;
+        let points: Vec<&[f32]> = points.iter().copied()
+            .filter(|point| point.len() == reference.len() && point.iter().zip(reference).all(|(p, r)| p > r))
+            .collect();
+        if points.is_empty() {
+            return 0.0;
+        }
+        match reference.len() {
+            0 => 0.0,
+            1 => points.iter().map(|point| point[0]).fold(f32::MIN, f32::max) - reference[0],
+            2 => area(points.iter().map(|point| (point[0], point[1])).collect(), reference[0], reference[1]),
+            3 => volume(&points, reference),
+            _ => self.estimate(&points),
+        }
+    }
+
+    // Monte Carlo estimate, the fraction of random points in the box between the reference
+    // point and the best value of every objective that some point dominates
+    fn estimate(&self, points: &[&[f32]]) -> f32 {
+        let reference = &self.reference;
+        let upper: Vec<f32> = (0..reference.len()).map(|i| points.iter().map(|point| point[i]).fold(f32::MIN, f32::max)).collect();
+        let box_volume: f64 = upper.iter().zip(reference).map(|(u, r)| (u - r) as f64).product();
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut sample = vec![0.0; reference.len()];
+        let mut hits = 0;
+        for _ in 0..self.samples {
+            for (i, value) in sample.iter_mut().enumerate() {
+                *value = rng.gen_range(reference[i]..upper[i]);
+            }
+            if points.iter().any(|point| point.iter().zip(&sample).all(|(p, s)| p >= s)) {
+                hits += 1;
+            }
+        }
+        (box_volume * hits as f64 / self.samples.max(1) as f64) as f32
+    }
+}
+
+// exact 2 objective hypervolume, sweeping the points from the best first objective down
+fn area(mut points: Vec<(f32, f32)>, reference_x: f32, reference_y: f32) -> f32 {
+    points.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut area = 0.0;
+    let mut covered_y = reference_y;
+    for (x, y) in points {
+        if y > covered_y {
+            area += (x - reference_x) * (y - covered_y);
+            covered_y = y;
+        }
+    }
+    area
+}
+
+// exact 3 objective hypervolume, as slices along the third objective each with the area of the
+// points at least that good on it
+fn volume(points: &[&[f32]], reference: &[f32]) -> f32 {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| b[2].total_cmp(&a[2]));
+    let mut volume = 0.0;
+    for i in 0..points.len() {
+        let next_z = points.get(i + 1).map_or(reference[2], |point| point[2]);
+        let depth = points[i][2] - next_z;
+        if depth > 0.0 {
+            let slice = points[..=i].iter().map(|point| (point[0], point[1])).collect();
+            volume += area(slice, reference[0], reference[1]) * depth;
+        }
+    }
+    volume
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn area_of_a_staircase() {
+        // rectangles of 3 x 1, 2 x 2 and 1 x 3 from the origin, 6 together
+        assert_eq!(area(vec![(1.0, 3.0), (3.0, 1.0), (2.0, 2.0)], 0.0, 0.0), 6.0);
+        // a dominated point adds nothing
+        assert_eq!(area(vec![(1.0, 3.0), (3.0, 1.0), (2.0, 2.0), (1.0, 1.0)], 0.0, 0.0), 6.0);
+        assert_eq!(area(vec![(1.0, 3.0), (3.0, 1.0)], -1.0, 0.0), 4.0 * 1.0 + 2.0 * 2.0);
+    }
+
+    #[test]
+    fn volume_of_overlapping_boxes() {
+        // boxes of 2 x 1 x 1 and 1 x 1 x 2 sharing a unit cube
+        let points: Vec<&[f32]> = vec![&[2.0, 1.0, 1.0], &[1.0, 1.0, 2.0]];
+        assert_eq!(volume(&points, &[0.0, 0.0, 0.0]), 3.0);
+        // a third box of 1 x 2 x 1 shares a unit cube with each and with both
+        let points: Vec<&[f32]> = vec![&[2.0, 1.0, 1.0], &[1.0, 1.0, 2.0], &[1.0, 2.0, 1.0]];
+        assert_eq!(volume(&points, &[0.0, 0.0, 0.0]), 4.0);
+    }
+
+    #[test]
+    fn points_not_beyond_the_reference_count_for_nothing() {
+        let hypervolume = Hypervolume::new(vec![0.0, 0.0]);
+        assert_eq!(hypervolume.of(&[&[2.0, 2.0], &[3.0, -1.0], &[1.0]]), 4.0);
+        assert_eq!(hypervolume.of(&[]), 0.0);
+        assert_eq!(Hypervolume::new(vec![1.0]).of(&[&[3.0], &[2.0]]), 2.0);
+    }
+
+    #[test]
+    fn estimate_is_close_to_exact() {
+        // the 3 objective boxes again with a fourth objective all at 1, so a volume of 3
+        let hypervolume = Hypervolume::new(vec![0.0; 4]);
+        let estimate = hypervolume.of(&[&[2.0, 1.0, 1.0, 1.0], &[1.0, 1.0, 2.0, 1.0]]);
+        assert!((estimate - 3.0).abs() < 0.05, "{0}", estimate);
+    }
+}
@@ -1,14 +1,461 @@
 use rayon::prelude::*;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
-use rand::Rng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 
+pub mod alps;
+pub mod archipelago;
+pub mod budget;
+pub mod bundle;
+pub mod cache;
+pub mod cellular;
+pub mod check;
+pub mod checkpoint;
+#[cfg(feature = "mpi")]
+pub mod cluster;
+pub mod coevolution;
+pub mod constraint;
+pub mod curriculum;
+pub mod dynamic;
+pub mod engine;
+pub mod evaluator;
+pub mod genome;
+pub mod hashing;
+pub mod history;
+pub mod hypermutation;
+pub mod hypervolume;
+pub mod manifest;
+pub mod matches;
+pub mod memetic;
+pub mod mutation;
+pub mod niching;
+pub mod observer;
+pub mod output;
+pub mod persist;
+pub mod pipeline;
+pub mod preset;
+pub mod replacement;
+pub mod resample;
+pub mod retry;
+pub mod scaling;
+pub mod schedule;
+pub mod selection;
+pub mod simd;
+pub mod spea2;
+pub mod speciation;
+pub mod summary;
+pub mod surrogate;
+pub mod termination;
+mod top_k;
+pub mod view;
+
+use budget::Evaluations;
+use cache::Cache;
+use evaluator::Evaluators;
+use hashing::GenomeHash;
+use hypermutation::Hypermutation;
+use memetic::Memetic;
+use constraint::{Constraints, Penalty};
+use niching::FitnessSharing;
+use pipeline::Pipeline;
+use replacement::{Elitism, Elitist, Replacement};
+use resample::Resample;
+use retry::Retrying;
+use schedule::Schedule;
+use selection::Selection;
+use summary::{sample_key, Summary};
+use surrogate::Surrogate;
+use top_k::TopK;
+use view::PopulationView;
+
+// All of the randomness used by the traits below comes from the rng the library passes in,
+// so that a seeded run (see Population::new_seeded and EvolveConfig::seed) can be replayed.
 pub trait Individual: Clone {
-    fn mutate(&self) -> Self;
+    fn mutate<R: Rng>(&self, rng: &mut R) -> Self;
+
+    // Mutation at the population's current strength (see EvolveConfig::adaptation), where 1.0
+    // is the normal step size. Operators that have no notion of step size just mutate.
+    fn mutate_scaled<R: Rng>(&self, rng: &mut R, _strength: f32) -> Self {
+        self.mutate(rng)
+    }
+
+    // Whether the individual is a legal solution at all (a tour that visits every city once,
+    // a program that parses, ...). Invalid offspring are resampled when EvolveConfig::resample
+    // is set, otherwise this isn't checked.
+    fn is_valid(&self) -> bool {
+        true
+    }
 }
 
+// Creates new random individuals.
 pub trait Generator<I> {
-    fn generate(&self) -> I;
-    fn evolve(&self, a: &I, b: &I) -> I;
+    fn generate<R: Rng>(&self, rng: &mut R) -> I;
+}
+
+impl<I, F> Generator<I> for F
+where
+    F: Fn(&mut dyn RngCore) -> I
+{
+    fn generate<R: Rng>(&self, rng: &mut R) -> I {
+        self(rng)
+    }
+}
+
+// Recombines two parents into a child.
+pub trait Crossover<I> {
+    fn crossover<R: Rng>(&self, a: &I, b: &I, rng: &mut R) -> I;
+
+    // Both children of a crossover. Operators that naturally produce two children (one point,
+    // uniform, ...) should override this, by default it is two crossovers with the parents swapped.
+    fn crossover_pair<R: Rng>(&self, a: &I, b: &I, rng: &mut R) -> (I, I) {
+        (self.crossover(a, b, rng), self.crossover(b, a, rng))
+    }
+}
+
+impl<I, F> Crossover<I> for F
+where
+    F: Fn(&I, &I, &mut dyn RngCore) -> I
+{
+    fn crossover<R: Rng>(&self, a: &I, b: &I, rng: &mut R) -> I {
+        self(a, b, rng)
+    }
+}
+
+// Adapts a function returning both children into a Crossover.
+pub struct PairCrossover<F>(pub F);
+
+impl<I, F> Crossover<I> for PairCrossover<F>
+where
+    F: Fn(&I, &I, &mut dyn RngCore) -> (I, I)
+{
+    fn crossover<R: Rng>(&self, a: &I, b: &I, rng: &mut R) -> I {
+        (self.0)(a, b, rng).0
+    }
+
+    fn crossover_pair<R: Rng>(&self, a: &I, b: &I, rng: &mut R) -> (I, I) {
+        (self.0)(a, b, rng)
+    }
+}
+
+// Seeds the rng for one stream of random numbers (a generation, an offspring slot, ...)
+// so that seeded runs come out the same no matter how rayon schedules the work.
+fn stream_rng(seed: u64, stream: usize) -> StdRng {
+    StdRng::seed_from_u64(seed ^ (stream as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+}
+
+// rng for one generation, from the run's seed if there is one, otherwise from the thread rng
+fn generation_rng(seed: Option<u64>, generation: usize) -> StdRng {
+    stream_rng(seed.unwrap_or_else(|| rand::thread_rng().gen()), generation)
+}
+
+// child, or a valid replacement for it if the config resamples invalid offspring
+fn resampled<I, F>(config: &EvolveConfig<I>, child: I, retry: F, r: &mut StdRng) -> I
+where
+    I: Individual + Send + Sync,
+    F: FnMut(&mut StdRng) -> I,
+{
+    match &config.resample {
+        Some(resample) => resample.check(child, retry, r),
+        None => child,
+    }
+}
+
+// Indices of individuals, best first. Under SortMode::TopK a population is only sorted as
+// far as its best k, so whatever goes by rank past those ranks the individuals with this.
+fn by_fitness<I>(individuals: &[GradedIndividual<I>]) -> Vec<usize>
+where
+    I: Individual + Send + Sync
+{
+    let mut order: Vec<usize> = (0..individuals.len()).collect();
+    order.sort_by(|&a, &b| individuals[b].fitness.total_cmp(&individuals[a].fitness));
+    order
+}
+
+// One child for the structured populations (Alps, CellularGrid, Speciation): a parent
+// picked by binary tournament from pool, crossed with a second one at rates.crossover and
+// mutated at rates.mutation, always if there was no crossover. The lineage indexes pool.
+fn tournament_child<I, C>(pool: &[&GradedIndividual<I>], rates: Rates, crossover: &C, r: &mut StdRng) -> (I, Lineage)
+where
+    I: Individual + Send + Sync,
+    C: Crossover<I> + Send + Sync,
+{
+    let tournament = |r: &mut StdRng| -> usize {
+        let a = r.gen_range(0..pool.len());
+        let b = r.gen_range(0..pool.len());
+        if pool[a].fitness >= pool[b].fitness { a } else { b }
+    };
+    let parent = tournament(r);
+    let mut partner = None;
+    let mut child = None;
+    if r.gen::<f32>() < rates.crossover {
+        let other = tournament(r);
+        partner = Some(other);
+        child = Some(crossover.crossover(&pool[parent].individual, &pool[other].individual, r));
+    }
+    if child.is_none() || r.gen::<f32>() < rates.mutation {
+        let source = child.as_ref().unwrap_or(&pool[parent].individual);
+        child = Some(source.mutate(r));
+    }
+    (child.unwrap(), Lineage::new(parent, partner))
+}
+
+pub type ScalarFitness<I> = Arc<dyn Fn(&I) -> f32 + Send + Sync>;
+pub type CaseFitness<I> = Arc<dyn Fn(&I) -> Vec<f32> + Send + Sync>;
+#[cfg(feature = "bumpalo")]
+pub type ScratchFitness<I> = Arc<dyn Fn(&I, &bumpalo::Bump) -> f32 + Send + Sync>;
+
+#[cfg(feature = "bumpalo")]
+thread_local! {
+    // per worker thread scratch arena for Fitness::Scratch
+    static SCRATCH: std::cell::RefCell<bumpalo::Bump> = std::cell::RefCell::new(bumpalo::Bump::new());
+}
+
+// A fitness function either returns a single score, or one score per test case.
+// Per-case scores are kept on the graded individual (for lexicase selection) and
+// their sum is used as the overall fitness.
+// With the bumpalo feature a scalar fitness function can also be handed a bump arena for
+// its temporary allocations. The arena belongs to the worker thread and is reset before
+// every evaluation, so nothing allocated in it may be kept.
+// A fitness function that needs state of its own can be given as per worker evaluators (see
+// evaluator::Evaluators), and several scalar objectives can be combined into one with
+// WeightedSum.
+#[derive(Clone)]
+pub enum Fitness<I> {
+    Scalar(ScalarFitness<I>),
+    Cases(CaseFitness<I>),
+    #[cfg(feature = "bumpalo")]
+    Scratch(ScratchFitness<I>),
+    Evaluators(Arc<Evaluators<I>>),
+    Weighted(Arc<WeightedSum<I>>),
+    Retrying(Arc<Retrying<I>>),
+    Surrogate(Arc<Surrogate<I>>),
+    Cached(Arc<Cache<I>>),
+    // another fitness function, counting what it grades, see budget::Evaluations
+    Counted(Arc<Fitness<I>>, Evaluations),
+}
+
+// Several scalar objectives combined into one fitness, the sum of each objective times its
+// weight. The unweighted value of every objective is kept as a per-case score on the graded
+// individual, in the order they were added, so they can be reported (or looked at by
+// Population::pareto_front) without evaluating again. Weights can be negative for objectives
+// that should be low.
+//
+//   WeightedSum::new().and(accuracy, 1.0).and(program_length, -0.01)
+pub struct WeightedSum<I> {
+    objectives: Vec<(ScalarFitness<I>, f32)>,
+}
+
+impl<I> WeightedSum<I> {
+    pub fn new() -> Self {
+        WeightedSum{
+            objectives: Vec::new(),
+        }
+    }
+
+    pub fn and<F>(mut self, objective: F, weight: f32) -> Self
+    where
+        F: Fn(&I) -> f32 + Send + Sync + 'static
+    {
+        self.objectives.push((Arc::new(objective), weight));
+        self
+    }
+
+    // the weights, in the order the objectives were added
+    pub fn weights(&self) -> Vec<f32> {
+        self.objectives.iter().map(|(_, weight)| *weight).collect()
+    }
+}
+
+impl<I> Default for WeightedSum<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> Fitness<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn grade(&self, individual: I) -> GradedIndividual<I> {
+        self.grade_or(individual, f32::MIN)
+    }
+
+    // worst is the score of an individual that couldn't be evaluated (see Retrying)
+    fn grade_or(&self, individual: I, worst: f32) -> GradedIndividual<I> {
+        match self {
+            Fitness::Scalar(f) => {
+                let score = f(&individual);
+                GradedIndividual::new(individual, score)
+            },
+            Fitness::Cases(f) => {
+                let scores = f(&individual);
+                GradedIndividual::with_scores(individual, scores)
+            },
+            #[cfg(feature = "bumpalo")]
+            Fitness::Scratch(f) => {
+                let score = SCRATCH.with(|arena| {
+                    match arena.try_borrow_mut() {
+                        Ok(mut arena) => {
+                            arena.reset();
+                            f(&individual, &arena)
+                        },
+                        // the fitness function itself ran parallel work and this thread picked
+                        // up another evaluation while waiting, use a throwaway arena
+                        Err(_) => f(&individual, &bumpalo::Bump::new()),
+                    }
+                });
+                GradedIndividual::new(individual, score)
+            },
+            Fitness::Evaluators(evaluators) => {
+                let score = evaluators.evaluate(&individual);
+                GradedIndividual::new(individual, score)
+            },
+            Fitness::Weighted(sum) => {
+                let scores: Vec<f32> = sum.objectives.iter().map(|(objective, _)| objective(&individual)).collect();
+                let fitness = scores.iter().zip(&sum.objectives).map(|(score, (_, weight))| score * weight).sum();
+                let mut graded = GradedIndividual::new(individual, fitness);
+                graded.scores = scores;
+                graded
+            },
+            Fitness::Retrying(retrying) => {
+                let score = retrying.evaluate(&individual).unwrap_or(worst);
+                GradedIndividual::new(individual, score)
+            },
+            Fitness::Surrogate(surrogate) => {
+                let score = surrogate.evaluate(&individual);
+                GradedIndividual::new(individual, score)
+            },
+            Fitness::Cached(cache) => {
+                let score = cache.evaluate(&individual);
+                GradedIndividual::new(individual, score)
+            },
+            Fitness::Counted(fitness, evaluations) => {
+                evaluations.add();
+                fitness.grade_or(individual, worst)
+            },
+        }
+    }
+
+    // true if individuals get per-case scores
+    fn has_cases(&self) -> bool {
+        match self {
+            Fitness::Cases(_) | Fitness::Weighted(_) => true,
+            Fitness::Counted(fitness, _) => fitness.has_cases(),
+            _ => false,
+        }
+    }
+
+    // called before each generation is graded
+    fn new_generation(&self) {
+        match self {
+            Fitness::Evaluators(evaluators) => evaluators.reset(),
+            Fitness::Retrying(retrying) => retrying.reset(),
+            Fitness::Surrogate(surrogate) => surrogate.new_generation(),
+            Fitness::Counted(fitness, _) => fitness.new_generation(),
+            _ => (),
+        }
+    }
+
+    // Like grade_for, but with config.recovery set an evaluation that panics (a worker or an
+    // external evaluator that died) is dispatched again on its own instead of taking the
+    // rest of the generation down with it.
+    pub(crate) fn grade_recovering(&self, individual: I, direction: OptimizationDirection, config: &EvolveConfig<I>) -> GradedIndividual<I> {
+        let Some(recovery) = &config.recovery else {
+            return self.grade_for(individual, direction);
+        };
+        let mut attempt = 0;
+        loop {
+            match std::panic::catch_unwind(AssertUnwindSafe(|| self.grade_for(individual.clone(), direction))) {
+                Ok(graded) => return graded,
+                Err(panic) if attempt >= recovery.redispatch => std::panic::resume_unwind(panic),
+                Err(_) => {
+                    attempt += 1;
+                    recovery.redispatched.fetch_add(1, Ordering::Relaxed);
+                },
+            }
+        }
+    }
+
+    // grades for a population optimizing in direction
+    pub(crate) fn grade_for(&self, individual: I, direction: OptimizationDirection) -> GradedIndividual<I> {
+        match direction {
+            OptimizationDirection::Maximize => self.grade(individual),
+            OptimizationDirection::Minimize => self.grade_or(individual, f32::MAX).negated(),
+        }
+    }
+}
+
+// Whether a population looks for the highest or the lowest value of the fitness function.
+// Everything in the library treats a higher fitness as better, so when minimizing the fitness
+// stored on each graded individual is the negated cost, which makes sorting, elitism,
+// selection and replacement work unchanged. Population::objective turns it back into the cost.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptimizationDirection {
+    #[default]
+    Maximize,
+    Minimize,
+}
+
+impl OptimizationDirection {
+    // what the fitness function returned, given the fitness stored on a graded individual
+    pub fn objective(&self, fitness: f32) -> f32 {
+        match self {
+            OptimizationDirection::Maximize => fitness,
+            OptimizationDirection::Minimize => -fitness,
+        }
+    }
+}
+
+impl<I> From<ScalarFitness<I>> for Fitness<I> {
+    fn from(f: ScalarFitness<I>) -> Self {
+        Fitness::Scalar(f)
+    }
+}
+
+impl<I> From<CaseFitness<I>> for Fitness<I> {
+    fn from(f: CaseFitness<I>) -> Self {
+        Fitness::Cases(f)
+    }
+}
+
+impl<I> From<WeightedSum<I>> for Fitness<I> {
+    fn from(sum: WeightedSum<I>) -> Self {
+        Fitness::Weighted(Arc::new(sum))
+    }
+}
+
+impl<I> From<Retrying<I>> for Fitness<I> {
+    fn from(retrying: Retrying<I>) -> Self {
+        Fitness::Retrying(Arc::new(retrying))
+    }
+}
+
+impl<I> From<Surrogate<I>> for Fitness<I> {
+    fn from(surrogate: Surrogate<I>) -> Self {
+        Fitness::Surrogate(Arc::new(surrogate))
+    }
+}
+
+impl<I> From<Cache<I>> for Fitness<I> {
+    fn from(cache: Cache<I>) -> Self {
+        Fitness::Cached(Arc::new(cache))
+    }
+}
+
+impl<I> From<Evaluators<I>> for Fitness<I> {
+    fn from(evaluators: Evaluators<I>) -> Self {
+        Fitness::Evaluators(Arc::new(evaluators))
+    }
+}
+
+#[cfg(feature = "bumpalo")]
+impl<I> From<ScratchFitness<I>> for Fitness<I> {
+    fn from(f: ScratchFitness<I>) -> Self {
+        Fitness::Scratch(f)
+    }
 }
 
 #[derive(Clone)]
@@ -17,90 +464,1243 @@ where
     I: Send + Sync
 {
     pub individual: I,
+    // what selection, replacement and sorting go by
     pub fitness: f32,
+    // fitness as returned by the fitness function, before any adjustment such as fitness
+    // sharing, otherwise the same as fitness
+    pub raw_fitness: f32,
+    // per-case scores, empty when the fitness function is scalar
+    pub scores: Vec<f32>,
+    // total constraint violation, 0 for feasible individuals or without constraints (see
+    // EvolveConfig::constraints)
+    pub violation: f32,
 }
 
 impl<I> GradedIndividual<I>
 where
     I: Individual + Send + Sync
 {
-    fn new(individual: I, fitness: f32) -> Self
-    where
-    {
+    pub fn new(individual: I, fitness: f32) -> Self {
         Self{
             individual,
             fitness,
+            raw_fitness: fitness,
+            scores: Vec::new(),
+            violation: 0.0,
+        }
+    }
+
+    fn with_scores(individual: I, scores: Vec<f32>) -> Self {
+        let fitness = scores.iter().sum();
+        Self{
+            individual,
+            fitness,
+            raw_fitness: fitness,
+            scores,
+            violation: 0.0,
+        }
+    }
+
+    // What Pareto dominance compares, the per-case scores, or just the fitness when there
+    // are none. Higher is better for every one.
+    pub fn objectives(&self) -> &[f32] {
+        if self.scores.is_empty() {
+            std::slice::from_ref(&self.fitness)
+        } else {
+            &self.scores
+        }
+    }
+
+    // Whether self is at least as good as other on every objective and better on one, with
+    // Deb's constrained dominance when there are constraint violations (see
+    // EvolveConfig::constraints): a feasible individual dominates every infeasible one, and
+    // of two infeasible ones the one with the smaller total violation dominates.
+    pub fn dominates(&self, other: &Self) -> bool {
+        match (self.violation > 0.0, other.violation > 0.0) {
+            (false, true) => return true,
+            (true, false) => return false,
+            (true, true) => return self.violation < other.violation,
+            (false, false) => {},
+        }
+        let (mine, theirs) = (self.objectives(), other.objectives());
+        mine.len() == theirs.len()
+            && mine.iter().zip(theirs).all(|(a, b)| a >= b)
+            && mine.iter().zip(theirs).any(|(a, b)| a > b)
+    }
+
+    // higher is better turned into lower is better and back, see OptimizationDirection
+    fn negated(mut self) -> Self {
+        self.fitness = -self.fitness;
+        self.raw_fitness = -self.raw_fitness;
+        self.scores.iter_mut().for_each(|score| *score = -*score);
+        self
+    }
+}
+
+// Which individuals of the previous population (by index) an offspring was bred from, the
+// partner being the second parent of a crossover.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lineage {
+    pub parent: usize,
+    pub partner: Option<usize>,
+}
+
+impl Lineage {
+    fn new(parent: usize, partner: Option<usize>) -> Self {
+        Lineage{
+            parent,
+            partner,
+        }
+    }
+}
+
+// How a population is put in order after each generation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SortMode {
+    // the whole population is sorted best first, individuals with equal fitness keep
+    // their relative order
+    #[default]
+    Full,
+    // the whole population is sorted best first with an unstable sort, which is faster and
+    // doesn't allocate, individuals with equal fitness end up in any order
+    Unstable,
+    // only the best k are sorted (best first) at the front, the rest follow in no particular
+    // order. Much cheaper for big populations when only the elites need to be known, k
+    // should be at least the number of elites.
+    TopK(usize),
+}
+
+impl SortMode {
+    fn apply<I>(&self, population: &mut [GradedIndividual<I>])
+    where
+        I: Individual + Send + Sync
+    {
+        match *self {
+            SortMode::Full => population.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness)),
+            SortMode::Unstable => population.par_sort_unstable_by(|a, b| b.fitness.total_cmp(&a.fitness)),
+            SortMode::TopK(k) => {
+                if k < population.len() {
+                    population.select_nth_unstable_by(k, |a, b| b.fitness.total_cmp(&a.fitness));
+                }
+                let k = k.min(population.len());
+                population[..k].par_sort_unstable_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            },
+        }
+    }
+}
+
+// What Population::deduplicated replaces the extra copies of a genome with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Duplicates {
+    // a mutant of the copy
+    Mutate,
+    // a freshly generated individual
+    Generate,
+}
+
+// How Population::snapshot picks its sample.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotStrategy {
+    // the best n
+    Top,
+    // n spread evenly over the fitness order, from the best to the worst, so the sample
+    // shows the spread of fitness
+    Stratified,
+    // n picked uniformly without replacement, with generation_rng so with a seed the same
+    // generation of a run always gives the same sample
+    Random(Option<u64>),
+}
+
+// A sample of a population, see Population::snapshot. Clones share the sample, so it can be
+// handed to other threads (a UI, a plotter, ...) for nothing.
+#[derive(Clone)]
+pub struct Snapshot<I>
+where
+    I: Individual + Send + Sync
+{
+    pub generation: usize,
+    // the sampled individuals, best first
+    pub individuals: Arc<[GradedIndividual<I>]>,
+}
+
+// Where the crossover partner (the second parent) comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum MatePool {
+    // the selection picks the partner too, without a selection it is picked uniformly from
+    // the whole population
+    #[default]
+    Selection,
+    // uniformly from the whole population, even with a selection
+    Everyone,
+    // uniformly from this best fraction of the population
+    Top(f32),
+}
+
+// Per offspring probabilities of applying crossover and mutation. A child can come from
+// crossover, mutation, both (crossover then mutation) or be a straight copy of its parent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rates {
+    pub crossover: f32,
+    pub mutation: f32,
+}
+
+impl Rates {
+    pub fn new(crossover: f32, mutation: f32) -> Self {
+        Rates{
+            crossover,
+            mutation,
+        }
+    }
+}
+
+// Re-dispatching of evaluations that panic, see EvolveConfig::recovery. Each individual is
+// tried up to redispatch more times, the panic is only passed on (ending the generation) if
+// every attempt panicked. A panicking evaluator of Fitness::Evaluators is replaced by a new
+// one. The panic messages are still printed by the panic hook.
+#[derive(Debug, Default)]
+pub struct Recovery {
+    pub redispatch: usize,
+    redispatched: AtomicUsize,
+}
+
+impl Recovery {
+    pub fn new(redispatch: usize) -> Self {
+        Recovery{
+            redispatch,
+            redispatched: AtomicUsize::new(0),
+        }
+    }
+
+    // evaluations dispatched again so far
+    pub fn redispatched(&self) -> usize {
+        self.redispatched.load(Ordering::Relaxed)
+    }
+}
+
+// Self-adaptive mutation strength following the 1/5th success rule. After each generation the
+// strength is multiplied by factor if more than target of the mutants beat their parent and
+// divided by it if fewer did, then clamped to min..=max.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adaptation {
+    pub target: f32,
+    pub factor: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Adaptation {
+    pub fn new(target: f32, factor: f32, min: f32, max: f32) -> Self {
+        Adaptation{
+            target,
+            factor,
+            min,
+            max,
+        }
+    }
+
+    fn adapt(&self, strength: f32, success: &Success) -> f32 {
+        let mutated = success.mutated.load(Ordering::Relaxed);
+        if mutated == 0 {
+            return strength;
+        }
+        let rate = success.improved.load(Ordering::Relaxed) as f32 / mutated as f32;
+        let strength = if rate > self.target {
+            strength * self.factor
+        } else if rate < self.target {
+            strength / self.factor
+        } else {
+            strength
+        };
+        strength.clamp(self.min, self.max)
+    }
+}
+
+impl Default for Adaptation {
+    fn default() -> Self {
+        Adaptation::new(0.2, 1.22, 1e-3, 1e3)
+    }
+}
+
+// How many mutants (mutation only, no crossover) were bred and how many beat their parent.
+// Recorded with raw fitness, the parent's fitness may already be shared or penalized.
+#[derive(Default)]
+struct Success {
+    mutated: AtomicUsize,
+    improved: AtomicUsize,
+}
+
+impl Success {
+    fn record(&self, parent: f32, mutant: f32) {
+        self.mutated.fetch_add(1, Ordering::Relaxed);
+        if mutant > parent {
+            self.improved.fetch_add(1, Ordering::Relaxed);
         }
     }
 }
 
+// Optional strategies used by Population::evolve_with. Anything left as None falls back
+// to what Population::evolve does.
+pub struct EvolveConfig<I>
+where
+    I: Individual + Send + Sync
+{
+    // picks both parents of every offspring, by default parents are taken in fitness
+    // order and the crossover partner is picked at random
+    pub selection: Option<Arc<dyn Selection<I>>>,
+    // picks the survivors, by default the elites are kept and offspring fill the rest
+    pub replacement: Option<Arc<dyn Replacement<I>>>,
+    // elites to keep when no replacement is given, and in evolve_streaming_with
+    pub elitism: Elitism,
+    pub sort: SortMode,
+    // by default offspring alternate between mutation and crossover
+    pub rates: Option<Rates>,
+    // Seeds every generation's rng so a run can be replayed, starting from a population
+    // built with Population::new_seeded. Unseeded runs use the thread rng.
+    pub seed: Option<u64>,
+    // adapts the population's mutation strength, by default it stays where it is
+    pub adaptation: Option<Adaptation>,
+    // scales the mutation strength passed to Individual::mutate_scaled by generation, on top
+    // of any adaptation
+    pub strength_schedule: Option<Schedule>,
+    // replaces the mutation rate of rates by generation, only used along with rates
+    pub mutation_rate_schedule: Option<Schedule>,
+    // applied to the survivors of each generation before they are sorted
+    pub sharing: Option<FitnessSharing<I>>,
+    // penalizes constraint violations of the survivors of each generation, after any sharing
+    pub constraints: Option<Arc<Constraints<I>>>,
+    // every new population is published to it for readers on other threads
+    pub view: Option<Arc<PopulationView<I>>>,
+    // breeds invalid offspring again, by default they are evaluated like any other
+    pub resample: Option<Arc<Resample<I>>>,
+    // evaluates offspring whose evaluation panicked again, by default a panic ends the
+    // generation
+    pub recovery: Option<Arc<Recovery>>,
+    // improves the best offspring of every generation by local search
+    pub memetic: Option<Memetic<I>>,
+    // boosts mutation or brings in random immigrants while the best fitness is stuck
+    pub hypermutation: Option<Arc<Hypermutation<I>>>,
+    // Overrides the pressure of the selection strategy (see Selection::select_with_pressure),
+    // from 1.0 for none to 2.0 for strong, so exploration can be traded for exploitation
+    // without swapping strategies. Has no effect without a selection.
+    pub selection_pressure: Option<f32>,
+    pub mate_pool: MatePool,
+}
+
+impl<I> Default for EvolveConfig<I>
+where
+    I: Individual + Send + Sync
+{
+    fn default() -> Self {
+        EvolveConfig{
+            selection: None,
+            replacement: None,
+            elitism: Elitism::default(),
+            sort: SortMode::default(),
+            rates: None,
+            seed: None,
+            adaptation: None,
+            strength_schedule: None,
+            mutation_rate_schedule: None,
+            sharing: None,
+            constraints: None,
+            view: None,
+            resample: None,
+            recovery: None,
+            memetic: None,
+            hypermutation: None,
+            selection_pressure: None,
+            mate_pool: MatePool::default(),
+        }
+    }
+}
+
+impl<I> Clone for EvolveConfig<I>
+where
+    I: Individual + Send + Sync
+{
+    fn clone(&self) -> Self {
+        EvolveConfig{
+            selection: self.selection.clone(),
+            replacement: self.replacement.clone(),
+            elitism: self.elitism,
+            sort: self.sort,
+            rates: self.rates,
+            seed: self.seed,
+            adaptation: self.adaptation,
+            strength_schedule: self.strength_schedule.clone(),
+            mutation_rate_schedule: self.mutation_rate_schedule.clone(),
+            sharing: self.sharing.clone(),
+            constraints: self.constraints.clone(),
+            view: self.view.clone(),
+            resample: self.resample.clone(),
+            recovery: self.recovery.clone(),
+            memetic: self.memetic.clone(),
+            hypermutation: self.hypermutation.clone(),
+            selection_pressure: self.selection_pressure,
+            mate_pool: self.mate_pool,
+        }
+    }
+}
+
+// A problem with an EvolveConfig, found by EvolveConfig::validate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    EmptyPopulation,
+    // a probability outside 0..=1
+    RateOutOfRange{name: &'static str, value: f32},
+    // crossover and mutation rates are both 0, so offspring are only ever copies
+    NoVariation,
+    // elitism keeps the whole population, so nothing is ever bred
+    TooManyElites{elites: usize, population_size: usize},
+    // the replacement asks for no offspring
+    NoOffspring,
+    // fewer individuals are kept sorted than there are elites
+    TopKBelowElites{k: usize, elites: usize},
+    // the selection needs per-case scores but the fitness function returns a single one
+    SelectionNeedsCases,
+    InvalidAdaptation(&'static str),
+    InvalidSharing(&'static str),
+    InvalidPenalty(&'static str),
+    // selection pressure outside 1..=2
+    PressureOutOfRange(f32),
+    // a MatePool::Top fraction outside 0 (exclusive) to 1
+    MatePoolOutOfRange(f32),
+    // a run of 0 generations, see engine::EvolutionBuilder
+    NoGenerations,
+    // a thread pool of 0 threads
+    NoThreads,
+    // the thread pool couldn't be started
+    ThreadPool(String),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::EmptyPopulation => write!(f, "the population is empty"),
+            ConfigError::RateOutOfRange{name, value} => write!(f, "the {0} rate {1} is not between 0 and 1", name, value),
+            ConfigError::NoVariation => write!(f, "the crossover and mutation rates are both 0, offspring would only be copies"),
+            ConfigError::TooManyElites{elites, population_size} => write!(f, "{0} elites leave no room for offspring in a population of {1}", elites, population_size),
+            ConfigError::NoOffspring => write!(f, "the replacement breeds no offspring"),
+            ConfigError::TopKBelowElites{k, elites} => write!(f, "only {0} individuals are kept sorted but there are {1} elites", k, elites),
+            ConfigError::SelectionNeedsCases => write!(f, "the selection needs per-case scores but the fitness function is not a case fitness"),
+            ConfigError::InvalidAdaptation(reason) => write!(f, "invalid adaptation: {0}", reason),
+            ConfigError::InvalidSharing(reason) => write!(f, "invalid fitness sharing: {0}", reason),
+            ConfigError::InvalidPenalty(reason) => write!(f, "invalid constraint penalty: {0}", reason),
+            ConfigError::PressureOutOfRange(pressure) => write!(f, "the selection pressure {0} is not between 1 and 2", pressure),
+            ConfigError::MatePoolOutOfRange(fraction) => write!(f, "the mate pool fraction {0} is not above 0 and at most 1", fraction),
+            ConfigError::NoGenerations => write!(f, "the generation budget is 0"),
+            ConfigError::NoThreads => write!(f, "the thread pool has no threads"),
+            ConfigError::ThreadPool(reason) => write!(f, "can't start the thread pool: {0}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl<I> EvolveConfig<I>
+where
+    I: Individual + Send + Sync
+{
+    // Checks the config against the population size and fitness function it will be used
+    // with, without evaluating anything. Returns every problem found, not just the first.
+    pub fn validate(&self, population_size: usize, fitness: &Fitness<I>) -> Result<(), Vec<ConfigError>> {
+        let mut errors = Vec::new();
+        if population_size == 0 {
+            errors.push(ConfigError::EmptyPopulation);
+        }
+        if let Some(rates) = self.rates {
+            for (name, value) in [("crossover", rates.crossover), ("mutation", rates.mutation)] {
+                if !(0.0..=1.0).contains(&value) {
+                    errors.push(ConfigError::RateOutOfRange{name, value});
+                }
+            }
+            if rates.crossover <= 0.0 && rates.mutation <= 0.0 && self.mutation_rate_schedule.is_none() {
+                errors.push(ConfigError::NoVariation);
+            }
+        }
+
+        let elites = self.elitism.count(population_size);
+        match &self.replacement {
+            Some(replacement) => {
+                if population_size > 0 && replacement.offspring(population_size) == 0 {
+                    errors.push(ConfigError::NoOffspring);
+                }
+            },
+            None => {
+                if population_size > 0 && elites >= population_size {
+                    errors.push(ConfigError::TooManyElites{elites, population_size});
+                }
+            },
+        }
+        if let SortMode::TopK(k) = self.sort {
+            if k < elites {
+                errors.push(ConfigError::TopKBelowElites{k, elites});
+            }
+        }
+
+        if let Some(pressure) = self.selection_pressure {
+            if !(1.0..=2.0).contains(&pressure) {
+                errors.push(ConfigError::PressureOutOfRange(pressure));
+            }
+        }
+
+        if let MatePool::Top(fraction) = self.mate_pool {
+            if !(fraction > 0.0 && fraction <= 1.0) {
+                errors.push(ConfigError::MatePoolOutOfRange(fraction));
+            }
+        }
+
+        if self.selection.as_ref().is_some_and(|selection| selection.needs_cases()) && !fitness.has_cases() {
+            errors.push(ConfigError::SelectionNeedsCases);
+        }
+
+        if let Some(adaptation) = self.adaptation {
+            if adaptation.factor <= 0.0 {
+                errors.push(ConfigError::InvalidAdaptation("factor must be positive"));
+            }
+            if adaptation.min > adaptation.max {
+                errors.push(ConfigError::InvalidAdaptation("min is greater than max"));
+            }
+        }
+        if let Some(sharing) = &self.sharing {
+            if sharing.radius <= 0.0 {
+                errors.push(ConfigError::InvalidSharing("radius must be positive"));
+            }
+        }
+        if let Some(constraints) = &self.constraints {
+            match constraints.penalty {
+                Penalty::Static{weight} if weight < 0.0 => errors.push(ConfigError::InvalidPenalty("weight is negative")),
+                Penalty::Dynamic{c, ..} if c < 0.0 => errors.push(ConfigError::InvalidPenalty("c is negative")),
+                Penalty::Adaptive{initial, ..} if initial < 0.0 => errors.push(ConfigError::InvalidPenalty("initial weight is negative")),
+                Penalty::Adaptive{relax, tighten, ..} if relax <= 1.0 || tighten <= 1.0 => {
+                    errors.push(ConfigError::InvalidPenalty("relax and tighten must be above 1"))
+                },
+                _ => {},
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// A graded individual, possibly with something attached.
+trait Graded<I>
+where
+    I: Individual + Send + Sync
+{
+    fn graded(&self) -> &GradedIndividual<I>;
+}
+
+impl<I> Graded<I> for GradedIndividual<I>
+where
+    I: Individual + Send + Sync
+{
+    fn graded(&self) -> &GradedIndividual<I> {
+        self
+    }
+}
+
+impl<I> Graded<I> for (GradedIndividual<I>, Lineage)
+where
+    I: Individual + Send + Sync
+{
+    fn graded(&self) -> &GradedIndividual<I> {
+        &self.0
+    }
+}
+
+// Collects individuals as they are graded along with a summary of them, so the statistics
+// come out of the same parallel pass as the evaluation rather than a second one. Each comes
+// with the key it is sampled by (see summary::sample_key).
+fn collect_summarized<I, T, P>(individuals: P) -> (Vec<T>, Summary<I>)
+where
+    I: Individual + Send + Sync,
+    T: Graded<I> + Send,
+    P: ParallelIterator<Item = (u64, T)>,
+{
+    individuals.fold(|| (Vec::new(), Summary::default()), |(mut collected, mut summary), (key, ind)| {
+        summary.push(ind.graded(), key);
+        collected.push(ind);
+        (collected, summary)
+    }).reduce(|| (Vec::new(), Summary::default()), |(mut a, a_summary), (b, b_summary)| {
+        a.extend(b);
+        (a, a_summary.merge(b_summary))
+    })
+}
+
 pub struct Population<I>
 where
     I: Individual + Send + Sync
 {
     pub individuals: Vec<GradedIndividual<I>>,
+    // number of times this population has been evolved, 0 for a freshly generated one
+    pub generation: usize,
+    // statistics over everything that was graded to build this population (the offspring,
+    // for an evolved one), gathered while grading
+    pub summary: Option<Summary<I>>,
+    // passed to Individual::mutate_scaled, 1.0 unless adapted by EvolveConfig::adaptation
+    pub mutation_strength: f32,
+    // see with_direction
+    pub direction: OptimizationDirection,
 }
 
 impl<I> Population<I>
 where
     I: Individual + Send + Sync
 {
-    pub fn new<G>(size: usize, generator: &G, fitness: Arc<dyn Fn(&I) -> f32 + Send + Sync>) -> Self
+    pub fn new<G, F>(size: usize, generator: &G, fitness: F) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        Self::generate(size, generator, fitness, None)
+    }
+
+    // Like new, but the same seed always generates the same population.
+    pub fn new_seeded<G, F>(size: usize, generator: &G, fitness: F, seed: u64) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        Self::generate(size, generator, fitness, Some(seed))
+    }
+
+    fn generate<G, F>(size: usize, generator: &G, fitness: F, seed: Option<u64>) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let base: u64 = generation_rng(seed, 0).gen();
+        let (mut population, summary) = collect_summarized((0..size).into_par_iter().map(|slot| -> (u64, GradedIndividual<I>) {
+            (sample_key(base, slot), fitness.grade(generator.generate(&mut stream_rng(base, slot))))
+        }));
+
+        population.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: population,
+            generation: 0,
+            summary: Some(summary),
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    // Starts from individuals carried over from an earlier run (its final population or best
+    // individuals, see persist::read_individuals), topped up with generated ones to size.
+    // Everything is graded afresh since the earlier run may have scored differently.
+    pub fn warm_start<G, F>(size: usize, mut individuals: Vec<I>, generator: &G, fitness: F) -> Self
     where
         G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
     {
-        let mut population : Vec<GradedIndividual<I>> = Vec::new();
-        population.reserve(size);
-        (1..size).into_par_iter().map(|_| -> GradedIndividual<I> {
-            let individual = generator.generate();
-            let score = fitness(&individual);
-            GradedIndividual::new(individual, score)
-        }).collect_into_vec(&mut population);
+        let fitness = fitness.into();
+        individuals.truncate(size);
+        let carried = individuals.len();
+        let base: u64 = rand::thread_rng().gen();
+        let generated = (carried..size).into_par_iter().map(|slot| generator.generate(&mut stream_rng(base, slot)));
+        let (mut population, summary) = collect_summarized(individuals.into_par_iter().chain(generated).enumerate().map(|(slot, ind)| (sample_key(base, slot), fitness.grade(ind))));
 
         population.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
         Population{
-            individuals: population
+            individuals: population,
+            generation: 0,
+            summary: Some(summary),
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
         }
     }
 
-    pub fn evolve<G>(&self, generator: &G, fitness: Arc<dyn Fn(&I) -> f32 + Send + Sync>) -> Self
+    // Seeds a population for a related problem (a harder variant, a bigger instance, ...)
+    // from the best count individuals of this one, mapped across by transform and topped up
+    // with individuals from generator as in warm_start.
+    pub fn transfer<J, T, G, F>(&self, count: usize, transform: T, size: usize, generator: &G, fitness: F) -> Population<J>
     where
-        G: Generator<I> + Send + Sync
+        J: Individual + Send + Sync,
+        T: Fn(&I) -> J + Send + Sync,
+        G: Generator<J> + Send + Sync,
+        F: Into<Fitness<J>>,
     {
-        let mut population : Vec<GradedIndividual<I>> = Vec::new();
-        population.reserve(self.individuals.len());
+        let count = count.min(self.individuals.len());
+        let seeds: Vec<J> = self.individuals[..count].par_iter().map(|entry| transform(&entry.individual)).collect();
+        Population::warm_start(size, seeds, generator, fitness)
+    }
+
+    // The population set to optimize in direction. If that changes the direction, the grades
+    // are flipped and the population re-sorted, nothing is evaluated again, so e.g.
+    // Population::new(size, &generator, cost).with_direction(OptimizationDirection::Minimize)
+    // costs no more than new. The summary is dropped since its statistics have the old sign.
+    pub fn with_direction(mut self, direction: OptimizationDirection) -> Self {
+        if direction != self.direction {
+            self.individuals = self.individuals.into_par_iter().map(GradedIndividual::negated).collect();
+            self.individuals.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+            self.summary = None;
+            self.direction = direction;
+        }
+        self
+    }
 
-        // copy the first 10% over
-        let copy_count = (self.individuals.len() as f32 * 0.1) as usize;
+    // the best individual, the first since the population is sorted
+    pub fn best(&self) -> Option<&GradedIndividual<I>> {
+        self.individuals.first()
+    }
 
-        let copy_it = self.individuals.par_iter().take(copy_count).map(|entry|-> GradedIndividual<I> {
-            (*entry).clone()
-        });
+    // The individuals no other individual dominates (see GradedIndividual::dominates), the
+    // tradeoffs between the per-case scores, in population order. Copies of an individual are
+    // all included. With a scalar fitness function this is every individual sharing the best
+    // fitness.
+    pub fn pareto_front(&self) -> Vec<&GradedIndividual<I>> {
+        self.individuals.par_iter().filter(|entry| !self.individuals.iter().any(|other| other.dominates(entry))).collect()
+    }
+
+    // A sample of up to n individuals picked by strategy, only those are copied.
+    pub fn snapshot(&self, n: usize, strategy: SnapshotStrategy) -> Snapshot<I> {
+        let len = self.individuals.len();
+        let n = n.min(len);
+        // the population may only be partly sorted (SortMode::TopK)
+        let mut picked: Vec<usize> = match strategy {
+            SnapshotStrategy::Top => {
+                let mut order = by_fitness(&self.individuals);
+                order.truncate(n);
+                order
+            },
+            SnapshotStrategy::Stratified => {
+                let order = by_fitness(&self.individuals);
+                (0..n).map(|i| order[if n > 1 { i * (len - 1) / (n - 1) } else { 0 }]).collect()
+            },
+            SnapshotStrategy::Random(seed) => rand::seq::index::sample(&mut generation_rng(seed, self.generation), len, n).into_vec(),
+        };
+        picked.sort_by(|&a, &b| self.individuals[b].fitness.total_cmp(&self.individuals[a].fitness));
+        Snapshot{
+            generation: self.generation,
+            individuals: picked.into_iter().map(|i| self.individuals[i].clone()).collect(),
+        }
+    }
 
-        let mutate_it = self.individuals[copy_count..].par_iter().step_by(2).map(|entry| -> GradedIndividual<I> {
-            let ind = entry.individual.mutate();
-            let score = fitness(&entry.individual);
-            GradedIndividual::<I>{
-                individual: ind,
-                fitness: score,
+    // what the fitness function returned for entry, undoing the negation when minimizing
+    pub fn objective(&self, entry: &GradedIndividual<I>) -> f32 {
+        self.direction.objective(entry.fitness)
+    }
+
+    // The same individuals graded again with fitness and re-sorted, leaving this population
+    // as it is: to carry on a run after changing the fitness function, rank a population
+    // by something else after the fact, or score the final population on validation cases.
+    // The summary is over the new grades.
+    pub fn rescore<F>(&self, fitness: F) -> Self
+    where
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        // regrading is deterministic, so is the sample
+        let base = self.generation as u64;
+        let (mut population, summary) = collect_summarized(self.individuals.par_iter().enumerate().map(|(slot, entry)| (sample_key(base, slot), fitness.grade_for(entry.individual.clone(), self.direction))));
+
+        population.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: population,
+            generation: self.generation,
+            summary: Some(summary),
+            mutation_strength: self.mutation_strength,
+            direction: self.direction,
+        }
+    }
+
+    // Like rescore, without copying the individuals.
+    pub(crate) fn regraded(self, fitness: &Fitness<I>) -> Self {
+        let base = self.generation as u64;
+        let (mut population, summary) = collect_summarized(self.individuals.into_par_iter().enumerate().map(|(slot, entry)| (sample_key(base, slot), fitness.grade_for(entry.individual, self.direction))));
+
+        population.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: population,
+            generation: self.generation,
+            summary: Some(summary),
+            mutation_strength: self.mutation_strength,
+            direction: self.direction,
+        }
+    }
+
+    // Generates and grades size individuals but only ever holds on to the best keep of them.
+    // For populations that are too large to materialize in full.
+    pub fn new_streaming<G, F>(size: usize, keep: usize, generator: &G, fitness: F) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let base: u64 = rand::thread_rng().gen();
+        let (top, summary) = (0..size).into_par_iter().fold(|| (TopK::new(keep), Summary::default()), |(mut top, mut summary), slot| {
+            let ind = fitness.grade(generator.generate(&mut stream_rng(base, slot)));
+            summary.push(&ind, sample_key(base, slot));
+            top.push(ind);
+            (top, summary)
+        }).reduce(|| (TopK::new(keep), Summary::default()), |a, b| (a.0.merge(b.0), a.1.merge(b.1)));
+
+        Population{
+            individuals: top.into_sorted_vec(),
+            generation: 0,
+            summary: Some(summary),
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    pub fn evolve<C, F>(&self, crossover: &C, fitness: F) -> Self
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        self.evolve_with(crossover, fitness, &EvolveConfig::default())
+    }
+
+    pub fn evolve_with<C, F>(&self, crossover: &C, fitness: F, config: &EvolveConfig<I>) -> Self
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        fitness.new_generation();
+        let generation = self.generation + 1;
+        let default_replacement = Elitist::new(config.elitism);
+        let replacement = config.replacement.as_deref().unwrap_or(&default_replacement);
+
+        let mut rng = generation_rng(config.seed, generation);
+        let success = Success::default();
+        if let Some(hypermutation) = &config.hypermutation {
+            hypermutation.record(self.individuals.iter().map(|entry| entry.fitness).fold(f32::NEG_INFINITY, f32::max));
+        }
+        let (mut bred, summary) = self.breed(crossover, &fitness, config, replacement.offspring(self.individuals.len()), generation, &mut rng, &success);
+        self.improve(&mut bred, &fitness, config, &mut rng);
+        self.survive(bred, summary, &success, config, generation, &mut rng)
+    }
+
+    // Like evolve_with, with the offspring bred by pipeline instead. The selection, rates and
+    // schedules of the config are ignored, the pipeline has its own.
+    pub fn evolve_pipeline<C, F>(&self, pipeline: &Pipeline<I, C>, fitness: F, config: &EvolveConfig<I>) -> Self
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        fitness.new_generation();
+        let generation = self.generation + 1;
+        let default_replacement = Elitist::new(config.elitism);
+        let replacement = config.replacement.as_deref().unwrap_or(&default_replacement);
+
+        let mut rng = generation_rng(config.seed, generation);
+        let success = Success::default();
+        if let Some(hypermutation) = &config.hypermutation {
+            hypermutation.record(self.individuals.iter().map(|entry| entry.fitness).fold(f32::NEG_INFINITY, f32::max));
+        }
+        let (mut bred, summary) = pipeline.breed(self, &fitness, config, replacement.offspring(self.individuals.len()), generation, &mut rng, &success);
+        self.improve(&mut bred, &fitness, config, &mut rng);
+        self.survive(bred, summary, &success, config, generation, &mut rng)
+    }
+
+    // Local search and hypermutation immigrants, applied to the offspring before they compete
+    // for survival.
+    fn improve(&self, bred: &mut [(GradedIndividual<I>, Lineage)], fitness: &Fitness<I>, config: &EvolveConfig<I>, rng: &mut StdRng) {
+        if let Some(memetic) = &config.memetic {
+            memetic.apply(bred, fitness, self.direction, rng);
+        }
+        if let Some(hypermutation) = &config.hypermutation {
+            hypermutation.immigrate(bred, fitness, self.direction, rng.gen());
+        }
+    }
+
+    // The next population from the offspring bred for generation, as set up by config.
+    fn survive(&self, bred: Vec<(GradedIndividual<I>, Lineage)>, summary: Summary<I>, success: &Success, config: &EvolveConfig<I>, generation: usize, rng: &mut StdRng) -> Self {
+        let default_replacement = Elitist::new(config.elitism);
+        let replacement = config.replacement.as_deref().unwrap_or(&default_replacement);
+        let (offspring, lineage): (Vec<GradedIndividual<I>>, Vec<Lineage>) = bred.into_par_iter().unzip();
+        let mut population = if config.sharing.is_some() || config.constraints.is_some() {
+            // the parents carry last generation's shared or penalized fitness and the offspring
+            // their raw fitness, so both are adjusted afresh, together, for replacement to
+            // compare like with like. The parents keep their order, lineage indexes them.
+            let mut pool: Vec<GradedIndividual<I>> = self.individuals.iter().cloned().chain(offspring).collect();
+            if let Some(sharing) = &config.sharing {
+                sharing.apply(&mut pool);
+            } else {
+                pool.iter_mut().for_each(|entry| entry.fitness = entry.raw_fitness);
             }
-        });
-        let evolve_it = self.individuals[copy_count+1..].par_iter().step_by(2).map(|entry| -> GradedIndividual<I> {
-            let mut r = rand::thread_rng();
-            let other_index = r.gen_range(0..self.individuals.len());
-            let ind = generator.evolve(&entry.individual, &self.individuals[other_index].individual);
-            let score = fitness(&ind);
-            GradedIndividual::<I>{
-                individual: ind,
-                fitness: score,
+            if let Some(constraints) = &config.constraints {
+                constraints.penalize(&mut pool, generation);
             }
+            let offspring = pool.split_off(self.individuals.len());
+            replacement.replace_with_lineage(&pool, offspring, &lineage, generation, rng)
+        } else {
+            replacement.replace_with_lineage(&self.individuals, offspring, &lineage, generation, rng)
+        };
+        if let Some(sharing) = &config.sharing {
+            sharing.apply(&mut population);
+        } else if config.constraints.is_some() {
+            // survivors still carry last generation's penalty
+            population.iter_mut().for_each(|entry| entry.fitness = entry.raw_fitness);
+        }
+        if let Some(constraints) = &config.constraints {
+            constraints.apply(&mut population, generation);
+        }
+
+        config.sort.apply(&mut population);
+        let population = Population{
+            individuals: population,
+            generation,
+            summary: Some(summary),
+            mutation_strength: match config.adaptation {
+                Some(adaptation) => adaptation.adapt(self.mutation_strength, success),
+                None => self.mutation_strength,
+            },
+
+            direction: self.direction,
+        };
+        if let Some(view) = &config.view {
+            view.publish(&population);
+        }
+        population
+    }
+
+    // Streaming counterpart to evolve. Breeds offspring children from the current population
+    // (alternating mutation and crossover, parents taken round robin in fitness order) and
+    // keeps only the best of them, so the population size stays the same no matter how
+    // many offspring are evaluated. With fewer offspring than it takes to fill the population
+    // after the elites, the best of the rest of the current population make up the difference.
+    pub fn evolve_streaming<C, F>(&self, offspring: usize, crossover: &C, fitness: F) -> Self
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        self.evolve_streaming_with(offspring, crossover, fitness, &EvolveConfig::default())
+    }
+
+    // Only the elitism, sort mode, seed, view, resample and recovery of the config apply to
+    // streaming evolution.
+    pub fn evolve_streaming_with<C, F>(&self, offspring: usize, crossover: &C, fitness: F, config: &EvolveConfig<I>) -> Self
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        fitness.new_generation();
+        let generation = self.generation + 1;
+
+        let copy_count = config.elitism.count(self.individuals.len());
+        let keep = self.individuals.len() - copy_count;
+
+        let base: u64 = generation_rng(config.seed, generation).gen();
+        let (top, summary) = (0..offspring).into_par_iter().fold(|| (TopK::new(keep), Summary::default()), |(mut top, mut summary), slot| {
+            let mut r = stream_rng(base, slot);
+            let parent = &self.individuals[slot % self.individuals.len()].individual;
+            let ind = if slot % 2 == 0 {
+                let mutant = parent.mutate_scaled(&mut r, self.mutation_strength);
+                resampled(config, mutant, |r| parent.mutate_scaled(r, self.mutation_strength), &mut r)
+            } else {
+                let other = &self.individuals[r.gen_range(0..self.individuals.len())].individual;
+                let child = crossover.crossover(parent, other, &mut r);
+                resampled(config, child, |r| crossover.crossover(parent, other, r), &mut r)
+            };
+            let ind = fitness.grade_recovering(ind, self.direction, config);
+            summary.push(&ind, sample_key(base, slot));
+            top.push(ind);
+            (top, summary)
+        }).reduce(|| (TopK::new(keep), Summary::default()), |a, b| (a.0.merge(b.0), a.1.merge(b.1)));
+
+        let mut population = self.individuals[..copy_count].to_vec();
+        population.extend(top.into_sorted_vec());
+        let missing = self.individuals.len() - population.len();
+        population.extend(by_fitness(&self.individuals).into_iter().skip(copy_count).take(missing).map(|i| self.individuals[i].clone()));
+
+        config.sort.apply(&mut population);
+        let population = Population{
+            individuals: population,
+            generation,
+            summary: Some(summary),
+            mutation_strength: self.mutation_strength,
+            direction: self.direction,
+        };
+        if let Some(view) = &config.view {
+            view.publish(&population);
+        }
+        population
+    }
+
+    // Breeds slots offspring, either alternating between mutation and crossover or according
+    // to the configured rates. Each slot (or pair of slots) gets its own rng seeded from rng.
+    // Mutants bred from a single parent are recorded in success.
+    #[allow(clippy::too_many_arguments)]
+    fn breed<C>(&self, crossover: &C, fitness: &Fitness<I>, config: &EvolveConfig<I>, slots: usize, generation: usize, rng: &mut StdRng, success: &Success) -> (Vec<(GradedIndividual<I>, Lineage)>, Summary<I>)
+    where
+        C: Crossover<I> + Send + Sync
+    {
+        // two parents for each slot, picked up front
+        let parents = config.selection.as_ref().map(|s| match config.selection_pressure {
+            Some(pressure) => s.select_with_pressure(&self.individuals, generation, 2 * slots, pressure, rng),
+            None => s.select(&self.individuals, generation, 2 * slots, rng),
         });
+        let base: u64 = rng.gen();
+        // without a selection the parents are the bottom slots individuals in fitness order,
+        // i.e. everyone but the elites, wrapping around if there are more slots than individuals
+        let offset = self.individuals.len().saturating_sub(slots);
+        let parent = |slot: usize| -> usize {
+            match &parents {
+                Some(p) => p[2 * slot],
+                None => (offset + slot) % self.individuals.len(),
+            }
+        };
+        let mates = match config.mate_pool {
+            MatePool::Top(fraction) => ((self.individuals.len() as f32 * fraction).ceil() as usize).clamp(1, self.individuals.len().max(1)),
+            _ => self.individuals.len(),
+        };
+        let partner = |slot: usize, r: &mut StdRng| -> usize {
+            match (&parents, config.mate_pool) {
+                (Some(p), MatePool::Selection) => p[2 * slot + 1],
+                _ => r.gen_range(0..mates),
+            }
+        };
 
-        copy_it.interleave(mutate_it).interleave(evolve_it).collect_into_vec(&mut population);
+        let boost = config.hypermutation.as_ref().map_or(1.0, |hypermutation| hypermutation.boost());
+        let strength = match &config.strength_schedule {
+            Some(schedule) => self.mutation_strength * schedule.at(generation),
+            None => self.mutation_strength,
+        } * boost;
+        let rates = match &config.mutation_rate_schedule {
+            Some(schedule) => config.rates.map(|rates| Rates::new(rates.crossover, schedule.at(generation))),
+            None => config.rates,
+        }.map(|rates| Rates::new(rates.crossover, (rates.mutation * boost).min(1.0)));
 
-        population.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        match rates {
+            Some(rates) => {
+                // Slots are filled two at a time. A crossover fills both from the pair of children
+                // of the first slot's parents, otherwise each slot gets a copy of its own parent.
+                // Either way each child is then mutated with the mutation rate.
+                collect_summarized((0..slots.div_ceil(2)).into_par_iter().flat_map_iter(|pair| -> Vec<(u64, (GradedIndividual<I>, Lineage))> {
+                    let slot = 2 * pair;
+                    let mut r = stream_rng(base, slot);
+                    let count = (slots - slot).min(2);
+                    if r.gen::<f32>() < rates.crossover {
+                        let (first, second) = (parent(slot), partner(slot, &mut r));
+                        let lineage = Lineage::new(first, Some(second));
+                        let (a, b) = (&self.individuals[first].individual, &self.individuals[second].individual);
+                        let children = crossover.crossover_pair(a, b, &mut r);
+                        [children.0, children.1].into_iter().enumerate().take(count).map(|(i, child)| {
+                            let mutate = r.gen::<f32>() < rates.mutation;
+                            let vary = |child: I, r: &mut StdRng| if mutate { child.mutate_scaled(r, strength) } else { child };
+                            let child = vary(child, &mut r);
+                            let child = resampled(config, child, |r| {
+                                let children = crossover.crossover_pair(a, b, r);
+                                vary(if i == 0 { children.0 } else { children.1 }, r)
+                            }, &mut r);
+                            (sample_key(base, slot + i), (fitness.grade_recovering(child, self.direction, config), lineage))
+                        }).collect()
+                    } else {
+                        (slot..slot + count).map(|slot| {
+                            let lineage = Lineage::new(parent(slot), None);
+                            let entry = &self.individuals[lineage.parent];
+                            if r.gen::<f32>() < rates.mutation {
+                                let mutant = entry.individual.mutate_scaled(&mut r, strength);
+                                let mutant = resampled(config, mutant, |r| entry.individual.mutate_scaled(r, strength), &mut r);
+                                let mutant = fitness.grade_recovering(mutant, self.direction, config);
+                                success.record(entry.raw_fitness, mutant.raw_fitness);
+                                (sample_key(base, slot), (mutant, lineage))
+                            } else {
+                                (sample_key(base, slot), (entry.clone(), lineage))
+                            }
+                        }).collect()
+                    }
+                }))
+            },
+            None => {
+                // even slots are mutants, odd slots crossover children, each crossover filling
+                // two odd slots
+                let crossed = slots / 2;
+                let (children, children_summary) = collect_summarized((0..crossed.div_ceil(2)).into_par_iter().flat_map_iter(|pair| -> Vec<(u64, (GradedIndividual<I>, Lineage))> {
+                    let slot = 4 * pair + 1;
+                    let mut r = stream_rng(base, slot);
+                    let count = (crossed - 2 * pair).min(2);
+                    let (first, second) = (parent(slot), partner(slot, &mut r));
+                    let lineage = Lineage::new(first, Some(second));
+                    let (a, b) = (&self.individuals[first].individual, &self.individuals[second].individual);
+                    let children = crossover.crossover_pair(a, b, &mut r);
+                    [children.0, children.1].into_iter().enumerate().take(count).map(|(i, child)| {
+                        let child = resampled(config, child, |r| {
+                            let children = crossover.crossover_pair(a, b, r);
+                            if i == 0 { children.0 } else { children.1 }
+                        }, &mut r);
+                        (sample_key(base, slot + 2 * i), (fitness.grade_recovering(child, self.direction, config), lineage))
+                    }).collect()
+                }));
+                let (mutants, mutants_summary) = collect_summarized((0..slots).into_par_iter().step_by(2).map(|slot| -> (u64, (GradedIndividual<I>, Lineage)) {
+                    let lineage = Lineage::new(parent(slot), None);
+                    let entry = &self.individuals[lineage.parent];
+                    let mut r = stream_rng(base, slot);
+                    let mutant = entry.individual.mutate_scaled(&mut r, strength);
+                    let mutant = resampled(config, mutant, |r| entry.individual.mutate_scaled(r, strength), &mut r);
+                    let mutant = fitness.grade_recovering(mutant, self.direction, config);
+                    success.record(entry.raw_fitness, mutant.raw_fitness);
+                    (sample_key(base, slot), (mutant, lineage))
+                }));
+                let mut offspring = Vec::with_capacity(slots);
+                mutants.into_par_iter().interleave(children.into_par_iter()).collect_into_vec(&mut offspring);
+                (offspring, mutants_summary.merge(children_summary))
+            },
+        }
+    }
+}
+
+impl<I> Population<I>
+where
+    I: Individual + GenomeHash + PartialEq + Send + Sync
+{
+    // Keeps the best copy of every genome and replaces the other copies according to
+    // duplicates, then re-sorts. Without this long runs tend to fill up with copies of the
+    // current best. The replacements are added to the summary. Genomes are bucketed by
+    // GenomeHash and only count as copies if they are also ==, so a hash collision doesn't
+    // throw a distinct genome away.
+    pub fn deduplicated<G, F>(self, duplicates: Duplicates, generator: &G, fitness: F) -> Self
+    where
+        G: Generator<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let mut seen: HashMap<u64, Vec<usize>> = HashMap::with_capacity(self.individuals.len());
+        let copies: Vec<usize> = (0..self.individuals.len()).filter(|&i| {
+            let individual = &self.individuals[i].individual;
+            let bucket = seen.entry(individual.genome_hash()).or_default();
+            if bucket.iter().any(|&first| self.individuals[first].individual == *individual) {
+                return true;
+            }
+            bucket.push(i);
+            false
+        }).collect();
+        if copies.is_empty() {
+            return self;
+        }
+
+        let fitness = fitness.into();
+        let base: u64 = rand::thread_rng().gen();
+        let (replacements, summary) = collect_summarized(copies.par_iter().map(|&i| -> (u64, GradedIndividual<I>) {
+            let mut r = stream_rng(base, i);
+            let ind = match duplicates {
+                Duplicates::Mutate => self.individuals[i].individual.mutate_scaled(&mut r, self.mutation_strength),
+                Duplicates::Generate => generator.generate(&mut r),
+            };
+            (sample_key(base, i), fitness.grade_for(ind, self.direction))
+        }));
+
+        let mut individuals = self.individuals;
+        for (i, replacement) in copies.into_iter().zip(replacements) {
+            individuals[i] = replacement;
+        }
+        individuals.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
         Population{
-            individuals: population
+            individuals,
+            generation: self.generation,
+            summary: Some(match self.summary {
+                Some(previous) => previous.merge(summary),
+                None => summary,
+            }),
+            mutation_strength: self.mutation_strength,
+            direction: self.direction,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn generate(r: &mut dyn RngCore) -> Number {
+        Number(r.gen_range(0.0..1.0))
+    }
+
+    fn average(a: &Number, b: &Number, _r: &mut dyn RngCore) -> Number {
+        Number((a.0 + b.0) / 2.0)
+    }
+
+    fn value() -> ScalarFitness<Number> {
+        Arc::new(|n: &Number| n.0)
+    }
+
+    #[test]
+    fn streaming_keeps_the_population_size() {
+        let mut population = Population::new_seeded(20, &generate, value(), 1);
+        let config = EvolveConfig{seed: Some(1), ..EvolveConfig::default()};
+        for offspring in [0, 5, 18, 100] {
+            population = population.evolve_streaming_with(offspring, &average, value(), &config);
+            assert_eq!(population.individuals.len(), 20, "{0} offspring", offspring);
+        }
+    }
+
+    // every genome hashes the same
+    #[derive(Clone, Debug, PartialEq)]
+    struct Colliding(u8);
+
+    impl Individual for Colliding {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            Colliding(self.0.wrapping_add(100))
+        }
+    }
+
+    impl GenomeHash for Colliding {
+        fn genome_hash(&self) -> u64 {
+            0
+        }
+    }
+
+    #[test]
+    fn deduplication_only_replaces_equal_genomes() {
+        let fitness: ScalarFitness<Colliding> = Arc::new(|c: &Colliding| c.0 as f32);
+        let generate = |_: &mut dyn RngCore| Colliding(0);
+        let grade = Fitness::from(fitness.clone());
+        let individuals = [3, 3, 2, 1, 1].into_iter().map(|x| grade.grade(Colliding(x))).collect();
+        let population = Population{
+            individuals,
+            generation: 0,
+            summary: None,
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        };
+        let deduplicated = population.deduplicated(Duplicates::Mutate, &generate, fitness);
+        let mut genomes: Vec<u8> = deduplicated.individuals.iter().map(|entry| entry.individual.0).collect();
+        genomes.sort();
+        // the second 3 and the second 1 are mutated, 2 isn't a copy of anything despite its hash
+        assert_eq!(genomes, vec![1, 2, 3, 101, 103]);
+    }
+
+    #[test]
+    fn replacement_compares_penalized_parents_with_penalized_offspring() {
+        // values above 5 are infeasible
+        let constraints = Constraints::new(Penalty::Static{weight: 10.0}).with(|n: &Number| n.0 - 5.0);
+        let config = EvolveConfig{
+            replacement: Some(Arc::new(replacement::MuPlusLambda::new(2))),
+            constraints: Some(Arc::new(constraints)),
+            ..EvolveConfig::default()
+        };
+        let grade = Fitness::from(value());
+        let mut parents = vec![grade.grade(Number(4.0)), grade.grade(Number(3.0))];
+        config.constraints.as_ref().unwrap().apply(&mut parents, 0);
+        let population = Population{
+            individuals: parents,
+            generation: 0,
+            summary: None,
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        };
+        // an infeasible offspring with a raw fitness above both parents
+        let bred = vec![(grade.grade(Number(6.0)), Lineage::new(0, None)), (grade.grade(Number(1.0)), Lineage::new(1, None))];
+        let next = population.survive(bred, Summary::default(), &Success::default(), &config, 1, &mut stream_rng(1, 0));
+        let values: Vec<f32> = next.individuals.iter().map(|entry| entry.individual.0).collect();
+        assert_eq!(values, vec![4.0, 3.0]);
+    }
+}
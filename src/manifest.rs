@@ -0,0 +1,108 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// The runs of an experiment (a sweep over seeds or settings) that have completed, so an
+// interrupted sweep can pick up where it left off instead of starting over. Runs are named by
+// the caller, e.g. by seed, and each completed run is appended to the file as a line with the
+// run name, a tab and its result (e.g. RunSummary::to_json), so at most the run in progress is
+// lost when the sweep is killed.
+pub struct Manifest {
+    path: PathBuf,
+    runs: Vec<(String, String)>,
+}
+
+impl Manifest {
+    // Loads the manifest at path, an empty one if the file doesn't exist yet.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>
+    {
+        let path = path.as_ref().to_path_buf();
+        let mut runs = Vec::new();
+        match File::open(&path) {
+            Ok(file) => {
+                let mut reader = BufReader::new(file);
+                let mut line = String::new();
+                while reader.read_line(&mut line)? > 0 {
+                    // a line cut short by an interrupted write has no newline at the end, that
+                    // run is redone
+                    if let Some((run, result)) = line.strip_suffix('\n').and_then(|line| line.split_once('\t')) {
+                        runs.push((run.to_string(), result.to_string()));
+                    }
+                    line.clear();
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+        Ok(Manifest{
+            path,
+            runs,
+        })
+    }
+
+    // the recorded result of run, if it completed
+    pub fn completed(&self, run: &str) -> Option<&str> {
+        self.runs.iter().rev().find(|(name, _)| name == run).map(|(_, result)| result.as_str())
+    }
+
+    // the runs that still have to be done, in the order given
+    pub fn pending<S>(&self, runs: impl IntoIterator<Item = S>) -> Vec<S>
+    where
+        S: AsRef<str>
+    {
+        runs.into_iter().filter(|run| self.completed(run.as_ref()).is_none()).collect()
+    }
+
+    // all completed runs with their results, in the order they completed
+    pub fn runs(&self) -> &[(String, String)] {
+        &self.runs
+    }
+
+    // Records run as completed with result. Neither may contain newlines, and run no tabs.
+    pub fn record(&mut self, run: &str, result: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).read(true).write(true).truncate(false).open(&self.path)?;
+        // a partial line left by an interrupted write (which open skipped) is dropped, so
+        // it isn't completed by this one or taken for a run once it ends in a newline
+        let length = file.metadata()?.len();
+        if length > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                let mut contents = Vec::with_capacity(length as usize);
+                file.seek(SeekFrom::Start(0))?;
+                file.read_to_end(&mut contents)?;
+                let complete = contents.iter().rposition(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+                file.set_len(complete as u64)?;
+            }
+        }
+        file.seek(SeekFrom::End(0))?;
+        file.write_all(format!("{0}\t{1}\n", run, result).as_bytes())?;
+        file.flush()?;
+        self.runs.push((run.to_string(), result.to_string()));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resumes_after_a_truncated_line() {
+        let path = std::env::temp_dir().join(format!("ga-manifest-{0}", std::process::id()));
+        // the last run was killed while writing its result
+        std::fs::write(&path, "1\t{\"solved\":true}\n2\t{\"sol").unwrap();
+        let mut manifest = Manifest::open(&path).unwrap();
+        assert_eq!(manifest.completed("1"), Some("{\"solved\":true}"));
+        assert_eq!(manifest.completed("2"), None);
+        assert_eq!(manifest.pending(["1", "2", "3"]), vec!["2", "3"]);
+
+        manifest.record("2", "{\"solved\":false}").unwrap();
+        let manifest = Manifest::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(manifest.runs(), [("1".to_string(), "{\"solved\":true}".to_string()), ("2".to_string(), "{\"solved\":false}".to_string())]);
+    }
+}
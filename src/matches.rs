@@ -0,0 +1,382 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::seq::index::sample;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use crate::hashing::GenomeHash;
+use crate::{generation_rng, Crossover, EvolveConfig, Fitness, Generator, GradedIndividual, Individual, OptimizationDirection, Population, ScalarFitness};
+
+pub type Match<I> = Arc<dyn Fn(&I, &I) -> (f32, f32) + Send + Sync>;
+
+// Who plays whom in a Matches tournament.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pairing {
+    // everyone plays everyone else once, n(n-1)/2 matches
+    RoundRobin,
+    // Every round individuals are paired with one with about as many points, avoiding
+    // rematches where possible, n/2 matches a round. An odd one out sits the round out.
+    // About log2(n) rounds rank a population nearly as well as a round robin.
+    Swiss{rounds: usize},
+}
+
+impl Pairing {
+    // Swiss pairing with enough rounds to rank a population of size
+    pub fn swiss(size: usize) -> Self {
+        Pairing::Swiss{rounds: (size.max(2) as f32).log2().ceil() as usize}
+    }
+}
+
+// Fitness from matches between the individuals of a population, for game players and the
+// like where there is no absolute measure of how good an individual is, only who beats whom.
+// play pits two individuals against each other and returns the score of each, e.g. 1 for a
+// win, 0.5 for a draw and 0 for a loss. An individual's fitness is its mean score over the
+// matches it played. The matches of a round are played in parallel.
+//
+//   let matches = Matches::new(|a: &Player, b: &Player| play(a, b), Pairing::RoundRobin);
+//   let mut population = matches.populate(100, &generator);
+//   population = matches.step(&population, &generator, &config);
+pub struct Matches<I> {
+    pub pairing: Pairing,
+    // how many parents each offspring plays while it is bred, see step
+    pub opponents: usize,
+    pub seed: Option<u64>,
+    play: Match<I>,
+}
+
+impl<I> Matches<I>
+where
+    I: Individual + Send + Sync + 'static
+{
+    pub fn new<P>(play: P, pairing: Pairing) -> Self
+    where
+        P: Fn(&I, &I) -> (f32, f32) + Send + Sync + 'static
+    {
+        Matches{
+            pairing,
+            opponents: 10,
+            seed: None,
+            play: Arc::new(play),
+        }
+    }
+
+    // Every individual's mean score, in the order given.
+    pub fn scores(&self, individuals: &[I], rng: &mut StdRng) -> Vec<f32> {
+        let n = individuals.len();
+        let mut points = vec![0.0f32; n];
+        let mut played = vec![0usize; n];
+        let mut play = |pairs: Vec<(usize, usize)>, points: &mut [f32]| {
+            for (i, j, (a, b)) in self.play_all(individuals, pairs) {
+                points[i] += a;
+                points[j] += b;
+                played[i] += 1;
+                played[j] += 1;
+            }
+        };
+        match self.pairing {
+            Pairing::RoundRobin => {
+                play((0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j))).collect(), &mut points);
+            },
+            Pairing::Swiss{rounds} => {
+                let mut met = HashSet::new();
+                for _ in 0..rounds {
+                    let pairs = swiss_pairs(&points, &mut met, rng);
+                    play(pairs, &mut points);
+                }
+            },
+        }
+        points.iter().zip(played).map(|(&points, played)| if played == 0 { 0.0 } else { points / played as f32 }).collect()
+    }
+
+    // Grades population by Elo ratings (see Elo) instead of the scores of this generation's
+    // matches alone: the individuals play a tournament, the results update their ratings and
+    // each individual's rating is its fitness. Ratings are kept by genome, so a survivor
+    // carries its rating over from earlier generations and offspring start at elo.initial.
+    // Swiss pairing pairs by rating. Ratings of genomes no longer in the population are
+    // dropped.
+    pub fn rated(&self, population: &Population<I>, elo: &mut Elo) -> Population<I>
+    where
+        I: GenomeHash,
+    {
+        let mut rng = generation_rng(self.seed, 2 * population.generation);
+        let individuals: Vec<I> = population.individuals.iter().map(|entry| entry.individual.clone()).collect();
+        let keys: Vec<u64> = individuals.par_iter().map(|individual| individual.genome_hash()).collect();
+        elo.retain(&keys);
+        let play = |pairs: Vec<(usize, usize)>, elo: &mut Elo| {
+            let results: Vec<(u64, u64, f32)> = self.play_all(&individuals, pairs).into_iter().map(|(i, j, (a, b))| {
+                (keys[i], keys[j], if a + b > 0.0 { a / (a + b) } else { 0.5 })
+            }).collect();
+            elo.update(&results);
+        };
+        match self.pairing {
+            Pairing::RoundRobin => {
+                let n = individuals.len();
+                play((0..n).flat_map(|i| (i + 1..n).map(move |j| (i, j))).collect(), elo);
+            },
+            Pairing::Swiss{rounds} => {
+                let mut met = HashSet::new();
+                for _ in 0..rounds {
+                    let standing: Vec<f32> = keys.iter().map(|&key| elo.rating(key).rating).collect();
+                    let pairs = swiss_pairs(&standing, &mut met, &mut rng);
+                    play(pairs, elo);
+                }
+            },
+        }
+        let mut graded: Vec<GradedIndividual<I>> = individuals.into_iter().zip(&keys).map(|(individual, &key)| GradedIndividual::new(individual, elo.rating(key).rating)).collect();
+        graded.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: graded,
+            generation: population.generation,
+            summary: None,
+            mutation_strength: population.mutation_strength,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    // as step, grading the new population by rating
+    pub fn step_rated<C>(&self, population: &Population<I>, crossover: &C, config: &EvolveConfig<I>, elo: &mut Elo) -> Population<I>
+    where
+        I: GenomeHash,
+        C: Crossover<I> + Send + Sync,
+    {
+        let evolved = population.evolve_with(crossover, self.against(population), config);
+        self.rated(&evolved, elo)
+    }
+
+    // the results of the matches between pairs of individuals, played in parallel
+    fn play_all(&self, individuals: &[I], pairs: Vec<(usize, usize)>) -> Vec<(usize, usize, (f32, f32))> {
+        pairs.into_par_iter().map(|(i, j)| (i, j, (self.play)(&individuals[i], &individuals[j]))).collect()
+    }
+
+    // Generates a population of size and grades it by a tournament.
+    pub fn populate<G>(&self, size: usize, generator: &G) -> Population<I>
+    where
+        G: Generator<I> + Send + Sync,
+    {
+        let ungraded: ScalarFitness<I> = Arc::new(|_: &I| 0.0f32);
+        self.graded(&Population::new(size, generator, ungraded))
+    }
+
+    // The individuals of population graded by a tournament between them and re-sorted. The
+    // summary is dropped since it was for the old grades.
+    pub fn graded(&self, population: &Population<I>) -> Population<I> {
+        let mut rng = generation_rng(self.seed, 2 * population.generation);
+        let individuals: Vec<I> = population.individuals.iter().map(|entry| entry.individual.clone()).collect();
+        let scores = self.scores(&individuals, &mut rng);
+        let mut graded: Vec<GradedIndividual<I>> = individuals.into_iter().zip(scores).map(|(individual, score)| GradedIndividual::new(individual, score)).collect();
+        graded.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: graded,
+            generation: population.generation,
+            summary: None,
+            mutation_strength: population.mutation_strength,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    // Evolves population one generation. Offspring are graded while they are bred by their
+    // mean score against opponents parents, then the new population is graded by a
+    // tournament of its own so that survivors and offspring are ranked against each other.
+    pub fn step<C>(&self, population: &Population<I>, crossover: &C, config: &EvolveConfig<I>) -> Population<I>
+    where
+        C: Crossover<I> + Send + Sync,
+    {
+        let evolved = population.evolve_with(crossover, self.against(population), config);
+        self.graded(&evolved)
+    }
+
+    // the fitness of an offspring, its mean score against parents sampled from population
+    fn against(&self, population: &Population<I>) -> Fitness<I> {
+        let mut rng = generation_rng(self.seed, 2 * population.generation + 1);
+        let count = self.opponents.max(1).min(population.individuals.len());
+        let opponents: Vec<I> = sample(&mut rng, population.individuals.len(), count).into_iter().map(|i| population.individuals[i].individual.clone()).collect();
+        let play = self.play.clone();
+        let fitness: ScalarFitness<I> = Arc::new(move |individual: &I| {
+            if opponents.is_empty() {
+                return 0.0;
+            }
+            opponents.iter().map(|opponent| play(individual, opponent).0).sum::<f32>() / opponents.len() as f32
+        });
+        fitness.into()
+    }
+}
+
+// One Swiss round: individuals in order of points (ties in random order) are each paired with
+// the next one down they haven't met yet, or the next one down if they have met everyone.
+fn swiss_pairs(points: &[f32], met: &mut HashSet<(usize, usize)>, rng: &mut StdRng) -> Vec<(usize, usize)> {
+    let mut order: Vec<usize> = (0..points.len()).collect();
+    order.shuffle(rng);
+    order.sort_by(|&a, &b| points[b].total_cmp(&points[a]));
+    let mut paired = vec![false; points.len()];
+    let mut pairs = Vec::with_capacity(points.len() / 2);
+    for (position, &i) in order.iter().enumerate() {
+        if paired[i] {
+            continue;
+        }
+        let mut rest = order[position + 1..].iter().copied().filter(|&j| !paired[j]);
+        let Some(first) = rest.clone().next() else {
+            break;
+        };
+        let j = rest.find(|&j| !met.contains(&(i.min(j), i.max(j)))).unwrap_or(first);
+        paired[i] = true;
+        paired[j] = true;
+        met.insert((i.min(j), i.max(j)));
+        pairs.push((i, j));
+    }
+    pairs
+}
+
+// An Elo rating, and how many rated matches it is based on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rating {
+    pub rating: f32,
+    pub games: usize,
+}
+
+// Elo ratings of genomes (by GenomeHash), kept across generations so match results add up
+// instead of every generation starting over, see Matches::rated. A match's score for the
+// first player is its share of the points scored, 1 for a win, 0.5 for a draw.
+#[derive(Clone, Debug)]
+pub struct Elo {
+    // how far a single match moves a rating
+    pub k: f32,
+    // the rating of a genome that hasn't played yet
+    pub initial: f32,
+    ratings: HashMap<u64, Rating>,
+}
+
+impl Elo {
+    pub const DEFAULT_K: f32 = 32.0;
+    pub const DEFAULT_INITIAL: f32 = 1500.0;
+
+    pub fn new() -> Self {
+        Elo{
+            k: Self::DEFAULT_K,
+            initial: Self::DEFAULT_INITIAL,
+            ratings: HashMap::new(),
+        }
+    }
+
+    pub fn rating(&self, key: u64) -> Rating {
+        self.ratings.get(&key).copied().unwrap_or(Rating{rating: self.initial, games: 0})
+    }
+
+    // the score a player rated a is expected to get against one rated b
+    pub fn expected(a: f32, b: f32) -> f32 {
+        1.0 / (1.0 + 10f32.powf((b - a) / 400.0))
+    }
+
+    // Updates the ratings with a round of results (first key, second key, first's score).
+    // Every match of the round is rated against the ratings from before it, so the order of
+    // the results doesn't matter. A genome playing itself isn't rated.
+    pub fn update(&mut self, results: &[(u64, u64, f32)]) {
+        let mut changes: HashMap<u64, (f32, usize)> = HashMap::new();
+        for &(a, b, score) in results {
+            if a == b {
+                continue;
+            }
+            let change = self.k * (score - Self::expected(self.rating(a).rating, self.rating(b).rating));
+            for (key, change) in [(a, change), (b, -change)] {
+                let entry = changes.entry(key).or_insert((0.0, 0));
+                entry.0 += change;
+                entry.1 += 1;
+            }
+        }
+        for (key, (change, games)) in changes {
+            let rating = self.rating(key);
+            self.ratings.insert(key, Rating{rating: rating.rating + change, games: rating.games + games});
+        }
+    }
+
+    // forgets the ratings of every genome not in keys
+    pub fn retain(&mut self, keys: &[u64]) {
+        let keep: HashSet<u64> = keys.iter().copied().collect();
+        self.ratings.retain(|key, _| keep.contains(key));
+    }
+
+    // genomes with a rating
+    pub fn len(&self) -> usize {
+        self.ratings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ratings.is_empty()
+    }
+}
+
+impl Default for Elo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Player(u32);
+
+    impl Individual for Player {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    // the stronger player always wins
+    fn play(a: &Player, b: &Player) -> (f32, f32) {
+        match a.0.cmp(&b.0) {
+            std::cmp::Ordering::Greater => (1.0, 0.0),
+            std::cmp::Ordering::Less => (0.0, 1.0),
+            std::cmp::Ordering::Equal => (0.5, 0.5),
+        }
+    }
+
+    #[test]
+    fn round_robin_scores_are_mean_points() {
+        let matches = Matches::new(play, Pairing::RoundRobin);
+        let players = vec![Player(2), Player(0), Player(3), Player(1)];
+        let scores = matches.scores(&players, &mut StdRng::seed_from_u64(1));
+        assert_eq!(scores, vec![2.0 / 3.0, 0.0, 1.0, 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn swiss_rounds_avoid_rematches() {
+        let points = vec![0.0; 8];
+        let mut met = HashSet::new();
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..3 {
+            let pairs = swiss_pairs(&points, &mut met, &mut rng);
+            assert_eq!(pairs.len(), 4);
+        }
+        // every pair was new, so 3 rounds of 4 matches met 12 different pairs
+        assert_eq!(met.len(), 12);
+    }
+
+    #[test]
+    fn swiss_ranks_the_strongest_first() {
+        let matches = Matches::new(play, Pairing::swiss(16));
+        let players: Vec<Player> = (0..16).map(Player).collect();
+        let scores = matches.scores(&players, &mut StdRng::seed_from_u64(1));
+        let best = (0..16).max_by(|&a, &b| scores[a].total_cmp(&scores[b])).unwrap();
+        assert_eq!(best, 15);
+        assert_eq!(scores[15], 1.0);
+        assert_eq!(scores[0], 0.0);
+    }
+
+    #[test]
+    fn elo_moves_ratings_by_the_surprise() {
+        let mut elo = Elo::new();
+        elo.update(&[(1, 2, 1.0)]);
+        // evenly rated, so the winner gains k / 2 and the loser loses as much
+        assert_eq!(elo.rating(1), Rating{rating: 1516.0, games: 1});
+        assert_eq!(elo.rating(2), Rating{rating: 1484.0, games: 1});
+        elo.update(&[(1, 2, 1.0), (3, 3, 1.0)]);
+        // the favourite winning again gains less, and playing yourself isn't rated
+        assert!(elo.rating(1).rating - 1516.0 < 16.0);
+        assert_eq!(elo.rating(3).games, 0);
+        elo.retain(&[1]);
+        assert_eq!(elo.len(), 1);
+    }
+}
@@ -0,0 +1,107 @@
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+use std::sync::Arc;
+use crate::{stream_rng, Fitness, GradedIndividual, Individual, Lineage, OptimizationDirection};
+
+// Improves an individual by a search of its own, e.g. hill climbing, see Memetic. grade
+// evaluates a candidate the way the population does.
+pub trait LocalSearch<I>: Send + Sync
+where
+    I: Individual + Send + Sync
+{
+    fn search(&self, start: &GradedIndividual<I>, grade: &dyn Fn(I) -> GradedIndividual<I>, rng: &mut dyn RngCore) -> GradedIndividual<I>;
+}
+
+// First improvement hill climbing: up to steps mutants are tried one after the other, each
+// replacing the current individual if it is at least as good.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HillClimbing {
+    pub steps: usize,
+}
+
+impl HillClimbing {
+    pub fn new(steps: usize) -> Self {
+        HillClimbing{
+            steps,
+        }
+    }
+}
+
+impl<I> LocalSearch<I> for HillClimbing
+where
+    I: Individual + Send + Sync
+{
+    fn search(&self, start: &GradedIndividual<I>, grade: &dyn Fn(I) -> GradedIndividual<I>, mut rng: &mut dyn RngCore) -> GradedIndividual<I> {
+        let mut current = start.clone();
+        for _ in 0..self.steps {
+            let candidate = grade(current.individual.mutate(&mut rng));
+            if candidate.fitness >= current.fitness {
+                current = candidate;
+            }
+        }
+        current
+    }
+}
+
+// What a local search improvement does to the offspring it was run on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Learning {
+    // the improved individual takes the offspring's place, so improvements are inherited
+    #[default]
+    Lamarckian,
+    // the offspring keeps its genome but gets the improved fitness, so selection favours
+    // genomes that are good starting points for the search without the search collapsing
+    // the population's diversity
+    Baldwinian,
+}
+
+// A memetic algorithm, set as EvolveConfig::memetic: every generation the best top_k
+// offspring are improved by a local search before the survivors are picked.
+//
+//   Memetic::new(HillClimbing::new(20), 5)
+#[derive(Clone)]
+pub struct Memetic<I>
+where
+    I: Individual + Send + Sync
+{
+    pub search: Arc<dyn LocalSearch<I>>,
+    pub top_k: usize,
+    pub learning: Learning,
+}
+
+impl<I> Memetic<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<S>(search: S, top_k: usize) -> Self
+    where
+        S: LocalSearch<I> + 'static
+    {
+        Memetic{
+            search: Arc::new(search),
+            top_k,
+            learning: Learning::default(),
+        }
+    }
+
+    pub(crate) fn apply(&self, offspring: &mut [(GradedIndividual<I>, Lineage)], fitness: &Fitness<I>, direction: OptimizationDirection, rng: &mut dyn RngCore) {
+        let k = self.top_k.min(offspring.len());
+        if k == 0 {
+            return;
+        }
+        offspring.select_nth_unstable_by(k - 1, |a, b| b.0.fitness.total_cmp(&a.0.fitness));
+        let base: u64 = rng.gen();
+        let grade = |individual: I| fitness.grade_for(individual, direction);
+        offspring[..k].par_iter_mut().enumerate().for_each(|(slot, (entry, _))| {
+            let improved = self.search.search(entry, &grade, &mut stream_rng(base, slot));
+            match self.learning {
+                Learning::Lamarckian => *entry = improved,
+                Learning::Baldwinian => {
+                    entry.fitness = improved.fitness;
+                    entry.raw_fitness = improved.raw_fitness;
+                    entry.scores = improved.scores;
+                },
+            }
+        });
+    }
+}
@@ -0,0 +1,125 @@
+use rand::{Rng, RngCore};
+use std::sync::Arc;
+
+// A single kind of mutation, e.g. a point mutation or a gene insertion. Building blocks for
+// Individual::mutate when a genome has several distinct ways of changing.
+pub trait Mutation<I>: Send + Sync {
+    fn mutate(&self, individual: &I, rng: &mut dyn RngCore) -> I;
+}
+
+impl<I, F> Mutation<I> for F
+where
+    F: Fn(&I, &mut dyn RngCore) -> I + Send + Sync
+{
+    fn mutate(&self, individual: &I, rng: &mut dyn RngCore) -> I {
+        self(individual, rng)
+    }
+}
+
+// Applies each stage in turn with its own probability, each stage working on the result
+// of the ones before it. If no stage fires the individual comes back unchanged.
+//
+//   MutationPipeline::new().then(point_mutation, 0.8).then(insert_gene, 0.1)
+pub struct MutationPipeline<I> {
+    stages: Vec<(Arc<dyn Mutation<I>>, f32)>,
+}
+
+impl<I> MutationPipeline<I>
+where
+    I: Clone
+{
+    pub fn new() -> Self {
+        MutationPipeline{
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn then<M>(mut self, mutation: M, probability: f32) -> Self
+    where
+        M: Mutation<I> + 'static
+    {
+        self.stages.push((Arc::new(mutation), probability));
+        self
+    }
+}
+
+impl<I> Default for MutationPipeline<I>
+where
+    I: Clone
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> Mutation<I> for MutationPipeline<I>
+where
+    I: Clone
+{
+    fn mutate(&self, individual: &I, rng: &mut dyn RngCore) -> I {
+        let mut result = individual.clone();
+        for (mutation, probability) in &self.stages {
+            if rng.gen::<f32>() < *probability {
+                result = mutation.mutate(&result, rng);
+            }
+        }
+        result
+    }
+}
+
+// Applies exactly one of its mutations, picked with probability proportional to its weight.
+//
+//   OneOf::new().or(replace_gene, 1.0).or(remove_gene, 1.0).or(append_gene, 1.0)
+pub struct OneOf<I> {
+    choices: Vec<(Arc<dyn Mutation<I>>, f32)>,
+}
+
+impl<I> OneOf<I>
+where
+    I: Clone
+{
+    pub fn new() -> Self {
+        OneOf{
+            choices: Vec::new(),
+        }
+    }
+
+    pub fn or<M>(mut self, mutation: M, weight: f32) -> Self
+    where
+        M: Mutation<I> + 'static
+    {
+        self.choices.push((Arc::new(mutation), weight));
+        self
+    }
+}
+
+impl<I> Default for OneOf<I>
+where
+    I: Clone
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> Mutation<I> for OneOf<I>
+where
+    I: Clone
+{
+    // with no choices the individual comes back unchanged
+    fn mutate(&self, individual: &I, rng: &mut dyn RngCore) -> I {
+        let total: f32 = self.choices.iter().map(|(_, weight)| weight).sum();
+        let mut pick = rng.gen::<f32>() * total;
+        for (mutation, weight) in &self.choices {
+            if pick < *weight {
+                return mutation.mutate(individual, rng);
+            }
+            pick -= weight;
+        }
+        match self.choices.last() {
+            // rounding left pick just past the last weight
+            Some((mutation, _)) => mutation.mutate(individual, rng),
+            None => individual.clone(),
+        }
+    }
+}
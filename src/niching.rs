@@ -0,0 +1,62 @@
+use rayon::prelude::*;
+use std::sync::Arc;
+use crate::{GradedIndividual, Individual};
+
+// How different two genomes are, 0.0 for identical ones. Used by the niching strategies to
+// find an individual's neighbours.
+pub trait Distance<I>: Send + Sync {
+    fn distance(&self, a: &I, b: &I) -> f32;
+}
+
+impl<I, F> Distance<I> for F
+where
+    F: Fn(&I, &I) -> f32 + Send + Sync
+{
+    fn distance(&self, a: &I, b: &I) -> f32 {
+        self(a, b)
+    }
+}
+
+// Fitness sharing: each individual's fitness is divided by its niche count, the sum of
+// 1 - (d / radius)^alpha over every individual (itself included) within radius of it.
+// Crowded regions of the search space get less credit, so the population spreads over
+// several optima instead of piling onto one. Assumes fitness is non-negative.
+#[derive(Clone)]
+pub struct FitnessSharing<I> {
+    pub radius: f32,
+    pub alpha: f32,
+    pub distance: Arc<dyn Distance<I>>,
+}
+
+impl<I> FitnessSharing<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<D>(radius: f32, distance: D) -> Self
+    where
+        D: Distance<I> + 'static
+    {
+        FitnessSharing{
+            radius,
+            alpha: 1.0,
+            distance: Arc::new(distance),
+        }
+    }
+
+    // Sets fitness to the shared fitness, computed from raw_fitness so it can be applied
+    // again every generation. Compares every pair, so O(n^2) distance calls.
+    pub fn apply(&self, population: &mut [GradedIndividual<I>]) {
+        let shared: Vec<f32> = population.par_iter().map(|entry| {
+            let niche: f32 = population.iter().map(|other| {
+                let d = self.distance.distance(&entry.individual, &other.individual);
+                if d < self.radius {
+                    1.0 - (d / self.radius).powf(self.alpha)
+                } else {
+                    0.0
+                }
+            }).sum();
+            entry.raw_fitness / niche.max(1.0)
+        }).collect();
+        population.iter_mut().zip(shared).for_each(|(entry, fitness)| entry.fitness = fitness);
+    }
+}
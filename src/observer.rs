@@ -0,0 +1,309 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use crate::bundle::Format;
+use crate::output::{Log, Verbosity};
+use crate::termination::GenerationStats;
+use crate::{GradedIndividual, Individual, Population, SnapshotStrategy};
+
+// Is told about a run as it goes, for logging, checkpointing, progress bars and the like.
+// Engine calls every observer in Engine::observers once per generation, before deciding
+// whether to stop, so the last generation is seen too. The hooks do nothing by default.
+pub trait Observer<I>: Send
+where
+    I: Individual + Send + Sync
+{
+    fn on_generation(&mut self, _stats: &GenerationStats, _population: &Population<I>) {}
+
+    // the run found a better individual than any before it, called after on_generation
+    fn on_improvement(&mut self, _best: &GradedIndividual<I>) {}
+
+    // the run is over, called by Engine::finish
+    fn on_finish(&mut self) {}
+}
+
+// Prints a run's progress to a Log the way the binaries do: at Verbosity::Generation the
+// generation number and the top individuals, at Verbosity::Debug the population's mean,
+// standard deviation and mutation strength as well. Individuals are printed with format,
+// without one only their fitness is.
+pub struct Progress<I> {
+    pub log: Log,
+    pub top: usize,
+    format: Option<Format<I>>,
+}
+
+impl<I> Progress<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new(log: Log) -> Self {
+        Progress{
+            log,
+            top: 5,
+            format: None,
+        }
+    }
+
+    pub fn with_format<F>(mut self, format: F) -> Self
+    where
+        F: Fn(&I) -> String + Send + Sync + 'static
+    {
+        self.format = Some(Arc::new(format));
+        self
+    }
+}
+
+impl<I> Observer<I> for Progress<I>
+where
+    I: Individual + Send + Sync
+{
+    fn on_generation(&mut self, stats: &GenerationStats, population: &Population<I>) {
+        if !self.log.enabled(Verbosity::Generation) {
+            return;
+        }
+        self.log.generation(format_args!("{0})", stats.generation));
+        population.individuals.iter().take(self.top).for_each(|ind| match &self.format {
+            Some(format) => self.log.generation(format_args!("\t{0} {1}", format(&ind.individual), ind.fitness)),
+            None => self.log.generation(format_args!("\t{0}", ind.fitness)),
+        });
+        if let Some(summary) = &population.summary {
+            self.log.debug(format_args!("\tmean {0} std dev {1} mutation strength {2}", summary.mean(), summary.std_dev(), population.mutation_strength));
+        }
+    }
+}
+
+// What Background::send does when the queue is full because the callback is slower than
+// evolution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    // the new item is thrown away
+    Drop,
+    // the newest queued item is replaced by the new one, so the callback skips ahead to the
+    // latest state, e.g. a plot that only needs to show the current generation
+    #[default]
+    Coalesce,
+    // evolution waits for room, nothing is lost but a slow callback slows the run down
+    Block,
+}
+
+struct Queue<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    // signalled when an item is queued or the queue is closed
+    filled: Condvar,
+    // signalled when an item is taken off
+    drained: Condvar,
+}
+
+// Closes the queue when the callback thread ends, even by a panic, so senders don't wait
+// for it forever.
+struct Closing<'a, T>(&'a Shared<T>);
+
+impl<T> Drop for Closing<'_, T> {
+    fn drop(&mut self) {
+        self.0.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).closed = true;
+        self.0.drained.notify_all();
+    }
+}
+
+// Runs a callback (logging, plotting, writing checkpoints, ...) on its own thread, fed through
+// a queue of at most capacity items, so an expensive callback doesn't hold up evolution. What
+// happens when the callback falls behind is up to the policy, and how many items were lost
+// to it is counted by dropped. Dropping the Background waits for the queued items to be
+// handled.
+//
+//   let plot = Background::spawn(4, Backpressure::Coalesce, |snapshot: Snapshot<I>| draw(&snapshot));
+//   ...
+//   plot.send(population.snapshot(100, SnapshotStrategy::Stratified));
+pub struct Background<T>
+where
+    T: Send + 'static
+{
+    pub capacity: usize,
+    pub policy: Backpressure,
+    shared: Arc<Shared<T>>,
+    dropped: AtomicUsize,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl<T> Background<T>
+where
+    T: Send + 'static
+{
+    pub fn spawn<F>(capacity: usize, policy: Backpressure, mut callback: F) -> Self
+    where
+        F: FnMut(T) + Send + 'static
+    {
+        let shared = Arc::new(Shared{
+            queue: Mutex::new(Queue{
+                items: VecDeque::new(),
+                closed: false,
+            }),
+            filled: Condvar::new(),
+            drained: Condvar::new(),
+        });
+        let worker = shared.clone();
+        let thread = std::thread::spawn(move || {
+            let _closing = Closing(&worker);
+            loop {
+                let item = {
+                    let mut queue = worker.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+                    loop {
+                        if let Some(item) = queue.items.pop_front() {
+                            break item;
+                        }
+                        if queue.closed {
+                            return;
+                        }
+                        queue = worker.filled.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner());
+                    }
+                };
+                worker.drained.notify_one();
+                callback(item);
+            }
+        });
+        Background{
+            capacity: capacity.max(1),
+            policy,
+            shared,
+            dropped: AtomicUsize::new(0),
+            thread: Some(thread),
+        }
+    }
+
+    // Queues item for the callback.
+    pub fn send(&self, item: T) {
+        let mut queue = self.shared.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if queue.closed {
+            // the callback panicked
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        if queue.items.len() >= self.capacity {
+            match self.policy {
+                Backpressure::Drop => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                },
+                Backpressure::Coalesce => {
+                    queue.items.pop_back();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                },
+                Backpressure::Block => {
+                    while queue.items.len() >= self.capacity && !queue.closed {
+                        queue = self.shared.drained.wait(queue).unwrap_or_else(|poisoned| poisoned.into_inner());
+                    }
+                },
+            }
+        }
+        queue.items.push_back(item);
+        drop(queue);
+        self.shared.filled.notify_one();
+    }
+
+    // items that never reached the callback because it fell behind
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    // Waits for the callback to handle everything queued and stops its thread. Dropping does
+    // the same, this only makes it explicit where the wait happens.
+    pub fn finish(self) {}
+}
+
+impl<T> Drop for Background<T>
+where
+    T: Send + 'static
+{
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).closed = true;
+        self.shared.filled.notify_one();
+        if let Some(thread) = self.thread.take() {
+            // a panic in the callback has already been reported on its thread
+            let _ = thread.join();
+        }
+    }
+}
+
+enum Event<I>
+where
+    I: Individual + Send + Sync
+{
+    Generation(GenerationStats, Box<Population<I>>),
+    Improvement(GradedIndividual<I>),
+}
+
+// Runs another observer on a Background thread, so a slow one (printing a lot, writing files,
+// plotting) doesn't hold up evolution. The observer is sent a copy of the population with
+// only its best keep individuals, which is all Progress looks at. Under
+// Backpressure::Coalesce an improvement can be skipped along with a generation. on_finish
+// waits for the observer to catch up, so nothing it prints comes after the run's summary.
+//
+//   engine.observers.push(Box::new(InBackground::spawn(Progress::new(log), 5, 16, Backpressure::Block)));
+pub struct InBackground<I>
+where
+    I: Individual + Send + Sync + 'static
+{
+    pub keep: usize,
+    background: Option<Background<Event<I>>>,
+}
+
+impl<I> InBackground<I>
+where
+    I: Individual + Send + Sync + 'static
+{
+    pub fn spawn<O>(mut observer: O, keep: usize, capacity: usize, policy: Backpressure) -> Self
+    where
+        O: Observer<I> + 'static
+    {
+        let background = Background::spawn(capacity, policy, move |event| match event {
+            Event::Generation(stats, population) => observer.on_generation(&stats, &population),
+            Event::Improvement(best) => observer.on_improvement(&best),
+        });
+        InBackground{
+            keep,
+            background: Some(background),
+        }
+    }
+
+    // events that never reached the observer because it fell behind
+    pub fn dropped(&self) -> usize {
+        self.background.as_ref().map_or(0, |background| background.dropped())
+    }
+}
+
+impl<I> Observer<I> for InBackground<I>
+where
+    I: Individual + Send + Sync + 'static
+{
+    fn on_generation(&mut self, stats: &GenerationStats, population: &Population<I>) {
+        let Some(background) = &self.background else {
+            return;
+        };
+        let best = population.snapshot(self.keep, SnapshotStrategy::Top);
+        background.send(Event::Generation(*stats, Box::new(Population{
+            individuals: best.individuals.to_vec(),
+            generation: population.generation,
+            summary: population.summary.clone(),
+            mutation_strength: population.mutation_strength,
+            direction: population.direction,
+        })));
+    }
+
+    fn on_improvement(&mut self, best: &GradedIndividual<I>) {
+        if let Some(background) = &self.background {
+            background.send(Event::Improvement(best.clone()));
+        }
+    }
+
+    fn on_finish(&mut self) {
+        if let Some(background) = self.background.take() {
+            background.finish();
+        }
+    }
+}
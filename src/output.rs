@@ -0,0 +1,179 @@
+use std::fmt::{Arguments, Display, Formatter};
+use std::io::Write;
+use std::str::FromStr;
+
+// How much a run reports, each level including everything below it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    // nothing at all, only the files asked for get written
+    Quiet,
+    // the result at the end of the run
+    #[default]
+    Summary,
+    // the best individuals of every generation
+    Generation,
+    // statistics of every generation on top of that
+    Debug,
+}
+
+impl Verbosity {
+    pub const ALL: [Verbosity; 4] = [
+        Verbosity::Quiet,
+        Verbosity::Summary,
+        Verbosity::Generation,
+        Verbosity::Debug,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verbosity::Quiet => "quiet",
+            Verbosity::Summary => "summary",
+            Verbosity::Generation => "generation",
+            Verbosity::Debug => "debug",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|level| level.name() == name)
+    }
+}
+
+impl Display for Verbosity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}", self.name())
+    }
+}
+
+impl FromStr for Verbosity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|level| level.name()).collect();
+            format!("unknown verbosity {0}, expected one of {1}", s, names.join(", "))
+        })
+    }
+}
+
+// Where output ends up. Implemented for closures so output can be captured or forwarded.
+pub trait Sink: Send + Sync {
+    fn write(&self, level: Verbosity, message: Arguments);
+}
+
+impl<F> Sink for F
+where
+    F: Fn(Verbosity, Arguments) + Send + Sync
+{
+    fn write(&self, level: Verbosity, message: Arguments) {
+        self(level, message)
+    }
+}
+
+// writes every message as a line on stdout
+pub struct Stdout;
+
+impl Sink for Stdout {
+    fn write(&self, _level: Verbosity, message: Arguments) {
+        let mut out = std::io::stdout().lock();
+        let _ = out.write_fmt(message);
+        let _ = out.write_all(b"\n");
+    }
+}
+
+// writes every message as a line on stderr, to keep stdout for results
+pub struct Stderr;
+
+impl Sink for Stderr {
+    fn write(&self, _level: Verbosity, message: Arguments) {
+        let mut out = std::io::stderr().lock();
+        let _ = out.write_fmt(message);
+        let _ = out.write_all(b"\n");
+    }
+}
+
+// Leveled output: messages above the verbosity are dropped before they are formatted.
+//
+//   let log = Log::new(Verbosity::Generation);
+//   log.generation(format_args!("{0}) {1}", generation, best));
+pub struct Log {
+    pub verbosity: Verbosity,
+    sink: Box<dyn Sink>,
+}
+
+impl Log {
+    // logs to stdout
+    pub fn new(verbosity: Verbosity) -> Self {
+        Self::with_sink(verbosity, Stdout)
+    }
+
+    pub fn with_sink<S>(verbosity: Verbosity, sink: S) -> Self
+    where
+        S: Sink + 'static
+    {
+        Log{
+            verbosity,
+            sink: Box::new(sink),
+        }
+    }
+
+    // true if messages at level are written, to skip work that only feeds the output
+    pub fn enabled(&self, level: Verbosity) -> bool {
+        level != Verbosity::Quiet && level <= self.verbosity
+    }
+
+    pub fn write(&self, level: Verbosity, message: Arguments) {
+        if self.enabled(level) {
+            self.sink.write(level, message);
+        }
+    }
+
+    pub fn summary(&self, message: Arguments) {
+        self.write(Verbosity::Summary, message)
+    }
+
+    pub fn generation(&self, message: Arguments) {
+        self.write(Verbosity::Generation, message)
+    }
+
+    pub fn debug(&self, message: Arguments) {
+        self.write(Verbosity::Debug, message)
+    }
+}
+
+// The outcome of a run, printed as the last line of the binaries' output so scripts don't
+// have to parse the human readable part.
+pub struct RunSummary {
+    pub solved: bool,
+    pub generations: usize,
+    pub best_fitness: f32,
+    // the best individual, formatted however the binary prints it
+    pub best: String,
+}
+
+impl RunSummary {
+    // a single line JSON object
+    pub fn to_json(&self) -> String {
+        // JSON has no representation for NaN or infinities
+        let fitness = if self.best_fitness.is_finite() { self.best_fitness.to_string() } else { "null".to_string() };
+        format!("{{\"solved\":{0},\"generations\":{1},\"best_fitness\":{2},\"best\":{3}}}",
+                self.solved, self.generations, fitness, json_string(&self.best))
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{0:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+// Saving and loading individuals as text, one individual per line, so the final population
+// or best individuals of one run can seed another (see Population::warm_start). The genome
+// format is up to the caller, format must not produce newlines.
+//
+// Files whose name ends in .zst are zstd compressed, which needs the zstd feature.
+
+fn compressed(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "zst")
+}
+
+#[cfg(not(feature = "zstd"))]
+fn no_zstd() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "reading and writing .zst files needs the zstd feature")
+}
+
+pub(crate) enum Output {
+    Plain(BufWriter<File>),
+    #[cfg(feature = "zstd")]
+    Zstd(BufWriter<zstd::stream::write::Encoder<'static, File>>),
+}
+
+impl Output {
+    pub(crate) fn create(path: &Path) -> io::Result<Self> {
+        if compressed(path) {
+            #[cfg(feature = "zstd")]
+            return Ok(Output::Zstd(BufWriter::new(zstd::stream::write::Encoder::new(File::create(path)?, 0)?)));
+            #[cfg(not(feature = "zstd"))]
+            return Err(no_zstd());
+        }
+        Ok(Output::Plain(BufWriter::new(File::create(path)?)))
+    }
+
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Plain(mut out) => out.flush(),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(out) => out.into_inner().map_err(|e| e.into_error())?.finish()?.flush(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(out) => out.write(buf),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(out) => out.flush(),
+            #[cfg(feature = "zstd")]
+            Output::Zstd(out) => out.flush(),
+        }
+    }
+}
+
+pub(crate) fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let file = File::open(path)?;
+    if compressed(path) {
+        #[cfg(feature = "zstd")]
+        return Ok(Box::new(BufReader::new(zstd::stream::read::Decoder::new(file)?)));
+        #[cfg(not(feature = "zstd"))]
+        return Err(no_zstd());
+    }
+    Ok(Box::new(BufReader::new(file)))
+}
+
+pub fn write_individuals<'a, I, P, F>(path: P, individuals: impl IntoIterator<Item = &'a I>, format: F) -> io::Result<()>
+where
+    I: 'a,
+    P: AsRef<Path>,
+    F: Fn(&I) -> String,
+{
+    let mut out = Output::create(path.as_ref())?;
+    for individual in individuals {
+        writeln!(out, "{}", format(individual))?;
+    }
+    out.finish()
+}
+
+// Blank lines are skipped, a line that parse rejects is an InvalidData error.
+pub fn read_individuals<I, P, F>(path: P, parse: F) -> io::Result<Vec<I>>
+where
+    P: AsRef<Path>,
+    F: Fn(&str) -> Option<I>,
+{
+    let mut individuals = Vec::new();
+    for_each_line(open(path.as_ref())?, |number, line| {
+        individuals.push(parse(line).ok_or_else(|| invalid_line(number, line))?);
+        Ok(())
+    })?;
+    Ok(individuals)
+}
+
+// Like write_individuals, but each distinct genome is written once, as "count genome", in the
+// order they first appear. A converged population is mostly copies of a few genomes, so this
+// is much smaller.
+pub fn write_deduplicated<'a, I, P, F>(path: P, individuals: impl IntoIterator<Item = &'a I>, format: F) -> io::Result<()>
+where
+    I: 'a,
+    P: AsRef<Path>,
+    F: Fn(&I) -> String,
+{
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut order = Vec::new();
+    for individual in individuals {
+        let line = format(individual);
+        match counts.get_mut(&line) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(line.clone(), 1);
+                order.push(line);
+            },
+        }
+    }
+
+    let mut out = Output::create(path.as_ref())?;
+    for line in order {
+        writeln!(out, "{0} {1}", counts[&line], line)?;
+    }
+    out.finish()
+}
+
+// Reads a file written by write_deduplicated, with every genome repeated count times.
+pub fn read_deduplicated<I, P, F>(path: P, parse: F) -> io::Result<Vec<I>>
+where
+    I: Clone,
+    P: AsRef<Path>,
+    F: Fn(&str) -> Option<I>,
+{
+    let mut individuals = Vec::new();
+    for_each_line(open(path.as_ref())?, |number, line| {
+        let (count, genome) = line.split_once(' ').ok_or_else(|| invalid_line(number, line))?;
+        let count: usize = count.parse().map_err(|_| invalid_line(number, line))?;
+        let individual = parse(genome).ok_or_else(|| invalid_line(number, line))?;
+        individuals.extend(std::iter::repeat_n(individual, count));
+        Ok(())
+    })?;
+    Ok(individuals)
+}
+
+fn invalid_line(number: usize, line: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {0}: can't parse {1:?}", number + 1, line))
+}
+
+// calls f with the number and trimmed text of every line that isn't blank
+fn for_each_line<R, F>(input: R, mut f: F) -> io::Result<()>
+where
+    R: BufRead,
+    F: FnMut(usize, &str) -> io::Result<()>,
+{
+    for (number, line) in input.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        f(number, line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ga-persist-{0}-{1}", std::process::id(), name))
+    }
+
+    fn round_trip(name: &str) {
+        let path = path(name);
+        let individuals = vec![3, 1, 4, 1, 5];
+        write_individuals(&path, &individuals, |n| n.to_string()).unwrap();
+        let read = read_individuals(&path, |line| line.parse::<i32>().ok());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(read.unwrap(), individuals);
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        round_trip("plain.txt");
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn saves_and_loads_compressed() {
+        round_trip("compressed.txt.zst");
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn compressed_needs_zstd() {
+        let error = write_individuals(path("compressed.txt.zst"), &[1], |n: &i32| n.to_string()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn saves_and_loads_deduplicated() {
+        let path = path("deduplicated.txt");
+        let individuals = vec![1, 2, 1, 1, 3, 2];
+        write_deduplicated(&path, &individuals, |n| n.to_string()).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let read = read_deduplicated(&path, |line| line.parse::<i32>().ok());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, "3 1\n2 2\n1 3\n");
+        assert_eq!(read.unwrap(), vec![1, 1, 1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn rejects_lines_it_cannot_parse() {
+        let path = path("invalid.txt");
+        std::fs::write(&path, "1\n\nx\n").unwrap();
+        let error = read_individuals(&path, |line| line.parse::<i32>().ok()).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 3"));
+    }
+}
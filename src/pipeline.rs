@@ -0,0 +1,188 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::sync::Arc;
+use crate::selection::Selection;
+use crate::summary::Summary;
+use crate::{collect_summarized, resampled, sample_key, stream_rng, Crossover, EvolveConfig, Fitness, GradedIndividual, Individual, Lineage, Population, Rates, Success};
+
+// The crossover of a pipeline without a crossover stage, never applied since its rate is 0.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoCrossover;
+
+impl<I> Crossover<I> for NoCrossover
+where
+    I: Clone
+{
+    fn crossover<R: Rng>(&self, a: &I, _b: &I, _rng: &mut R) -> I {
+        a.clone()
+    }
+}
+
+// How offspring are bred, as a chain of stages applied to every offspring in turn:
+// select → crossover → mutate → repair → evaluate. Used by Population::evolve_pipeline,
+// which takes everything else (replacement, sharing, constraints, ...) from its config.
+//
+//   Pipeline::new()
+//       .select(Tournament::new(3))
+//       .crossover(OnePoint{}, 0.9)
+//       .mutate(0.2)
+//       .repair(|tour: Tour| tour.fix_duplicates())
+//
+// Offspring that are neither crossed over nor mutated are copies of their parent and keep
+// its grade rather than being evaluated again.
+pub struct Pipeline<I, C = NoCrossover>
+where
+    I: Individual + Send + Sync
+{
+    // picks both parents of every offspring, by default they are picked the way
+    // Population::evolve does
+    selection: Option<Arc<dyn Selection<I>>>,
+    crossover: C,
+    rates: Rates,
+    // even offspring are mutants and odd ones crossover children, instead of using rates
+    alternate: bool,
+    repairs: Vec<Arc<dyn Fn(I) -> I + Send + Sync>>,
+}
+
+impl<I> Pipeline<I>
+where
+    I: Individual + Send + Sync
+{
+    // every offspring is a mutant of its parent
+    pub fn new() -> Self {
+        Pipeline{
+            selection: None,
+            crossover: NoCrossover,
+            rates: Rates::new(0.0, 1.0),
+            alternate: false,
+            repairs: Vec::new(),
+        }
+    }
+}
+
+impl<I> Default for Pipeline<I>
+where
+    I: Individual + Send + Sync
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I, C> Pipeline<I, C>
+where
+    I: Individual + Send + Sync,
+    C: Crossover<I> + Send + Sync,
+{
+    // What Population::evolve does: offspring alternate between mutation and crossover.
+    pub fn alternating(crossover: C) -> Self {
+        Pipeline{
+            selection: None,
+            crossover,
+            rates: Rates::new(0.5, 0.5),
+            alternate: true,
+            repairs: Vec::new(),
+        }
+    }
+
+    // What Population::evolve_with does with EvolveConfig::rates set.
+    pub fn with_rates(crossover: C, rates: Rates) -> Self {
+        Pipeline{
+            selection: None,
+            crossover,
+            rates,
+            alternate: false,
+            repairs: Vec::new(),
+        }
+    }
+
+    pub fn select<S>(mut self, selection: S) -> Self
+    where
+        S: Selection<I> + 'static
+    {
+        self.selection = Some(Arc::new(selection));
+        self
+    }
+
+    // crosses the parents over with probability rate
+    pub fn crossover<D>(self, crossover: D, rate: f32) -> Pipeline<I, D>
+    where
+        D: Crossover<I> + Send + Sync
+    {
+        Pipeline{
+            selection: self.selection,
+            crossover,
+            rates: Rates::new(rate, self.rates.mutation),
+            alternate: false,
+            repairs: self.repairs,
+        }
+    }
+
+    // mutates the child with probability rate, at the population's mutation strength
+    pub fn mutate(mut self, rate: f32) -> Self {
+        self.rates.mutation = rate;
+        self.alternate = false;
+        self
+    }
+
+    // Fixes up every varied child before it is evaluated, repairs run in the order added.
+    pub fn repair<F>(mut self, repair: F) -> Self
+    where
+        F: Fn(I) -> I + Send + Sync + 'static
+    {
+        self.repairs.push(Arc::new(repair));
+        self
+    }
+
+    // the crossover and mutation rates, meaningless for an alternating pipeline
+    pub fn rates(&self) -> Rates {
+        self.rates
+    }
+
+    // Breeds slots offspring from population, each slot with its own rng seeded from rng.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn breed(&self, population: &Population<I>, fitness: &Fitness<I>, config: &EvolveConfig<I>, slots: usize, generation: usize, rng: &mut StdRng, success: &Success) -> (Vec<(GradedIndividual<I>, Lineage)>, Summary<I>) {
+        let individuals = &population.individuals;
+        let parents = self.selection.as_ref().map(|s| s.select(individuals, generation, 2 * slots, rng));
+        let base: u64 = rng.gen();
+        let offset = individuals.len().saturating_sub(slots);
+        let strength = population.mutation_strength;
+
+        collect_summarized((0..slots).into_par_iter().map(|slot| -> (u64, (GradedIndividual<I>, Lineage)) {
+            let mut r = stream_rng(base, slot);
+            let (first, second) = match &parents {
+                Some(p) => (p[2 * slot], p[2 * slot + 1]),
+                None => ((offset + slot) % individuals.len(), r.gen_range(0..individuals.len())),
+            };
+            let (cross, mutate) = match self.alternate {
+                true => (slot % 2 == 1, slot % 2 == 0),
+                false => (r.gen::<f32>() < self.rates.crossover, r.gen::<f32>() < self.rates.mutation),
+            };
+            let entry = &individuals[first];
+            if !cross && !mutate {
+                return (sample_key(base, slot), (entry.clone(), Lineage::new(first, None)));
+            }
+
+            let partner = &individuals[second].individual;
+            let vary = |r: &mut StdRng| -> I {
+                let child = match cross {
+                    true => self.crossover.crossover(&entry.individual, partner, r),
+                    false => entry.individual.clone(),
+                };
+                let child = match mutate {
+                    true => child.mutate_scaled(r, strength),
+                    false => child,
+                };
+                self.repairs.iter().fold(child, |child, repair| repair(child))
+            };
+            let child = vary(&mut r);
+            let child = resampled(config, child, vary, &mut r);
+            let child = fitness.grade_recovering(child, population.direction, config);
+            if !cross {
+                success.record(entry.raw_fitness, child.raw_fitness);
+            }
+            (sample_key(base, slot), (child, Lineage::new(first, cross.then_some(second))))
+        }))
+    }
+}
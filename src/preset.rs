@@ -0,0 +1,111 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::budget::GenerationBudget;
+use crate::replacement::{Elitism, MuPlusLambda};
+use crate::selection::Tournament;
+use crate::{Adaptation, EvolveConfig, Individual, Rates};
+
+// Named combinations of selection, rates, elitism and termination that work well together,
+// a starting point for evolve_with when there is no reason yet to pick each one.
+//
+//   let config = Preset::SteadyState.config();
+//   let mut budget = Preset::SteadyState.budget(1000, population_size);
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Preset {
+    // the whole population is replaced every generation, bar the best, by children of binary
+    // tournament winners
+    ClassicGenerational,
+    // two children a generation replace the worst of the population if they beat them
+    SteadyState,
+    // high crossover and mutation rates with self-adapting mutation strength and weak
+    // selection, for rugged problems that trap the classic preset in local optima, run for as
+    // long as it keeps improving
+    AggressiveExploration,
+    // a fifth of the population kept as elites and many children bred as copies, which are not
+    // evaluated again, with strong selection so few evaluations are wasted on bad parents
+    ExpensiveFitness,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::ClassicGenerational,
+        Preset::SteadyState,
+        Preset::AggressiveExploration,
+        Preset::ExpensiveFitness,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::ClassicGenerational => "classic-generational",
+            Preset::SteadyState => "steady-state",
+            Preset::AggressiveExploration => "aggressive-exploration",
+            Preset::ExpensiveFitness => "expensive-fitness",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|preset| preset.name() == name)
+    }
+
+    pub fn config<I>(&self) -> EvolveConfig<I>
+    where
+        I: Individual + Send + Sync + 'static
+    {
+        match self {
+            Preset::ClassicGenerational => EvolveConfig{
+                selection: Some(Arc::new(Tournament::new(2))),
+                elitism: Elitism::Count(1),
+                rates: Some(Rates::new(0.8, 0.2)),
+                ..EvolveConfig::default()
+            },
+            Preset::SteadyState => EvolveConfig{
+                selection: Some(Arc::new(Tournament::new(2))),
+                replacement: Some(Arc::new(MuPlusLambda::new(2))),
+                rates: Some(Rates::new(0.9, 0.5)),
+                ..EvolveConfig::default()
+            },
+            Preset::AggressiveExploration => EvolveConfig{
+                selection: Some(Arc::new(Tournament::new(2))),
+                elitism: Elitism::Count(1),
+                rates: Some(Rates::new(0.9, 0.8)),
+                adaptation: Some(Adaptation::new(0.2, 1.5, 0.25, 8.0)),
+                selection_pressure: Some(1.2),
+                ..EvolveConfig::default()
+            },
+            Preset::ExpensiveFitness => EvolveConfig{
+                selection: Some(Arc::new(Tournament::new(4))),
+                elitism: Elitism::Fraction(0.2),
+                rates: Some(Rates::new(0.6, 0.4)),
+                ..EvolveConfig::default()
+            },
+        }
+    }
+
+    // When to stop, for a run the size of max_generations generations of the classic preset.
+    // Steady state runs get the generations for as many evaluations.
+    pub fn budget(&self, max_generations: usize, population_size: usize) -> GenerationBudget {
+        match self {
+            Preset::SteadyState => GenerationBudget::new(max_generations * population_size.div_ceil(2)),
+            Preset::AggressiveExploration => GenerationBudget::extendable(max_generations, 2 * max_generations, 0.0),
+            Preset::ClassicGenerational | Preset::ExpensiveFitness => GenerationBudget::new(max_generations),
+        }
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{0}", self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|preset| preset.name()).collect();
+            format!("unknown preset {0}, expected one of {1}", s, names.join(", "))
+        })
+    }
+}
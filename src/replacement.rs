@@ -0,0 +1,286 @@
+use rand::{Rng, RngCore};
+use rayon::prelude::*;
+use std::sync::Arc;
+use crate::{GradedIndividual, Individual, Lineage};
+use crate::niching::Distance;
+use crate::schedule::Schedule;
+
+// Decides which individuals make it into the next generation, out of the current
+// population (sorted best first) and the offspring bred from it. generation is the one
+// being made, as for Selection::select.
+pub trait Replacement<I>: Send + Sync
+where
+    I: Individual + Send + Sync
+{
+    // number of offspring to breed for a population of the given size
+    fn offspring(&self, population_size: usize) -> usize;
+
+    // the survivors, in any order, the population sorts them afterwards
+    fn replace(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, generation: usize, rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>>;
+
+    // Like replace, with lineage[i] giving the parents offspring[i] was bred from. Only
+    // strategies that match offspring up with their parents need to override this.
+    fn replace_with_lineage(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, _lineage: &[Lineage], generation: usize, rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        self.replace(parents, offspring, generation, rng)
+    }
+}
+
+// The offspring replace the whole population.
+#[derive(Clone, Default)]
+pub struct Generational {}
+
+impl<I> Replacement<I> for Generational
+where
+    I: Individual + Send + Sync
+{
+    fn offspring(&self, population_size: usize) -> usize {
+        population_size
+    }
+
+    fn replace(&self, _parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, _generation: usize, _rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        offspring
+    }
+}
+
+// How many of the best individuals are carried over into the next generation unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Elitism {
+    Disabled,
+    Count(usize),
+    // fraction of the population size, rounded down
+    Fraction(f32),
+}
+
+impl Elitism {
+    pub fn count(&self, population_size: usize) -> usize {
+        match *self {
+            Elitism::Disabled => 0,
+            Elitism::Count(count) => count.min(population_size),
+            Elitism::Fraction(fraction) => ((population_size as f32 * fraction) as usize).min(population_size),
+        }
+    }
+}
+
+impl Default for Elitism {
+    fn default() -> Self {
+        Elitism::Fraction(0.1)
+    }
+}
+
+// The elites are copied over unchanged and offspring fill the rest.
+#[derive(Clone, Default)]
+pub struct Elitist {
+    pub elitism: Elitism,
+}
+
+impl Elitist {
+    pub fn new(elitism: Elitism) -> Self {
+        Elitist{
+            elitism,
+        }
+    }
+}
+
+impl<I> Replacement<I> for Elitist
+where
+    I: Individual + Send + Sync
+{
+    fn offspring(&self, population_size: usize) -> usize {
+        population_size - self.elitism.count(population_size)
+    }
+
+    fn replace(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, _generation: usize, _rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        let mut survivors = parents[..self.elitism.count(parents.len())].to_vec();
+        survivors.extend(offspring);
+        survivors
+    }
+}
+
+// (mu + lambda): lambda offspring are bred and the best mu of parents and offspring
+// together survive, where mu is the current population size.
+#[derive(Clone)]
+pub struct MuPlusLambda {
+    pub lambda: usize,
+}
+
+impl MuPlusLambda {
+    pub fn new(lambda: usize) -> Self {
+        MuPlusLambda{
+            lambda,
+        }
+    }
+}
+
+impl<I> Replacement<I> for MuPlusLambda
+where
+    I: Individual + Send + Sync
+{
+    fn offspring(&self, _population_size: usize) -> usize {
+        self.lambda
+    }
+
+    fn replace(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, _generation: usize, _rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        let mut survivors = parents.to_vec();
+        survivors.extend(offspring);
+        survivors.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        survivors.truncate(parents.len());
+        survivors
+    }
+}
+
+// Deterministic crowding: every offspring competes only against the more similar of its
+// parents and takes that parent's place if it is fitter. Offspring replace like with like,
+// so several niches can survive side by side. Breeds a full population of offspring.
+#[derive(Clone)]
+pub struct DeterministicCrowding<I> {
+    pub distance: Arc<dyn Distance<I>>,
+}
+
+impl<I> DeterministicCrowding<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<D>(distance: D) -> Self
+    where
+        D: Distance<I> + 'static
+    {
+        DeterministicCrowding{
+            distance: Arc::new(distance),
+        }
+    }
+}
+
+impl<I> Replacement<I> for DeterministicCrowding<I>
+where
+    I: Individual + Send + Sync
+{
+    fn offspring(&self, population_size: usize) -> usize {
+        population_size
+    }
+
+    // without lineage offspring i is taken to be the child of parent i
+    fn replace(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, generation: usize, rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        let lineage: Vec<Lineage> = (0..offspring.len()).map(|i| Lineage{parent: i % parents.len().max(1), partner: None}).collect();
+        self.replace_with_lineage(parents, offspring, &lineage, generation, rng)
+    }
+
+    fn replace_with_lineage(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, lineage: &[Lineage], _generation: usize, _rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        // the parent each child competes against, picked in parallel
+        let slots: Vec<usize> = offspring.par_iter().zip(lineage.par_iter()).map(|(child, lineage)| {
+            match lineage.partner {
+                Some(partner) if self.distance.distance(&child.individual, &parents[partner].individual)
+                    < self.distance.distance(&child.individual, &parents[lineage.parent].individual) => partner,
+                _ => lineage.parent,
+            }
+        }).collect();
+
+        // a parent picked by several children goes to the fittest of them
+        let mut survivors = parents.to_vec();
+        for (child, slot) in offspring.into_iter().zip(slots) {
+            if child.fitness > survivors[slot].fitness {
+                survivors[slot] = child;
+            }
+        }
+        survivors
+    }
+}
+
+// Restricted tournament selection: every offspring picks window individuals of the
+// population at random and competes against the most similar of them, replacing it if the
+// offspring is fitter. Like crowding this keeps several optima alive, but without needing
+// to know an offspring's parents. Breeds a full population of offspring.
+#[derive(Clone)]
+pub struct RestrictedTournament<I> {
+    pub window: usize,
+    pub distance: Arc<dyn Distance<I>>,
+}
+
+impl<I> RestrictedTournament<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<D>(window: usize, distance: D) -> Self
+    where
+        D: Distance<I> + 'static
+    {
+        RestrictedTournament{
+            window,
+            distance: Arc::new(distance),
+        }
+    }
+}
+
+impl<I> Replacement<I> for RestrictedTournament<I>
+where
+    I: Individual + Send + Sync
+{
+    fn offspring(&self, population_size: usize) -> usize {
+        population_size
+    }
+
+    // offspring go in one at a time, so later ones compete against earlier winners
+    fn replace(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, _generation: usize, rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        let mut survivors = parents.to_vec();
+        if survivors.is_empty() {
+            return survivors;
+        }
+        for child in offspring {
+            let closest = (0..self.window.max(1)).map(|_| {
+                let i = rng.gen_range(0..survivors.len());
+                (i, self.distance.distance(&child.individual, &survivors[i].individual))
+            }).min_by(|a, b| a.1.total_cmp(&b.1)).unwrap().0;
+            if child.fitness > survivors[closest].fitness {
+                survivors[closest] = child;
+            }
+        }
+        survivors
+    }
+}
+
+// Simulated annealing acceptance, for an SA/GA hybrid on rugged landscapes: every offspring
+// competes against its parent and takes its place if it is fitter, or if it is worse with
+// probability exp(-(parent fitness - offspring fitness) / temperature). The temperature is
+// taken from a schedule by generation like a mutation rate, high early on so the search can
+// walk out of local optima and lowered so it settles later. Breeds a full population of
+// offspring.
+#[derive(Clone)]
+pub struct Annealing {
+    pub temperature: Schedule,
+}
+
+impl Annealing {
+    pub fn new(temperature: Schedule) -> Self {
+        Annealing{
+            temperature,
+        }
+    }
+}
+
+impl<I> Replacement<I> for Annealing
+where
+    I: Individual + Send + Sync
+{
+    fn offspring(&self, population_size: usize) -> usize {
+        population_size
+    }
+
+    // without lineage offspring i is taken to be the child of parent i
+    fn replace(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, generation: usize, rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        let lineage: Vec<Lineage> = (0..offspring.len()).map(|i| Lineage{parent: i % parents.len().max(1), partner: None}).collect();
+        self.replace_with_lineage(parents, offspring, &lineage, generation, rng)
+    }
+
+    fn replace_with_lineage(&self, parents: &[GradedIndividual<I>], offspring: Vec<GradedIndividual<I>>, lineage: &[Lineage], generation: usize, rng: &mut dyn RngCore) -> Vec<GradedIndividual<I>> {
+        let temperature = self.temperature.at(generation);
+        let mut survivors = parents.to_vec();
+        for (child, lineage) in offspring.into_iter().zip(lineage) {
+            let parent = &survivors[lineage.parent];
+            let accept = child.fitness >= parent.fitness
+                || (temperature > 0.0 && rng.gen::<f32>() < ((child.fitness - parent.fitness) / temperature).exp());
+            if accept {
+                survivors[lineage.parent] = child;
+            }
+        }
+        survivors
+    }
+}
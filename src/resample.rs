@@ -0,0 +1,65 @@
+use rand::rngs::StdRng;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{Generator, Individual};
+
+// Rejection resampling of invalid offspring (see Individual::is_valid). Set on
+// EvolveConfig::resample, an offspring that isn't valid is bred again from the same parents
+// up to retries times, and if none of the attempts is valid a fresh individual is generated
+// in its place, so invalid individuals are never evaluated or make it into the population.
+//
+//   Resample::new(5, generator)
+pub struct Resample<I> {
+    pub retries: usize,
+    generate: Arc<dyn Fn(&mut StdRng) -> I + Send + Sync>,
+    rejected: AtomicUsize,
+    generated: AtomicUsize,
+}
+
+impl<I> Resample<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<G>(retries: usize, generator: G) -> Self
+    where
+        G: Generator<I> + Send + Sync + 'static
+    {
+        Resample{
+            retries,
+            generate: Arc::new(move |r: &mut StdRng| generator.generate(r)),
+            rejected: AtomicUsize::new(0),
+            generated: AtomicUsize::new(0),
+        }
+    }
+
+    // child if it is valid, otherwise the first valid one of up to retries more made by
+    // retry, otherwise a generated one
+    pub(crate) fn check<F>(&self, child: I, mut retry: F, r: &mut StdRng) -> I
+    where
+        F: FnMut(&mut StdRng) -> I
+    {
+        if child.is_valid() {
+            return child;
+        }
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+        for _ in 0..self.retries {
+            let child = retry(r);
+            if child.is_valid() {
+                return child;
+            }
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        self.generated.fetch_add(1, Ordering::Relaxed);
+        (self.generate)(r)
+    }
+
+    // invalid offspring thrown away so far
+    pub fn rejected(&self) -> usize {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    // offspring generated from scratch because every retry was invalid
+    pub fn generated(&self) -> usize {
+        self.generated.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,115 @@
+use rand::Rng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+pub type FallibleFitness<I> = Box<dyn Fn(&I) -> Result<f32, Error> + Send + Sync>;
+
+// What an individual is graded when every attempt to evaluate it failed.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OnFailure {
+    // the worst fitness there is, so it won't survive, the highest when minimizing
+    #[default]
+    Worst,
+    Fitness(f32),
+    // give up on the run
+    Panic,
+}
+
+// Retries and failures of a Retrying fitness function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetryStats {
+    // attempts that failed and were tried again
+    pub retries: usize,
+    // individuals that failed every attempt and were graded by the failure policy
+    pub failures: usize,
+}
+
+// A fitness function that can fail, e.g. one that calls out to a server or runs a
+// subprocess, set as Fitness::Retrying. A failed evaluation is tried again up to retries
+// times, waiting backoff before the first retry and twice as long before each one after that
+// (up to max_backoff), each wait stretched or shrunk by a random jitter fraction so
+// evaluations that failed together don't all retry at the same moment. If the last attempt
+// fails too on_failure decides the fitness. Waiting blocks the worker thread, so it is meant
+// for failures that are rare.
+//
+//   let fitness: Fitness<Config> = Retrying::new(|config: &Config| benchmark(config)).into();
+pub struct Retrying<I> {
+    evaluate: FallibleFitness<I>,
+    pub retries: usize,
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    pub jitter: f32,
+    pub on_failure: OnFailure,
+    // for the current generation and all of them
+    generation: [AtomicUsize; 2],
+    total: [AtomicUsize; 2],
+}
+
+impl<I> Retrying<I> {
+    pub fn new<F>(evaluate: F) -> Self
+    where
+        F: Fn(&I) -> Result<f32, Error> + Send + Sync + 'static
+    {
+        Retrying{
+            evaluate: Box::new(evaluate),
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            jitter: 0.5,
+            on_failure: OnFailure::default(),
+            generation: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            total: [AtomicUsize::new(0), AtomicUsize::new(0)],
+        }
+    }
+
+    // the fitness of individual, None if it failed and the failure policy is Worst
+    pub fn evaluate(&self, individual: &I) -> Option<f32> {
+        let mut wait = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let error = match (self.evaluate)(individual) {
+                Ok(fitness) => return Some(fitness),
+                Err(error) => error,
+            };
+            if attempt == self.retries {
+                self.count(1);
+                return match self.on_failure {
+                    OnFailure::Worst => None,
+                    OnFailure::Fitness(fitness) => Some(fitness),
+                    OnFailure::Panic => panic!("evaluation failed {0} times, last with: {1}", attempt + 1, error),
+                };
+            }
+            attempt += 1;
+            self.count(0);
+            let jitter = 1.0 + self.jitter * rand::thread_rng().gen_range(-1.0f32..=1.0);
+            std::thread::sleep(wait.mul_f32(jitter.max(0.0)));
+            wait = (wait * 2).min(self.max_backoff);
+        }
+    }
+
+    fn count(&self, stat: usize) {
+        self.generation[stat].fetch_add(1, Ordering::Relaxed);
+        self.total[stat].fetch_add(1, Ordering::Relaxed);
+    }
+
+    // since the current generation started being graded
+    pub fn generation_stats(&self) -> RetryStats {
+        RetryStats{
+            retries: self.generation[0].load(Ordering::Relaxed),
+            failures: self.generation[1].load(Ordering::Relaxed),
+        }
+    }
+
+    // over the whole run
+    pub fn stats(&self) -> RetryStats {
+        RetryStats{
+            retries: self.total[0].load(Ordering::Relaxed),
+            failures: self.total[1].load(Ordering::Relaxed),
+        }
+    }
+
+    pub(crate) fn reset(&self) {
+        self.generation.iter().for_each(|count| count.store(0, Ordering::Relaxed));
+    }
+}
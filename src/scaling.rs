@@ -0,0 +1,102 @@
+// Transforms fitness values into the weights used by fitness proportionate selection (see
+// selection::Proportionate). Raw fitness makes a poor weight: one super-fit individual in the
+// first generation can take nearly every pick and wipe out the rest of the population, while
+// late in a run everyone has about the same fitness and selection barely discriminates.
+pub trait FitnessScaler: Send + Sync {
+    // one non-negative weight per fitness, in the same order
+    fn scale(&self, fitness: &[f32]) -> Vec<f32>;
+}
+
+impl<F> FitnessScaler for F
+where
+    F: Fn(&[f32]) -> Vec<f32> + Send + Sync
+{
+    fn scale(&self, fitness: &[f32]) -> Vec<f32> {
+        self(fitness)
+    }
+}
+
+fn mean_and_std_dev(fitness: &[f32]) -> (f32, f32) {
+    let n = fitness.len().max(1) as f32;
+    let mean = fitness.iter().sum::<f32>() / n;
+    let variance = fitness.iter().map(|f| (f - mean) * (f - mean)).sum::<f32>() / n;
+    (mean, variance.sqrt())
+}
+
+// Sigma truncation: weight = fitness - (mean - c * std dev), or 0 if that is negative. The
+// weights depend on how far above average an individual is relative to the spread, so
+// selection pressure stays about the same over the run. Goldberg suggests c between 1 and 3.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SigmaScaling {
+    pub c: f32,
+}
+
+impl SigmaScaling {
+    pub fn new(c: f32) -> Self {
+        SigmaScaling{
+            c,
+        }
+    }
+}
+
+impl Default for SigmaScaling {
+    fn default() -> Self {
+        Self::new(2.0)
+    }
+}
+
+impl FitnessScaler for SigmaScaling {
+    fn scale(&self, fitness: &[f32]) -> Vec<f32> {
+        let (mean, std_dev) = mean_and_std_dev(fitness);
+        if std_dev == 0.0 {
+            return vec![1.0; fitness.len()];
+        }
+        let floor = mean - self.c * std_dev;
+        fitness.iter().map(|f| (f - floor).max(0.0)).collect()
+    }
+}
+
+// Linear scaling: weight = a * fitness + b, chosen so the average individual keeps its
+// average weight and the best gets multiple times the average (1.2 to 2 is typical). When
+// that would push the worst below 0, the scaling is instead chosen to put the worst at 0.
+// Fitness is first shifted up to be non-negative if it isn't already.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinearScaling {
+    pub multiple: f32,
+}
+
+impl LinearScaling {
+    pub fn new(multiple: f32) -> Self {
+        LinearScaling{
+            multiple,
+        }
+    }
+}
+
+impl Default for LinearScaling {
+    fn default() -> Self {
+        Self::new(2.0)
+    }
+}
+
+impl FitnessScaler for LinearScaling {
+    fn scale(&self, fitness: &[f32]) -> Vec<f32> {
+        let min = fitness.iter().copied().fold(f32::INFINITY, f32::min);
+        let shift = if min < 0.0 { -min } else { 0.0 };
+        let (min, max) = (min + shift, fitness.iter().copied().fold(f32::NEG_INFINITY, f32::max) + shift);
+        let mean = mean_and_std_dev(fitness).0 + shift;
+        if max <= mean {
+            return vec![1.0; fitness.len()];
+        }
+
+        let c = self.multiple;
+        let (a, b) = if min > (c * mean - max) / (c - 1.0) {
+            let delta = max - mean;
+            ((c - 1.0) * mean / delta, mean * (max - c * mean) / delta)
+        } else {
+            let delta = mean - min;
+            (mean / delta, -min * mean / delta)
+        };
+        fitness.iter().map(|f| (a * (f + shift) + b).max(0.0)).collect()
+    }
+}
@@ -0,0 +1,50 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+// A value that changes over the generations of a run: a mutation rate or strength, a
+// selection temperature, ...
+#[derive(Clone)]
+pub struct Schedule {
+    f: Arc<dyn Fn(usize) -> f32 + Send + Sync>,
+}
+
+impl Schedule {
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(usize) -> f32 + Send + Sync + 'static
+    {
+        Schedule{
+            f: Arc::new(f),
+        }
+    }
+
+    pub fn constant(value: f32) -> Self {
+        Self::new(move |_| value)
+    }
+
+    // start at generation 0 to end at generation generations, then end from there on
+    pub fn linear(start: f32, end: f32, generations: usize) -> Self {
+        Self::new(move |generation| {
+            let t = generation.min(generations) as f32 / generations.max(1) as f32;
+            start + (end - start) * t
+        })
+    }
+
+    // initial * decay^generation
+    pub fn exponential(initial: f32, decay: f32) -> Self {
+        Self::new(move |generation| initial * decay.powi(generation as i32))
+    }
+
+    // Cosine wave between high (at the start of each period) and low (half way through),
+    // for alternating between exploration and refinement.
+    pub fn cyclic(low: f32, high: f32, period: usize) -> Self {
+        Self::new(move |generation| {
+            let phase = (generation % period.max(1)) as f32 / period.max(1) as f32;
+            low + (high - low) * 0.5 * (1.0 + (2.0 * PI * phase).cos())
+        })
+    }
+
+    pub fn at(&self, generation: usize) -> f32 {
+        (self.f)(generation)
+    }
+}
@@ -0,0 +1,465 @@
+use rand::{Rng, RngCore, SeedableRng};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use crate::{by_fitness, GradedIndividual, Individual};
+use std::sync::Arc;
+use crate::scaling::{FitnessScaler, LinearScaling};
+use crate::schedule::Schedule;
+
+// Picks parents out of a population that is sorted best first, at least as far as its best k
+// under SortMode::TopK.
+// All of the parents for a generation are picked in one call so that strategies can
+// do their per-generation work (weights, temperatures, ...) once.
+pub trait Selection<I>: Send + Sync
+where
+    I: Individual + Send + Sync
+{
+    fn select(&self, population: &[GradedIndividual<I>], generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize>;
+
+    // Like select, but with the selection pressure set to pressure (see
+    // EvolveConfig::selection_pressure): the expected number of picks of the best individual
+    // per population size picks, from 1.0 (none) to 2.0 (strong). Strategies without a notion
+    // of pressure ignore it.
+    fn select_with_pressure(&self, population: &[GradedIndividual<I>], generation: usize, count: usize, _pressure: f32, rng: &mut dyn RngCore) -> Vec<usize> {
+        self.select(population, generation, count, rng)
+    }
+
+    // true if the selection only makes sense with per-case scores (see Fitness::Cases)
+    fn needs_cases(&self) -> bool {
+        false
+    }
+}
+
+// One rng per pick, seeded from rng, so picks can be made in parallel and still be reproducible.
+fn pick_rngs(rng: &mut dyn RngCore, count: usize) -> Vec<StdRng> {
+    (0..count).map(|_| StdRng::seed_from_u64(rng.gen())).collect()
+}
+
+// Lexicase selection over per-case scores (see Fitness::Cases).
+// The cases are shuffled and the candidates are narrowed down to those that are
+// within epsilon of the best score on each case in turn, a random survivor wins.
+// With no per-case scores this degrades to uniform random selection.
+#[derive(Clone, Default)]
+pub struct Lexicase {
+    pub epsilon: f32,
+}
+
+impl Lexicase {
+    pub fn new(epsilon: f32) -> Self {
+        Lexicase{
+            epsilon,
+        }
+    }
+
+    fn pick<I>(&self, population: &[GradedIndividual<I>], case_count: usize, r: &mut StdRng) -> usize
+    where
+        I: Individual + Send + Sync
+    {
+        let score = |index: usize, case: usize| -> f32 {
+            population[index].scores.get(case).copied().unwrap_or(f32::NEG_INFINITY)
+        };
+
+        let mut cases: Vec<usize> = (0..case_count).collect();
+        cases.shuffle(r);
+
+        let mut candidates: Vec<usize> = (0..population.len()).collect();
+        for case in cases {
+            if candidates.len() <= 1 {
+                break;
+            }
+            let best = candidates.iter().map(|&i| score(i, case)).fold(f32::NEG_INFINITY, f32::max);
+            if best == f32::NEG_INFINITY {
+                // nobody has a usable score for this case
+                continue;
+            }
+            candidates.retain(|&i| score(i, case) >= best - self.epsilon);
+        }
+        candidates[r.gen_range(0..candidates.len())]
+    }
+}
+
+impl<I> Selection<I> for Lexicase
+where
+    I: Individual + Send + Sync
+{
+    fn select(&self, population: &[GradedIndividual<I>], _generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let case_count = population.iter().map(|entry| entry.scores.len()).max().unwrap_or(0);
+        pick_rngs(rng, count).into_par_iter().map(|mut r| self.pick(population, case_count, &mut r)).collect()
+    }
+
+    fn needs_cases(&self) -> bool {
+        true
+    }
+}
+
+// Tournament selection by Pareto dominance over the per-case scores (see
+// GradedIndividual::dominates), for runs where the scores are separate objectives rather than
+// test cases. size candidates are drawn and a random one of those no other candidate
+// dominates wins, so tradeoffs between objectives survive instead of the sum of the scores
+// deciding everything.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParetoTournament {
+    pub size: usize,
+}
+
+impl ParetoTournament {
+    pub fn new(size: usize) -> Self {
+        ParetoTournament{
+            size,
+        }
+    }
+}
+
+impl Default for ParetoTournament {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl<I> Selection<I> for ParetoTournament
+where
+    I: Individual + Send + Sync
+{
+    fn select(&self, population: &[GradedIndividual<I>], _generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        if population.is_empty() {
+            return Vec::new();
+        }
+        pick_rngs(rng, count).into_par_iter().map(|mut r| {
+            let drawn: Vec<usize> = (0..self.size.max(1)).map(|_| r.gen_range(0..population.len())).collect();
+            let front: Vec<usize> = drawn.iter().copied()
+                .filter(|&i| !drawn.iter().any(|&j| population[j].dominates(&population[i])))
+                .collect();
+            front[r.gen_range(0..front.len())]
+        }).collect()
+    }
+
+    fn needs_cases(&self) -> bool {
+        true
+    }
+}
+
+// Boltzmann (softmax) selection, an individual is picked with probability proportional
+// to exp(fitness / T). The temperature T comes from a user supplied schedule indexed by
+// generation, so selection goes from exploratory (high T) to greedy (low T) as it decays.
+#[derive(Clone)]
+pub struct Boltzmann {
+    pub temperature: Schedule,
+}
+
+impl Boltzmann {
+    pub fn new(temperature: Schedule) -> Self {
+        Boltzmann{
+            temperature,
+        }
+    }
+
+    // T = initial * decay^generation
+    pub fn exponential(initial: f32, decay: f32) -> Self {
+        Self::new(Schedule::exponential(initial, decay))
+    }
+}
+
+impl<I> Selection<I> for Boltzmann
+where
+    I: Individual + Send + Sync
+{
+    fn select(&self, population: &[GradedIndividual<I>], generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        if population.is_empty() {
+            return Vec::new();
+        }
+        let temperature = self.temperature.at(generation).max(f32::MIN_POSITIVE);
+        let best = population.iter().map(|entry| entry.fitness).fold(f32::NEG_INFINITY, f32::max);
+        // shift by the best fitness so the largest weight is exp(0) and nothing overflows
+        let weights: Vec<f32> = population.par_iter().map(|entry| ((entry.fitness - best) / temperature).exp()).collect();
+
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => (0..count).map(|_| dist.sample(rng)).collect(),
+            Err(_) => (0..count).map(|_| rng.gen_range(0..population.len())).collect(),
+        }
+    }
+}
+
+// Fitness proportionate (roulette wheel) selection, an individual is picked with probability
+// proportional to its weight. Without a scaler the weight is the fitness shifted so the worst
+// individual is at 0, a scaler (see scaling) can keep early super-fit individuals from taking
+// over and keep up the pressure once fitness has converged.
+#[derive(Clone, Default)]
+pub struct Proportionate {
+    pub scaler: Option<Arc<dyn FitnessScaler>>,
+}
+
+impl Proportionate {
+    pub fn new() -> Self {
+        Proportionate{
+            scaler: None,
+        }
+    }
+
+    pub fn scaled<S>(scaler: S) -> Self
+    where
+        S: FitnessScaler + 'static
+    {
+        Proportionate{
+            scaler: Some(Arc::new(scaler)),
+        }
+    }
+}
+
+impl<I> Selection<I> for Proportionate
+where
+    I: Individual + Send + Sync
+{
+    fn select(&self, population: &[GradedIndividual<I>], _generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        if population.is_empty() {
+            return Vec::new();
+        }
+        let fitness: Vec<f32> = population.iter().map(|entry| entry.fitness).collect();
+        let weights = match &self.scaler {
+            Some(scaler) => scaler.scale(&fitness),
+            None => {
+                let worst = fitness.iter().copied().fold(f32::INFINITY, f32::min);
+                fitness.iter().map(|f| f - worst).collect()
+            },
+        };
+
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => (0..count).map(|_| dist.sample(rng)).collect(),
+            // all weights 0, everyone is as good as everyone else
+            Err(_) => (0..count).map(|_| rng.gen_range(0..population.len())).collect(),
+        }
+    }
+
+    // linear scaling with the best getting pressure times the average weight
+    fn select_with_pressure(&self, population: &[GradedIndividual<I>], generation: usize, count: usize, pressure: f32, rng: &mut dyn RngCore) -> Vec<usize> {
+        Proportionate::scaled(LinearScaling::new(pressure)).select(population, generation, count, rng)
+    }
+}
+
+// Linear ranking: the population is ranked by fitness and the individual of rank i (0 being
+// the best, out of n) is picked with probability (pressure - (2 * pressure - 2) * i / (n - 1)) / n,
+// so the best is expected to be picked pressure times per n picks and the worst 2 - pressure
+// times. Unlike proportionate selection this doesn't care how far apart the fitness values are.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rank {
+    pub pressure: f32,
+}
+
+impl Rank {
+    pub fn new(pressure: f32) -> Self {
+        Rank{
+            pressure,
+        }
+    }
+
+    fn pick(population_size: usize, pressure: f32, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        let n = population_size;
+        match n {
+            0 => return Vec::new(),
+            1 => return vec![0; count],
+            _ => {},
+        }
+        let pressure = pressure.clamp(1.0, 2.0);
+        let weights: Vec<f32> = (0..n).map(|i| pressure - (2.0 * pressure - 2.0) * i as f32 / (n - 1) as f32).collect();
+        match WeightedIndex::new(&weights) {
+            Ok(dist) => (0..count).map(|_| dist.sample(rng)).collect(),
+            Err(_) => (0..count).map(|_| rng.gen_range(0..n)).collect(),
+        }
+    }
+}
+
+impl Default for Rank {
+    fn default() -> Self {
+        Self::new(1.5)
+    }
+}
+
+impl<I> Selection<I> for Rank
+where
+    I: Individual + Send + Sync
+{
+    // ranked explicitly rather than by index, the population may only be partly sorted
+    fn select(&self, population: &[GradedIndividual<I>], generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        self.select_with_pressure(population, generation, count, self.pressure, rng)
+    }
+
+    fn select_with_pressure(&self, population: &[GradedIndividual<I>], _generation: usize, count: usize, pressure: f32, rng: &mut dyn RngCore) -> Vec<usize> {
+        let order = by_fitness(population);
+        Rank::pick(population.len(), pressure, count, rng).into_iter().map(|rank| order[rank]).collect()
+    }
+}
+
+// Tournament selection: each pick is the best of size individuals drawn at random. With a
+// selection pressure set it becomes a binary tournament that the better individual wins with
+// probability pressure / 2, which has the same expected picks per rank as Rank.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tournament {
+    pub size: usize,
+}
+
+impl Tournament {
+    pub fn new(size: usize) -> Self {
+        Tournament{
+            size,
+        }
+    }
+
+    // the fitter of a and b, the lower index on a tie
+    fn better<I>(population: &[GradedIndividual<I>], a: usize, b: usize) -> usize
+    where
+        I: Individual + Send + Sync
+    {
+        match population[a].fitness.total_cmp(&population[b].fitness) {
+            std::cmp::Ordering::Greater => a,
+            std::cmp::Ordering::Less => b,
+            std::cmp::Ordering::Equal => a.min(b),
+        }
+    }
+}
+
+impl Default for Tournament {
+    fn default() -> Self {
+        Self::new(2)
+    }
+}
+
+impl<I> Selection<I> for Tournament
+where
+    I: Individual + Send + Sync
+{
+    // the draw is compared by fitness rather than index, the population may only be partly
+    // sorted
+    fn select(&self, population: &[GradedIndividual<I>], _generation: usize, count: usize, rng: &mut dyn RngCore) -> Vec<usize> {
+        if population.is_empty() {
+            return Vec::new();
+        }
+        (0..count).map(|_| {
+            (0..self.size.max(1)).map(|_| rng.gen_range(0..population.len())).reduce(|a, b| Tournament::better(population, a, b)).unwrap()
+        }).collect()
+    }
+
+    fn select_with_pressure(&self, population: &[GradedIndividual<I>], _generation: usize, count: usize, pressure: f32, rng: &mut dyn RngCore) -> Vec<usize> {
+        if population.is_empty() {
+            return Vec::new();
+        }
+        let win = pressure.clamp(1.0, 2.0) / 2.0;
+        (0..count).map(|_| {
+            let (a, b) = (rng.gen_range(0..population.len()), rng.gen_range(0..population.len()));
+            let better = Tournament::better(population, a, b);
+            let worse = if better == a { b } else { a };
+            if rng.gen::<f32>() < win { better } else { worse }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // selection only looks at fitness
+    #[derive(Clone, Debug)]
+    struct Candidate;
+
+    impl Individual for Candidate {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    const PICKS: usize = 100_000;
+
+    // fitness 4 down to 0 in scrambled order, as SortMode::TopK(0) could leave them, so the
+    // rank of population[i] is RANKS[i]
+    const RANKS: [usize; 5] = [3, 0, 4, 2, 1];
+
+    fn population() -> Vec<GradedIndividual<Candidate>> {
+        RANKS.iter().map(|&rank| GradedIndividual::new(Candidate, 4.0 - rank as f32)).collect()
+    }
+
+    // the fraction of picks that went to each rank
+    fn by_rank(picks: Vec<usize>) -> Vec<f32> {
+        let mut counts = vec![0; RANKS.len()];
+        picks.iter().for_each(|&i| counts[RANKS[i]] += 1);
+        counts.into_iter().map(|count| count as f32 / PICKS as f32).collect()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 0.01), "{0:?} isn't close to {1:?}", actual, expected);
+    }
+
+    fn with_scores(scores: &[[f32; 2]]) -> Vec<GradedIndividual<Candidate>> {
+        scores.iter().map(|scores| {
+            let mut graded = GradedIndividual::new(Candidate, scores.iter().sum());
+            graded.scores = scores.to_vec();
+            graded
+        }).collect()
+    }
+
+    #[test]
+    fn lexicase_picks_specialists_over_a_generalist() {
+        // the generalist has the highest total but isn't the best on any case
+        let population = with_scores(&[[1.0, 0.0], [0.0, 1.0], [0.7, 0.7], [0.5, 0.5]]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Lexicase::new(0.0).select(&population, 0, PICKS, &mut rng);
+        let mut counts = [0; 4];
+        picks.iter().for_each(|&i| counts[i] += 1);
+        assert_eq!(counts[2] + counts[3], 0);
+        assert!((counts[0] as f32 / PICKS as f32 - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn lexicase_keeps_candidates_within_epsilon() {
+        let population = with_scores(&[[1.0, 0.0], [0.0, 1.0], [0.7, 0.7], [0.5, 0.5]]);
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Lexicase::new(0.3).select(&population, 0, PICKS, &mut rng);
+        // the generalist survives whichever case comes first and then wins the other one
+        // outright, the specialists only the case they are best on
+        assert!(picks.iter().all(|&i| i == 2));
+    }
+
+    #[test]
+    fn rank_picks_linearly_by_rank() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Rank::new(2.0).select(&population(), 0, PICKS, &mut rng);
+        // pressure - (2 * pressure - 2) * i / (n - 1), over n
+        assert_close(&by_rank(picks), &[0.4, 0.3, 0.2, 0.1, 0.0]);
+    }
+
+    #[test]
+    fn rank_without_pressure_picks_uniformly() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Rank::new(2.0).select_with_pressure(&population(), 0, PICKS, 1.0, &mut rng);
+        assert_close(&by_rank(picks), &[0.2; 5]);
+    }
+
+    #[test]
+    fn tournament_picks_the_best_of_the_draw() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Tournament::new(2).select(&population(), 0, PICKS, &mut rng);
+        // rank i wins if it is drawn and nothing better is, ((n - i)^2 - (n - i - 1)^2) / n^2
+        assert_close(&by_rank(picks), &[0.36, 0.28, 0.2, 0.12, 0.04]);
+    }
+
+    #[test]
+    fn tournament_with_full_pressure_picks_the_better() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Tournament::new(2).select_with_pressure(&population(), 0, PICKS, 2.0, &mut rng);
+        // the better of the two always wins, as in a tournament of 2
+        assert_close(&by_rank(picks), &[0.36, 0.28, 0.2, 0.12, 0.04]);
+    }
+
+    #[test]
+    fn nothing_is_picked_from_an_empty_population() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let selections: Vec<Box<dyn Selection<Candidate>>> = vec![
+            Box::new(Boltzmann::new(Schedule::constant(1.0))),
+            Box::new(Proportionate::new()),
+            Box::new(Rank::new(1.5)),
+            Box::new(Tournament::new(2)),
+        ];
+        for selection in selections {
+            assert!(selection.select(&[], 0, 10, &mut rng).is_empty());
+        }
+    }
+}
@@ -0,0 +1,165 @@
+// Fitness helpers for byte string and bit string genomes. With the simd feature on x86_64
+// the byte helpers use SSE2 (always available on x86_64) 16 bytes at a time, everywhere
+// else they fall back to plain loops. The bit helpers work on u64 words and count_ones,
+// which already compiles down to popcnt when the target has it.
+
+// Number of positions at which a and b hold different bytes, over the shorter of the two.
+pub fn hamming_distance(a: &[u8], b: &[u8]) -> usize {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        let split = len - len % 16;
+        // safe, SSE2 is part of the x86_64 baseline and the loads are unaligned
+        let head = unsafe { sse2::hamming_distance(&a[..split], &b[..split]) };
+        head + scalar::hamming_distance(&a[split..], &b[split..])
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    {
+        scalar::hamming_distance(a, b)
+    }
+}
+
+// Number of positions at which a and b hold different chars, over the shorter of the two.
+// The counterpart of hamming_distance for strings of Unicode scalar values, which compares
+// 4 chars at a time with the simd feature.
+pub fn char_hamming_distance(a: &[char], b: &[char]) -> usize {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        let split = len - len % 4;
+        let head = unsafe { sse2::char_hamming_distance(&a[..split], &b[..split]) };
+        head + scalar::char_hamming_distance(&a[split..], &b[split..])
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    {
+        scalar::char_hamming_distance(a, b)
+    }
+}
+
+// Sum of |a[i] - b[i]| over the shorter of the two, e.g. the distance of a string from a
+// target phrase.
+pub fn abs_diff_sum(a: &[u8], b: &[u8]) -> u64 {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        let split = len - len % 16;
+        let head = unsafe { sse2::abs_diff_sum(&a[..split], &b[..split]) };
+        head + scalar::abs_diff_sum(&a[split..], &b[split..])
+    }
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    {
+        scalar::abs_diff_sum(a, b)
+    }
+}
+
+// Number of set bits, the OneMax fitness of a bit string.
+pub fn popcount(bits: &[u64]) -> u64 {
+    bits.iter().map(|word| word.count_ones() as u64).sum()
+}
+
+// Number of bits that differ between a and b, over the shorter of the two.
+pub fn bit_hamming_distance(a: &[u64], b: &[u64]) -> u64 {
+    a.iter().zip(b.iter()).map(|(x, y)| (x ^ y).count_ones() as u64).sum()
+}
+
+mod scalar {
+    pub(super) fn hamming_distance(a: &[u8], b: &[u8]) -> usize {
+        a.iter().zip(b.iter()).filter(|(x, y)| x != y).count()
+    }
+
+    pub(super) fn char_hamming_distance(a: &[char], b: &[char]) -> usize {
+        a.iter().zip(b.iter()).filter(|(x, y)| x != y).count()
+    }
+
+    pub(super) fn abs_diff_sum(a: &[u8], b: &[u8]) -> u64 {
+        a.iter().zip(b.iter()).map(|(x, y)| x.abs_diff(*y) as u64).sum()
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod sse2 {
+    use std::arch::x86_64::*;
+
+    // a and b must have the same length, a multiple of 16
+    pub(super) unsafe fn hamming_distance(a: &[u8], b: &[u8]) -> usize {
+        let mut equal = 0;
+        for (x, y) in a.chunks_exact(16).zip(b.chunks_exact(16)) {
+            let x = _mm_loadu_si128(x.as_ptr() as *const __m128i);
+            let y = _mm_loadu_si128(y.as_ptr() as *const __m128i);
+            equal += (_mm_movemask_epi8(_mm_cmpeq_epi8(x, y)) as u32).count_ones() as usize;
+        }
+        a.len() - equal
+    }
+
+    // a and b must have the same length, a multiple of 4. A char is a u32 in memory.
+    pub(super) unsafe fn char_hamming_distance(a: &[char], b: &[char]) -> usize {
+        let mut equal = 0;
+        for (x, y) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+            let x = _mm_loadu_si128(x.as_ptr() as *const __m128i);
+            let y = _mm_loadu_si128(y.as_ptr() as *const __m128i);
+            // 4 mask bits per equal char
+            equal += (_mm_movemask_epi8(_mm_cmpeq_epi32(x, y)) as u32).count_ones() as usize / 4;
+        }
+        a.len() - equal
+    }
+
+    // a and b must have the same length, a multiple of 16
+    pub(super) unsafe fn abs_diff_sum(a: &[u8], b: &[u8]) -> u64 {
+        let mut sum = _mm_setzero_si128();
+        for (x, y) in a.chunks_exact(16).zip(b.chunks_exact(16)) {
+            let x = _mm_loadu_si128(x.as_ptr() as *const __m128i);
+            let y = _mm_loadu_si128(y.as_ptr() as *const __m128i);
+            // two u64 lanes, each the sum of 8 absolute differences
+            sum = _mm_add_epi64(sum, _mm_sad_epu8(x, y));
+        }
+        let mut lanes = [0u64; 2];
+        _mm_storeu_si128(lanes.as_mut_ptr() as *mut __m128i, sum);
+        lanes[0] + lanes[1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // lengths around the 16 byte chunks so both the vector and the scalar tail are covered
+    fn strings(len: usize) -> (Vec<u8>, Vec<u8>) {
+        let a: Vec<u8> = (0..len).map(|i| (i * 7 % 251) as u8).collect();
+        let b: Vec<u8> = (0..len).map(|i| if i % 3 == 0 { (i * 11 % 256) as u8 } else { a[i] }).collect();
+        (a, b)
+    }
+
+    #[test]
+    fn byte_helpers_match_the_plain_loops() {
+        for len in [0, 1, 15, 16, 17, 31, 32, 33, 100] {
+            let (a, b) = strings(len);
+            assert_eq!(hamming_distance(&a, &b), scalar::hamming_distance(&a, &b), "length {0}", len);
+            assert_eq!(abs_diff_sum(&a, &b), scalar::abs_diff_sum(&a, &b), "length {0}", len);
+        }
+    }
+
+    #[test]
+    fn byte_helpers_stop_at_the_shorter_string() {
+        assert_eq!(hamming_distance(b"hello world, again", b"hello"), 0);
+        assert_eq!(abs_diff_sum(b"ab", b"ba!"), 2);
+    }
+
+    #[test]
+    fn char_hamming_distance_matches_the_plain_loop() {
+        let a: Vec<char> = "héllo wörld, ça va? 🦀 ok".chars().collect();
+        let b: Vec<char> = "hello world, ca va? 🦀 ok!".chars().collect();
+        for len in 0..a.len() {
+            assert_eq!(char_hamming_distance(&a[..len], &b), scalar::char_hamming_distance(&a[..len], &b), "length {0}", len);
+        }
+        assert_eq!(char_hamming_distance(&a, &b), 3);
+    }
+
+    #[test]
+    fn bit_helpers() {
+        assert_eq!(popcount(&[0b1011, u64::MAX]), 67);
+        assert_eq!(bit_hamming_distance(&[0b1100, 1], &[0b1010, 1, 7]), 2);
+    }
+}
@@ -0,0 +1,246 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::sync::Arc;
+use crate::constraint::Constraints;
+use crate::{collect_summarized, generation_rng, sample_key, stream_rng, Crossover, Fitness, GradedIndividual, Individual, OptimizationDirection, Population, Rates};
+
+// SPEA2 (Zitzler, Laumanns and Thiele), a multi-objective alternative to evolving with
+// ParetoTournament selection. The objectives are the per-case scores, as everywhere else (see
+// GradedIndividual::objectives).
+//
+// Besides the population an archive of up to archive_size of the best individuals found is
+// kept from one generation to the next. Every generation the population and archive are
+// graded together: an individual's raw fitness is the sum of the strengths (how many others
+// each dominates) of the individuals dominating it, plus a density term from the distance to
+// its k-th nearest neighbour in (normalized) objective space, lower being better. The new
+// archive is every non-dominated individual, topped up with the best dominated ones or
+// thinned by repeatedly dropping the most crowded one, and the next population is bred from
+// it by binary tournament on that fitness.
+pub struct Spea2<I>
+where
+    I: Individual + Send + Sync
+{
+    pub archive_size: usize,
+    // the neighbour the density is taken from, None for the square root of the number of
+    // individuals graded, as in the paper
+    pub k: Option<usize>,
+    pub rates: Rates,
+    pub seed: Option<u64>,
+    // violations are recorded on every individual, so dominance puts feasible ones first
+    pub constraints: Option<Arc<Constraints<I>>>,
+    archive: Vec<GradedIndividual<I>>,
+}
+
+impl<I> Spea2<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new(archive_size: usize) -> Self {
+        Spea2{
+            archive_size: archive_size.max(1),
+            k: None,
+            rates: Rates::new(0.75, 0.8),
+            seed: None,
+            constraints: None,
+            archive: Vec::new(),
+        }
+    }
+
+    // the archive after the last call to evolve, non-dominated individuals first
+    pub fn archive(&self) -> &[GradedIndividual<I>] {
+        &self.archive
+    }
+
+    // Updates the archive from population and breeds the next population of the same size.
+    pub fn evolve<C, F>(&mut self, population: &Population<I>, crossover: &C, fitness: F) -> Population<I>
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let generation = population.generation + 1;
+
+        let mut union: Vec<GradedIndividual<I>> = population.individuals.clone();
+        union.append(&mut self.archive);
+        if let Some(constraints) = &self.constraints {
+            constraints.record(&mut union);
+        }
+        let strength_fitness = self.assign(&union);
+        let archive = self.select_archive(union, strength_fitness);
+        self.archive = archive.iter().map(|(entry, _)| entry.clone()).collect();
+
+        let base: u64 = generation_rng(self.seed, generation).gen();
+        let (mut offspring, summary) = collect_summarized((0..population.individuals.len()).into_par_iter().map(|slot| {
+            let mut r = stream_rng(base, slot);
+            (sample_key(base, slot), self.breed(&archive, crossover, &fitness, population.direction, &mut r))
+        }));
+
+        offspring.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: offspring,
+            generation,
+            summary: Some(summary),
+            mutation_strength: population.mutation_strength,
+            direction: population.direction,
+        }
+    }
+
+    // the SPEA2 fitness of every individual, raw fitness plus density, lower is better
+    fn assign(&self, individuals: &[GradedIndividual<I>]) -> Vec<f32> {
+        let n = individuals.len();
+        let strength: Vec<usize> = individuals.par_iter()
+            .map(|entry| individuals.iter().filter(|other| entry.dominates(other)).count())
+            .collect();
+        let distances = distances(&individuals.iter().map(|entry| entry.objectives()).collect::<Vec<&[f32]>>());
+        let k = self.k.unwrap_or_else(|| (n as f32).sqrt() as usize).min(n.saturating_sub(1));
+        (0..n).into_par_iter().map(|i| {
+            let raw: usize = (0..n).filter(|&j| individuals[j].dominates(&individuals[i])).map(|j| strength[j]).sum();
+            let mut nearest: Vec<f32> = (0..n).filter(|&j| j != i).map(|j| distances[i][j]).collect();
+            let sigma = if nearest.is_empty() {
+                0.0
+            } else {
+                let k = k.min(nearest.len() - 1);
+                *nearest.select_nth_unstable_by(k, |a, b| a.total_cmp(b)).1
+            };
+            raw as f32 + 1.0 / (sigma + 2.0)
+        }).collect()
+    }
+
+    // The next archive with the SPEA2 fitness of its members, the non-dominated individuals
+    // (fitness below 1) first.
+    fn select_archive(&self, individuals: Vec<GradedIndividual<I>>, fitness: Vec<f32>) -> Vec<(GradedIndividual<I>, f32)> {
+        let mut graded: Vec<(GradedIndividual<I>, f32)> = individuals.into_iter().zip(fitness).collect();
+        graded.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let non_dominated = graded.iter().take_while(|(_, f)| *f < 1.0).count();
+        if non_dominated <= self.archive_size {
+            graded.truncate(self.archive_size);
+        } else {
+            graded.truncate(non_dominated);
+            truncate_crowded(&mut graded, self.archive_size);
+        }
+        graded
+    }
+
+    fn breed<C>(&self, archive: &[(GradedIndividual<I>, f32)], crossover: &C, fitness: &Fitness<I>, direction: OptimizationDirection, r: &mut StdRng) -> GradedIndividual<I>
+    where
+        C: Crossover<I> + Send + Sync
+    {
+        let tournament = |r: &mut StdRng| -> &GradedIndividual<I> {
+            let a = &archive[r.gen_range(0..archive.len())];
+            let b = &archive[r.gen_range(0..archive.len())];
+            if a.1 <= b.1 { &a.0 } else { &b.0 }
+        };
+        let parent = tournament(r);
+        let mut child = None;
+        if r.gen::<f32>() < self.rates.crossover {
+            let partner = tournament(r);
+            child = Some(crossover.crossover(&parent.individual, &partner.individual, r));
+        }
+        if child.is_none() || r.gen::<f32>() < self.rates.mutation {
+            let source = child.as_ref().unwrap_or(&parent.individual);
+            child = Some(source.mutate(r));
+        }
+        fitness.grade_for(child.unwrap(), direction)
+    }
+}
+
+// Euclidean distances between every pair of points in objective space, each objective scaled
+// to its range so one with big values doesn't decide the density alone.
+fn distances(points: &[&[f32]]) -> Vec<Vec<f32>> {
+    let dimensions = points.iter().map(|point| point.len()).max().unwrap_or(0);
+    let value = |point: &[f32], d: usize| point.get(d).copied().unwrap_or(0.0);
+    let ranges: Vec<f32> = (0..dimensions).map(|d| {
+        let (low, high) = points.iter().fold((f32::MAX, f32::MIN), |(low, high), point| (low.min(value(point, d)), high.max(value(point, d))));
+        if high > low { high - low } else { 1.0 }
+    }).collect();
+    points.par_iter().map(|&a| {
+        points.iter().map(|&b| {
+            (0..dimensions).map(|d| ((value(a, d) - value(b, d)) / ranges[d]).powi(2)).sum::<f32>().sqrt()
+        }).collect()
+    }).collect()
+}
+
+// Drops the individual closest to its nearest neighbour (ties going to the second nearest and
+// so on) until only size are left, which keeps the spread of the front.
+fn truncate_crowded<I>(graded: &mut Vec<(GradedIndividual<I>, f32)>, size: usize)
+where
+    I: Individual + Send + Sync
+{
+    let distances = distances(&graded.iter().map(|(entry, _)| entry.objectives()).collect::<Vec<&[f32]>>());
+    let mut alive: Vec<usize> = (0..graded.len()).collect();
+    while alive.len() > size.max(1) {
+        let neighbours = |i: usize| -> Vec<f32> {
+            let mut near: Vec<f32> = alive.iter().filter(|&&j| j != i).map(|&j| distances[i][j]).collect();
+            near.sort_by(|a, b| a.total_cmp(b));
+            near
+        };
+        let crowded = (0..alive.len()).min_by(|&a, &b| {
+            let (near_a, near_b) = (neighbours(alive[a]), neighbours(alive[b]));
+            near_a.iter().zip(&near_b).map(|(x, y)| x.total_cmp(y)).find(|order| order.is_ne()).unwrap_or(std::cmp::Ordering::Equal)
+        }).unwrap();
+        alive.remove(crowded);
+    }
+    let mut index = 0;
+    graded.retain(|_| {
+        let keep = alive.binary_search(&index).is_ok();
+        index += 1;
+        keep
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Candidate;
+
+    impl Individual for Candidate {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    fn graded(points: &[(f32, f32)]) -> Vec<GradedIndividual<Candidate>> {
+        points.iter().map(|&(a, b)| GradedIndividual::with_scores(Candidate, vec![a, b])).collect()
+    }
+
+    fn objectives(archive: &[(GradedIndividual<Candidate>, f32)]) -> Vec<Vec<f32>> {
+        archive.iter().map(|(entry, _)| entry.objectives().to_vec()).collect()
+    }
+
+    #[test]
+    fn truncation_drops_the_most_crowded() {
+        // (1, 3) and (1.1, 2.9) are each other's nearest, (1, 3) is the nearer to (0, 4)
+        let front = graded(&[(0.0, 4.0), (1.0, 3.0), (1.1, 2.9), (3.0, 1.0), (4.0, 0.0)]);
+        let mut archive: Vec<(GradedIndividual<Candidate>, f32)> = front.into_iter().map(|entry| (entry, 0.0)).collect();
+        truncate_crowded(&mut archive, 4);
+        assert_eq!(objectives(&archive), vec![vec![0.0, 4.0], vec![1.1, 2.9], vec![3.0, 1.0], vec![4.0, 0.0]]);
+        // the extremes are the last to go
+        truncate_crowded(&mut archive, 2);
+        assert_eq!(objectives(&archive), vec![vec![0.0, 4.0], vec![4.0, 0.0]]);
+    }
+
+    #[test]
+    fn archive_is_topped_up_with_the_best_dominated() {
+        let spea2: Spea2<Candidate> = Spea2::new(3);
+        let individuals = graded(&[(0.0, 0.0), (2.0, 2.0), (1.0, 1.0), (3.0, 3.0)]);
+        let fitness = spea2.assign(&individuals);
+        let archive = spea2.select_archive(individuals, fitness);
+        assert_eq!(objectives(&archive), vec![vec![3.0, 3.0], vec![2.0, 2.0], vec![1.0, 1.0]]);
+        // only the first is non-dominated
+        assert!(archive[0].1 < 1.0 && archive[1].1 >= 1.0);
+    }
+
+    #[test]
+    fn archive_of_too_many_non_dominated_is_truncated() {
+        let spea2: Spea2<Candidate> = Spea2::new(4);
+        let individuals = graded(&[(0.0, 4.0), (1.0, 3.0), (1.1, 2.9), (3.0, 1.0), (4.0, 0.0), (0.5, 0.5)]);
+        let fitness = spea2.assign(&individuals);
+        let archive = spea2.select_archive(individuals, fitness);
+        assert_eq!(archive.len(), 4);
+        assert!(archive.iter().all(|(_, fitness)| *fitness < 1.0));
+        assert!(!objectives(&archive).contains(&vec![1.0, 3.0]));
+    }
+}
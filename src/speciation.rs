@@ -0,0 +1,279 @@
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use crate::niching::Distance;
+use crate::output::{Log, Verbosity};
+use crate::{collect_summarized, generation_rng, sample_key, stream_rng, tournament_child, Crossover, Fitness, GradedIndividual, Individual, OptimizationDirection, Population, Rates};
+
+// A group of similar individuals, tracked from one generation to the next.
+#[derive(Clone)]
+pub struct Species<I> {
+    pub id: usize,
+    // individuals within the threshold of this join the species
+    pub representative: I,
+    // indices of the members in the population last passed to Speciation::evolve
+    pub members: Vec<usize>,
+    // fitness of the best and the mean fitness of the current members
+    pub fitness: f32,
+    pub mean_fitness: f32,
+    // best fitness any member ever had
+    pub best_fitness: f32,
+    // generations since the species was founded
+    pub age: usize,
+    // generations since best_fitness last improved
+    pub stagnant: usize,
+    // offspring allotted to the species for the next generation
+    pub offspring: usize,
+}
+
+// One line per species for reports, as Speciation::log prints every generation.
+impl<I> Display for Species<I> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "species {0}: {1} members, best {2} mean {3}, best ever {4}, age {5}, stagnant {6}, {7} offspring",
+               self.id, self.members.len(), self.fitness, self.mean_fitness, self.best_fitness, self.age, self.stagnant, self.offspring)
+    }
+}
+
+// NEAT-style speciation. Every generation each individual joins the first species whose
+// representative is within threshold of it, or founds a new one. Fitness is shared within
+// each species, so a species gets offspring in proportion to the mean fitness of its
+// members rather than to its size, and a new species isn't crowded out by a large one
+// before it had a chance to improve. Species that stop improving for stagnation
+// generations get no more offspring, unless they hold the best individual.
+//
+// Offspring are bred within their species: the champion is copied over, the rest are
+// children of parents picked by binary tournament from the best survival fraction.
+pub struct Speciation<I> {
+    pub threshold: f32,
+    pub distance: Arc<dyn Distance<I>>,
+    pub stagnation: usize,
+    pub survival: f32,
+    pub rates: Rates,
+    pub seed: Option<u64>,
+    // the species of every generation are printed to it at Verbosity::Generation
+    pub log: Option<Log>,
+    species: Vec<Species<I>>,
+    next_id: usize,
+}
+
+impl<I> Speciation<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new<D>(threshold: f32, distance: D) -> Self
+    where
+        D: Distance<I> + 'static
+    {
+        Speciation{
+            threshold,
+            distance: Arc::new(distance),
+            stagnation: 15,
+            survival: 0.5,
+            rates: Rates::new(0.75, 0.8),
+            seed: None,
+            log: None,
+            species: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    // the species found in the last call to evolve, with their statistics for that generation
+    pub fn species(&self) -> &[Species<I>] {
+        &self.species
+    }
+
+    // Splits the population into species and breeds the next generation of the same size.
+    pub fn evolve<C, F>(&mut self, population: &Population<I>, crossover: &C, fitness: F) -> Population<I>
+    where
+        C: Crossover<I> + Send + Sync,
+        F: Into<Fitness<I>>,
+    {
+        let fitness = fitness.into();
+        let generation = population.generation + 1;
+        let individuals = &population.individuals;
+        self.speciate(individuals);
+        self.allot(individuals);
+        if let Some(log) = self.log.as_ref().filter(|log| log.enabled(Verbosity::Generation)) {
+            log.generation(format_args!("{0}) {1} species", population.generation, self.species.len()));
+            self.species.iter().for_each(|species| log.generation(format_args!("\t{0}", species)));
+        }
+
+        let base: u64 = generation_rng(self.seed, generation).gen();
+        let slots: Vec<(&Species<I>, usize)> = self.species.iter()
+            .flat_map(|species| (0..species.offspring).map(move |slot| (species, slot)))
+            .collect();
+        let (mut offspring, summary) = collect_summarized(slots.into_par_iter().enumerate().map(|(i, (species, slot))| {
+            let mut r = stream_rng(base, i);
+            // members are in fitness order, the first is the champion
+            let champion = &individuals[species.members[0]];
+            let ind = if slot == 0 {
+                champion.clone()
+            } else {
+                self.breed(individuals, species, crossover, &fitness, population.direction, &mut r)
+            };
+            (sample_key(base, i), ind)
+        }));
+
+        offspring.par_sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        Population{
+            individuals: offspring,
+            generation,
+            summary: Some(summary),
+            mutation_strength: population.mutation_strength,
+            direction: population.direction,
+        }
+    }
+
+    fn speciate(&mut self, individuals: &[GradedIndividual<I>]) {
+        for species in self.species.iter_mut() {
+            species.members.clear();
+            species.age += 1;
+        }
+        for (i, entry) in individuals.iter().enumerate() {
+            let found = self.species.iter().position(|species| {
+                self.distance.distance(&species.representative, &entry.individual) < self.threshold
+            });
+            match found {
+                Some(s) => self.species[s].members.push(i),
+                None => {
+                    self.species.push(Species{
+                        id: self.next_id,
+                        representative: entry.individual.clone(),
+                        members: vec![i],
+                        fitness: entry.fitness,
+                        mean_fitness: entry.fitness,
+                        best_fitness: f32::NEG_INFINITY,
+                        age: 0,
+                        stagnant: 0,
+                        offspring: 0,
+                    });
+                    self.next_id += 1;
+                }
+            }
+        }
+        self.species.retain(|species| !species.members.is_empty());
+
+        for species in self.species.iter_mut() {
+            species.members.sort_by(|&a, &b| individuals[b].fitness.total_cmp(&individuals[a].fitness));
+            let champion = &individuals[species.members[0]];
+            species.fitness = champion.fitness;
+            species.mean_fitness = species.members.iter().map(|&i| individuals[i].fitness).sum::<f32>() / species.members.len() as f32;
+            if champion.fitness > species.best_fitness {
+                species.best_fitness = champion.fitness;
+                species.stagnant = 0;
+            } else {
+                species.stagnant += 1;
+            }
+            // the champion stands for the species in the next generation
+            species.representative = champion.individual.clone();
+        }
+    }
+
+    // Shares out individuals.len() offspring by the species' shared fitness, the sum of the
+    // members' fitness divided by the species size. Fitness is shifted so the worst
+    // individual is at 0, so negative fitness works too.
+    fn allot(&mut self, individuals: &[GradedIndividual<I>]) {
+        let size = individuals.len();
+        let lowest = individuals.iter().map(|entry| entry.fitness).fold(f32::INFINITY, f32::min);
+        let best = self.species.iter().map(|species| species.best_fitness).fold(f32::NEG_INFINITY, f32::max);
+        let shares: Vec<f32> = self.species.iter().map(|species| {
+            if species.stagnant >= self.stagnation && species.best_fitness < best {
+                return 0.0;
+            }
+            let total: f32 = species.members.iter().map(|&i| individuals[i].fitness - lowest).sum();
+            total / species.members.len() as f32 + f32::EPSILON
+        }).collect();
+        let total: f32 = shares.iter().sum();
+
+        for (species, share) in self.species.iter_mut().zip(&shares) {
+            species.offspring = (size as f32 * share / total).round() as usize;
+        }
+        // rounding may leave a few too many or too few, settle up with the largest share
+        let allotted: usize = self.species.iter().map(|species| species.offspring).sum();
+        if let Some(largest) = shares.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).map(|(i, _)| i) {
+            let species = &mut self.species[largest];
+            species.offspring = (species.offspring + size).saturating_sub(allotted);
+        }
+    }
+
+    fn breed<C>(&self, individuals: &[GradedIndividual<I>], species: &Species<I>, crossover: &C, fitness: &Fitness<I>, direction: OptimizationDirection, r: &mut StdRng) -> GradedIndividual<I>
+    where
+        C: Crossover<I> + Send + Sync
+    {
+        let survivors: Vec<&GradedIndividual<I>> = species.members[..((species.members.len() as f32 * self.survival).ceil() as usize).max(1)]
+            .iter().map(|&i| &individuals[i]).collect();
+        let (child, _) = tournament_child(&survivors, self.rates, crossover, r);
+        fitness.grade_for(child, direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use crate::ScalarFitness;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Number(f32);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, rng: &mut R) -> Self {
+            Number(self.0 + rng.gen_range(-0.1..0.1))
+        }
+    }
+
+    fn average(a: &Number, b: &Number, _r: &mut dyn RngCore) -> Number {
+        Number((a.0 + b.0) / 2.0)
+    }
+
+    fn value() -> ScalarFitness<Number> {
+        Arc::new(|n: &Number| n.0)
+    }
+
+    fn apart(a: &Number, b: &Number) -> f32 {
+        (a.0 - b.0).abs()
+    }
+
+    // three clusters, of 5 at 10, 4 at 0 and 1 at -10, best first
+    fn clustered() -> Population<Number> {
+        let fitness = Fitness::from(value());
+        let values = [10.0; 5].into_iter().chain([0.0; 4]).chain([-10.0]);
+        Population{
+            individuals: values.map(|x| fitness.grade(Number(x))).collect(),
+            generation: 0,
+            summary: None,
+            mutation_strength: 1.0,
+            direction: OptimizationDirection::Maximize,
+        }
+    }
+
+    fn offspring(speciation: &Speciation<Number>) -> Vec<usize> {
+        speciation.species().iter().map(|species| species.offspring).collect()
+    }
+
+    #[test]
+    fn species_share_offspring_by_mean_fitness() {
+        let mut speciation = Speciation::new(1.0, apart);
+        speciation.seed = Some(1);
+        let next = speciation.evolve(&clustered(), &average, value());
+        let sizes: Vec<usize> = speciation.species().iter().map(|species| species.members.len()).collect();
+        assert_eq!(sizes, vec![5, 4, 1]);
+        // shifted so the worst is at 0, the mean fitness of the clusters is 20, 10 and 0
+        assert_eq!(offspring(&speciation), vec![7, 3, 0]);
+        assert_eq!(next.individuals.len(), 10);
+        // the champions of the species with offspring are copied over
+        for champion in [Number(10.0), Number(0.0)] {
+            assert!(next.individuals.iter().any(|entry| entry.individual == champion));
+        }
+    }
+
+    #[test]
+    fn stagnant_species_get_no_offspring_unless_they_hold_the_best() {
+        let mut speciation = Speciation::new(1.0, apart);
+        speciation.stagnation = 0;
+        speciation.evolve(&clustered(), &average, value());
+        assert_eq!(offspring(&speciation), vec![10, 0, 0]);
+    }
+}
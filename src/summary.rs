@@ -0,0 +1,217 @@
+use rand::Rng;
+use crate::{stream_rng, GradedIndividual, Individual};
+
+// The key the individual bred in slot is sampled by (see Summary::push), from a stream of its
+// own so a seeded run keeps the same samples however rayon splits up the work. seed can be
+// the one the slot was bred with, the key comes from a different stream.
+pub fn sample_key(seed: u64, slot: usize) -> u64 {
+    stream_rng(!seed, slot).gen()
+}
+
+// Uniform sample of at most capacity items out of everything pushed into it: every item comes
+// with a random key and the items with the lowest keys are kept, in key order. Unlike
+// algorithm R nothing random happens while sampling, so the sample only depends on the keys
+// and not on how the pushes were split up between reservoirs and merged.
+#[derive(Clone)]
+struct Reservoir<T> {
+    capacity: usize,
+    keys: Vec<u64>,
+    items: Vec<T>,
+}
+
+impl<T> Reservoir<T> {
+    fn new(capacity: usize) -> Self {
+        Reservoir{
+            capacity,
+            keys: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    // item is only called if the item makes it into the sample
+    fn push<F>(&mut self, key: u64, item: F)
+    where
+        F: FnOnce() -> T
+    {
+        if self.items.len() >= self.capacity {
+            match self.keys.last() {
+                Some(&highest) if key < highest => {
+                    self.keys.pop();
+                    self.items.pop();
+                },
+                _ => return,
+            }
+        }
+        let at = self.keys.partition_point(|&other| other < key);
+        self.keys.insert(at, key);
+        self.items.insert(at, item());
+    }
+
+    // the lowest keys of both, which are the lowest keys of everything pushed into either
+    fn merge(mut self, other: Self) -> Self {
+        for (key, item) in other.keys.into_iter().zip(other.items) {
+            self.push(key, || item);
+        }
+        self
+    }
+}
+
+// Memory bounded statistics over a stream of graded individuals. Counts, extremes, mean and
+// variance are exact, quantiles are estimated from a reservoir sample of the fitness values,
+// and a reservoir sample of the individuals themselves is kept for inspection.
+#[derive(Clone)]
+pub struct Summary<I>
+where
+    I: Individual + Send + Sync
+{
+    pub count: usize,
+    pub min: f32,
+    pub max: f32,
+    mean: f64,
+    m2: f64,
+    individuals: Reservoir<GradedIndividual<I>>,
+    fitness: Reservoir<f32>,
+}
+
+impl<I> Summary<I>
+where
+    I: Individual + Send + Sync
+{
+    pub const DEFAULT_SAMPLE_SIZE: usize = 100;
+    pub const DEFAULT_QUANTILE_SAMPLE_SIZE: usize = 1024;
+
+    pub fn new(sample_size: usize, quantile_sample_size: usize) -> Self {
+        Summary{
+            count: 0,
+            min: f32::INFINITY,
+            max: f32::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            individuals: Reservoir::new(sample_size),
+            fitness: Reservoir::new(quantile_sample_size),
+        }
+    }
+
+    // key decides whether individual is sampled, see sample_key
+    pub fn push(&mut self, individual: &GradedIndividual<I>, key: u64) {
+        let fitness = individual.fitness;
+        self.count += 1;
+        self.min = self.min.min(fitness);
+        self.max = self.max.max(fitness);
+        let delta = fitness as f64 - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (fitness as f64 - self.mean);
+        self.fitness.push(key, || fitness);
+        self.individuals.push(key, || individual.clone());
+    }
+
+    pub fn merge(self, other: Self) -> Self {
+        let count = self.count + other.count;
+        let (mean, m2) = if count == 0 {
+            (0.0, 0.0)
+        } else {
+            let delta = other.mean - self.mean;
+            (
+                self.mean + delta * other.count as f64 / count as f64,
+                self.m2 + other.m2 + delta * delta * (self.count as f64 * other.count as f64) / count as f64,
+            )
+        };
+        Summary{
+            count,
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+            mean,
+            m2,
+            individuals: self.individuals.merge(other.individuals),
+            fitness: self.fitness.merge(other.fitness),
+        }
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean as f32
+    }
+
+    pub fn std_dev(&self) -> f32 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        (self.m2 / self.count as f64).sqrt() as f32
+    }
+
+    // Estimated fitness at quantile q (0.0 is the worst, 1.0 the best), None when empty.
+    pub fn quantile(&self, q: f32) -> Option<f32> {
+        if self.fitness.items.is_empty() {
+            return None;
+        }
+        let mut values = self.fitness.items.clone();
+        values.sort_by(|a, b| a.total_cmp(b));
+        let index = (q.clamp(0.0, 1.0) * (values.len() - 1) as f32).round() as usize;
+        Some(values[index])
+    }
+
+    // uniform sample of the individuals that were pushed
+    pub fn sample(&self) -> &[GradedIndividual<I>] {
+        &self.individuals.items
+    }
+}
+
+impl<I> Default for Summary<I>
+where
+    I: Individual + Send + Sync
+{
+    fn default() -> Self {
+        Summary::new(Self::DEFAULT_SAMPLE_SIZE, Self::DEFAULT_QUANTILE_SAMPLE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::RngCore;
+    use super::*;
+
+    #[derive(Clone, Debug)]
+    struct Number(usize);
+
+    impl Individual for Number {
+        fn mutate<R: Rng>(&self, _rng: &mut R) -> Self {
+            self.clone()
+        }
+    }
+
+    fn summary(slots: std::ops::Range<usize>) -> Summary<Number> {
+        let mut summary = Summary::new(10, 50);
+        slots.for_each(|slot| summary.push(&GradedIndividual::new(Number(slot), slot as f32), sample_key(7, slot)));
+        summary
+    }
+
+    fn sampled(summary: &Summary<Number>) -> Vec<usize> {
+        summary.sample().iter().map(|entry| entry.individual.0).collect()
+    }
+
+    #[test]
+    fn sample_does_not_depend_on_how_the_work_was_split() {
+        let whole = summary(0..1000);
+        let split = summary(0..100).merge(summary(600..1000)).merge(summary(100..600));
+        assert_eq!(sampled(&whole), sampled(&split));
+        assert_eq!(whole.quantile(0.5), split.quantile(0.5));
+        assert_eq!(split.count, 1000);
+        assert_eq!(split.sample().len(), 10);
+    }
+
+    #[test]
+    fn statistics_are_exact() {
+        let summary = summary(0..5).merge(summary(5..10));
+        assert_eq!(summary.count, 10);
+        assert_eq!((summary.min, summary.max), (0.0, 9.0));
+        assert_eq!(summary.mean(), 4.5);
+        assert!((summary.std_dev() - 8.25f32.sqrt()).abs() < 1e-6);
+        // every value fits in the quantile sample
+        assert_eq!(summary.quantile(0.0), Some(0.0));
+        assert_eq!(summary.quantile(1.0), Some(9.0));
+    }
+
+    #[test]
+    fn keys_differ_from_the_slot_streams() {
+        assert_ne!(sample_key(7, 3), stream_rng(7, 3).next_u64());
+    }
+}
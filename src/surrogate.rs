@@ -0,0 +1,95 @@
+use rand::Rng;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use crate::ScalarFitness;
+
+// A cheap approximation of an expensive fitness function, trained on individuals the real
+// function has been run on.
+pub trait SurrogateModel<I>: Send + Sync {
+    fn predict(&self, individual: &I) -> f32;
+
+    // samples is every recent true evaluation, the model is free to start over from them
+    fn train(&mut self, samples: &[(I, f32)]);
+}
+
+// Surrogate assisted evaluation, set as Fitness::Surrogate. A fraction of the individuals
+// graded are evaluated with the real fitness function and the rest only with the model,
+// which is retrained from the latest true evaluations (up to max_samples of them) every
+// refresh generations. Everything is evaluated for real until the model has been trained.
+//
+// Which individuals are evaluated for real is random and not seeded, so seeded runs
+// aren't reproducible with a surrogate. Predicted fitness sticks to an individual for as
+// long as it survives, so the fraction shouldn't be too low.
+pub struct Surrogate<I> {
+    fitness: ScalarFitness<I>,
+    model: RwLock<Box<dyn SurrogateModel<I>>>,
+    pub fraction: f32,
+    pub refresh: usize,
+    pub max_samples: usize,
+    samples: Mutex<Vec<(I, f32)>>,
+    generation: AtomicUsize,
+    trained: AtomicUsize,
+    true_evaluations: AtomicUsize,
+    predictions: AtomicUsize,
+}
+
+impl<I> Surrogate<I>
+where
+    I: Clone + Send + Sync
+{
+    pub fn new<M>(fitness: ScalarFitness<I>, model: M) -> Self
+    where
+        M: SurrogateModel<I> + 'static
+    {
+        Surrogate{
+            fitness,
+            model: RwLock::new(Box::new(model)),
+            fraction: 0.2,
+            refresh: 5,
+            max_samples: 1000,
+            samples: Mutex::new(Vec::new()),
+            generation: AtomicUsize::new(0),
+            trained: AtomicUsize::new(0),
+            true_evaluations: AtomicUsize::new(0),
+            predictions: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn evaluate(&self, individual: &I) -> f32 {
+        if self.trained.load(Ordering::Relaxed) > 0 && rand::thread_rng().gen::<f32>() >= self.fraction {
+            self.predictions.fetch_add(1, Ordering::Relaxed);
+            return self.model.read().unwrap_or_else(|poisoned| poisoned.into_inner()).predict(individual);
+        }
+        let fitness = (self.fitness)(individual);
+        self.true_evaluations.fetch_add(1, Ordering::Relaxed);
+        self.samples.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).push((individual.clone(), fitness));
+        fitness
+    }
+
+    // Retrains the model when it is due, called before each generation is graded.
+    pub(crate) fn new_generation(&self) {
+        let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+        let trained = self.trained.load(Ordering::Relaxed);
+        if trained > 0 && generation - trained < self.refresh.max(1) {
+            return;
+        }
+        let mut samples = self.samples.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if samples.is_empty() {
+            return;
+        }
+        let excess = samples.len().saturating_sub(self.max_samples);
+        samples.drain(..excess);
+        self.model.write().unwrap_or_else(|poisoned| poisoned.into_inner()).train(&samples);
+        self.trained.store(generation, Ordering::Relaxed);
+    }
+
+    // individuals evaluated with the real fitness function so far
+    pub fn true_evaluations(&self) -> usize {
+        self.true_evaluations.load(Ordering::Relaxed)
+    }
+
+    // individuals graded by the model so far
+    pub fn predictions(&self) -> usize {
+        self.predictions.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,264 @@
+use std::time::Duration;
+use crate::budget::GenerationBudget;
+use crate::OptimizationDirection;
+
+// Where a run stands after a generation, what a Termination decides by.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GenerationStats {
+    // generations looked at so far, counting the initial population as the first
+    pub generation: usize,
+    // the best fitness, as graded (so negated when minimizing)
+    pub best: f32,
+    // the mean fitness of what was graded to build the population, as graded, if known
+    pub mean: Option<f32>,
+    pub direction: OptimizationDirection,
+    // since the run started
+    pub elapsed: Duration,
+    // fitness evaluations so far, if they are counted (see budget::Evaluations)
+    pub evaluations: Option<usize>,
+}
+
+impl GenerationStats {
+    // the best fitness as the fitness function returned it
+    pub fn best_objective(&self) -> f32 {
+        self.direction.objective(self.best)
+    }
+}
+
+// Decides when a run stops, checked once per generation by Engine (see Engine::termination)
+// on top of its budget and target. Criteria are combined with and and or, and any
+// FnMut(&GenerationStats) -> bool is one:
+//
+//   engine.termination = Some(Box::new((|stats: &GenerationStats| stats.best >= 0.99).or(GenerationBudget::new(500))));
+pub trait Termination: Send {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool;
+
+    // stops once both do
+    fn and<T>(self, other: T) -> And<Self, T>
+    where
+        Self: Sized,
+        T: Termination,
+    {
+        And(self, other)
+    }
+
+    // stops once either does
+    fn or<T>(self, other: T) -> Or<Self, T>
+    where
+        Self: Sized,
+        T: Termination,
+    {
+        Or(self, other)
+    }
+}
+
+impl<F> Termination for F
+where
+    F: FnMut(&GenerationStats) -> bool + Send
+{
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        self(stats)
+    }
+}
+
+impl Termination for Box<dyn Termination> {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        self.as_mut().should_stop(stats)
+    }
+}
+
+// Both criteria are asked every generation, even once the first says no, so criteria that
+// keep track of the run (stagnation, a budget) see all of it.
+#[derive(Clone, Debug)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A, B> Termination for And<A, B>
+where
+    A: Termination,
+    B: Termination,
+{
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        let a = self.0.should_stop(stats);
+        let b = self.1.should_stop(stats);
+        a && b
+    }
+}
+
+// Both criteria are asked every generation, as for And.
+#[derive(Clone, Debug)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A, B> Termination for Or<A, B>
+where
+    A: Termination,
+    B: Termination,
+{
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        let a = self.0.should_stop(stats);
+        let b = self.1.should_stop(stats);
+        a || b
+    }
+}
+
+// Stops once the best fitness reaches target, or has been at target for sustain
+// generations in a row, e.g. so a run keeps going until an error-free solution has held
+// up for a while rather than stopping on a lucky one. target is as the fitness function
+// returns it, so when minimizing it is reached by going at or below it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TargetFitness {
+    pub target: f32,
+    pub sustain: usize,
+    streak: usize,
+}
+
+impl TargetFitness {
+    pub fn new(target: f32) -> Self {
+        Self::sustained(target, 1)
+    }
+
+    pub fn sustained(target: f32, sustain: usize) -> Self {
+        TargetFitness{
+            target,
+            sustain: sustain.max(1),
+            streak: 0,
+        }
+    }
+
+    // Counts a generation that did or didn't hit the target, for runs that decide that
+    // themselves, returning true once the target has been sustained.
+    pub fn record(&mut self, hit: bool) -> bool {
+        self.streak = if hit { self.streak + 1 } else { 0 };
+        self.streak >= self.sustain
+    }
+
+    // generations in a row the target has been hit
+    pub fn streak(&self) -> usize {
+        self.streak
+    }
+}
+
+impl Termination for TargetFitness {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        // objective turns the target into a graded fitness as well as back
+        self.record(stats.best >= stats.direction.objective(self.target))
+    }
+}
+
+// What Stagnation watches.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Measure {
+    #[default]
+    Best,
+    // the mean of the population's summary, generations without one don't count
+    Mean,
+}
+
+// Stops once the measure hasn't improved by more than epsilon for patience generations.
+// Improvement is judged on graded fitness, so it means going up when maximizing and down
+// when minimizing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stagnation {
+    pub patience: usize,
+    pub epsilon: f32,
+    pub measure: Measure,
+    // the best value so far and generations since it last improved
+    best: Option<f32>,
+    since: usize,
+}
+
+impl Stagnation {
+    pub fn new(patience: usize, epsilon: f32) -> Self {
+        Stagnation{
+            patience: patience.max(1),
+            epsilon,
+            measure: Measure::default(),
+            best: None,
+            since: 0,
+        }
+    }
+
+    // as new, watching the mean rather than the best fitness
+    pub fn of_mean(patience: usize, epsilon: f32) -> Self {
+        Stagnation{
+            measure: Measure::Mean,
+            ..Self::new(patience, epsilon)
+        }
+    }
+
+    // generations since the measure last improved
+    pub fn stagnant_for(&self) -> usize {
+        self.since
+    }
+}
+
+impl Termination for Stagnation {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        let value = match self.measure {
+            Measure::Best => stats.best,
+            Measure::Mean => match stats.mean {
+                Some(mean) => mean,
+                None => return false,
+            },
+        };
+        match self.best {
+            Some(best) if value <= best + self.epsilon => self.since += 1,
+            _ => {
+                self.best = Some(value);
+                self.since = 0;
+            },
+        }
+        self.since >= self.patience
+    }
+}
+
+// Stops once the run has taken limit of wall clock time, for anytime optimization where an
+// answer is needed by a deadline. Checked between generations, so a run overruns by up to a
+// generation, and what it returns is the best of the last one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeLimit {
+    pub limit: Duration,
+}
+
+impl TimeLimit {
+    pub fn new(limit: Duration) -> Self {
+        TimeLimit{
+            limit,
+        }
+    }
+}
+
+impl Termination for TimeLimit {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        stats.elapsed >= self.limit
+    }
+}
+
+// Stops once limit fitness evaluations have been made. Checked between generations, so a
+// run overshoots by up to a generation's worth. Needs the evaluations counted, as Engine
+// does, a run that doesn't count them never stops on this.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MaxEvaluations {
+    pub limit: usize,
+}
+
+impl MaxEvaluations {
+    pub fn new(limit: usize) -> Self {
+        MaxEvaluations{
+            limit,
+        }
+    }
+}
+
+impl Termination for MaxEvaluations {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        stats.evaluations.is_some_and(|evaluations| evaluations >= self.limit)
+    }
+}
+
+// the budget's max_generations, extended while the run is still improving
+impl Termination for GenerationBudget {
+    fn should_stop(&mut self, stats: &GenerationStats) -> bool {
+        self.record(stats.best);
+        self.exhausted(stats.generation)
+    }
+}
@@ -0,0 +1,83 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::{GradedIndividual, Individual};
+
+// Ordered so that the worst individual is at the top of a BinaryHeap.
+struct Ranked<I>(GradedIndividual<I>)
+where
+    I: Individual + Send + Sync;
+
+impl<I> PartialEq for Ranked<I>
+where
+    I: Individual + Send + Sync
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<I> Eq for Ranked<I>
+where
+    I: Individual + Send + Sync
+{}
+
+impl<I> PartialOrd for Ranked<I>
+where
+    I: Individual + Send + Sync
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<I> Ord for Ranked<I>
+where
+    I: Individual + Send + Sync
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.fitness.total_cmp(&self.0.fitness)
+    }
+}
+
+// Keeps the k fittest individuals pushed into it, without holding on to the rest.
+pub(crate) struct TopK<I>
+where
+    I: Individual + Send + Sync
+{
+    k: usize,
+    heap: BinaryHeap<Ranked<I>>,
+}
+
+impl<I> TopK<I>
+where
+    I: Individual + Send + Sync
+{
+    pub(crate) fn new(k: usize) -> Self {
+        TopK{
+            k,
+            heap: BinaryHeap::with_capacity(k),
+        }
+    }
+
+    pub(crate) fn push(&mut self, individual: GradedIndividual<I>) {
+        if self.heap.len() < self.k {
+            self.heap.push(Ranked(individual));
+        } else if let Some(mut worst) = self.heap.peek_mut() {
+            if individual.fitness > worst.0.fitness {
+                *worst = Ranked(individual);
+            }
+        }
+    }
+
+    pub(crate) fn merge(mut self, other: Self) -> Self {
+        for entry in other.heap {
+            self.push(entry.0);
+        }
+        self
+    }
+
+    // best first
+    pub(crate) fn into_sorted_vec(self) -> Vec<GradedIndividual<I>> {
+        self.heap.into_sorted_vec().into_iter().map(|entry| entry.0).collect()
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::Mutex;
+use crate::{Individual, Population, Snapshot, SnapshotStrategy};
+
+// The latest population of a run, for readers on other threads (dashboards, loggers, ...).
+// Set on EvolveConfig::view and a snapshot of every new population is published to it, which
+// readers get with latest. A snapshot is immutable and shared, the lock is only held to swap
+// or clone the pointer, so readers never wait on a generation and never see one half built.
+// By default the whole population is copied each generation, sampled keeps only a sample.
+pub struct PopulationView<I>
+where
+    I: Individual + Send + Sync
+{
+    pub sample: Option<(usize, SnapshotStrategy)>,
+    latest: Mutex<Option<Snapshot<I>>>,
+}
+
+impl<I> PopulationView<I>
+where
+    I: Individual + Send + Sync
+{
+    pub fn new() -> Self {
+        PopulationView{
+            sample: None,
+            latest: Mutex::new(None),
+        }
+    }
+
+    // publishes a sample of n individuals picked by strategy instead of the whole population
+    pub fn sampled(n: usize, strategy: SnapshotStrategy) -> Self {
+        PopulationView{
+            sample: Some((n, strategy)),
+            latest: Mutex::new(None),
+        }
+    }
+
+    pub fn publish(&self, population: &Population<I>) {
+        let (n, strategy) = self.sample.unwrap_or((population.individuals.len(), SnapshotStrategy::Top));
+        let snapshot = population.snapshot(n, strategy);
+        *self.latest.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(snapshot);
+    }
+
+    // the last published snapshot, None until the first
+    pub fn latest(&self) -> Option<Snapshot<I>> {
+        self.latest.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+}
+
+impl<I> Default for PopulationView<I>
+where
+    I: Individual + Send + Sync
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}